@@ -1,119 +1,196 @@
 use lox_ir::{
-    bytecode::{Chunk, Code, CompiledFunction},
+    bytecode::{Chunk, Code, CompiledFunction, LocalVariable},
+    function::{bind_arguments, Function},
     input_file::InputFile,
+    span::Span,
     syntax,
+    word::Word,
 };
 use lox_parse::prelude::FunctionParseExt;
 
+use crate::{
+    globals::{global_variables, GlobalVariables},
+    number::parse_number,
+};
+
 #[salsa::tracked]
 pub fn compile_file(db: &dyn crate::Db, input_file: InputFile) -> CompiledFunction {
     let stmts = lox_parse::parse_file(db, input_file);
     let mut chunk = Chunk::default();
-    let mut compiler = Compiler::default();
+    let mut compiler = Compiler::new(input_file, global_variables(db, input_file), 0);
     for stmt in stmts {
         compiler.compile_stmt(db, stmt, &mut chunk);
     }
     CompiledFunction {
         name: "main".to_string(),
         arity: 0,
+        locals: compiler.finish(&chunk),
         chunk,
     }
 }
 
 #[salsa::tracked]
-pub fn compile_fn(db: &dyn crate::Db, function: lox_ir::function::Function) -> CompiledFunction {
+pub fn compile_fn(db: &dyn crate::Db, function: Function) -> CompiledFunction {
     let stmts = function.parse(db);
 
-    // Whether the scope depth is 0 or not determines the variable type, global or local
-    // The "main" function is a fake function because there is no function named "main" in the source code,
-    // and all the code defined in the global scope is compiled into the "main" function.
-    let scope_depth = if function.name(db).as_str(db) == "main" {
-        0
-    } else {
-        1
-    };
-    let mut compiler = Compiler {
-        scope_depth,
-        ..Default::default()
-    };
+    let input_file = function.body(db).input_file(db);
+    let scope_depth = function_scope_depth(db, function);
+    let mut compiler = Compiler::new(input_file, global_variables(db, input_file), scope_depth);
 
     let mut chunk = Chunk::default();
 
     for param in function.params(db) {
-        let local = Local::new(param.as_str(db), scope_depth);
+        let local = Local::new(param.name.as_str(db), scope_depth, 0);
         compiler.locals.push(local);
     }
+
+    // the parameters that a call gave no argument get their default value first
+    for (index, default) in function.defaults(db).iter().enumerate() {
+        let Some(default) = default else {
+            continue;
+        };
+        let span = default.span;
+        let jump = chunk.emit_byte(
+            Code::JumpIfArgumentGiven {
+                index_in_stack: index,
+                ip: 0,
+            },
+            span,
+        );
+        compiler.compile_expr(db, default, &mut chunk);
+        chunk.emit_byte(
+            Code::WriteLocalVariable {
+                index_in_stack: index,
+            },
+            span,
+        );
+        chunk.emit_byte(Code::Pop, span);
+        compiler.patch_jump(jump, &mut chunk);
+    }
+
     for stmt in stmts {
-        compiler.compile_stmt(db, &stmt, &mut chunk);
+        compiler.compile_stmt(db, stmt, &mut chunk);
     }
 
     CompiledFunction {
         name: function.name(db).as_str(db).to_string(),
         arity: function.params(db).len(),
+        locals: compiler.finish(&chunk),
         chunk,
     }
 }
 
+// Whether the scope depth is 0 or not determines the variable type, global or local
+// The "main" function is a fake function because there is no function named "main" in the source code,
+// and all the code defined in the global scope is compiled into the "main" function.
+pub(crate) fn function_scope_depth(db: &dyn crate::Db, function: Function) -> usize {
+    if function.name(db).as_str(db) == "main" {
+        0
+    } else {
+        1
+    }
+}
+
 struct Local {
     name: String,
     depth: usize,
+
+    // the offset of the first instruction executed after the variable is initialized
+    start: usize,
 }
 
 impl Local {
-    fn new(name: &str, depth: usize) -> Self {
+    fn new(name: &str, depth: usize, start: usize) -> Self {
         Self {
             name: name.to_string(),
             depth,
+            start,
         }
     }
 }
 
-#[derive(Default)]
-struct Compiler {
+struct Compiler<'me> {
+    // the file of the compiled code, that diagnostics point to
+    input_file: InputFile,
+    globals: &'me GlobalVariables,
     locals: Vec<Local>,
     scope_depth: usize,
+
+    // the local variables that went out of scope, kept for debuggers
+    finished_locals: Vec<LocalVariable>,
 }
 
-impl Compiler {
+impl<'me> Compiler<'me> {
+    fn new(input_file: InputFile, globals: &'me GlobalVariables, scope_depth: usize) -> Self {
+        Self {
+            input_file,
+            globals,
+            locals: vec![],
+            scope_depth,
+            finished_locals: vec![],
+        }
+    }
+
+    // returns the debug info of all the local variables of the function, once it is compiled
+    fn finish(mut self, chunk: &Chunk) -> Vec<LocalVariable> {
+        while let Some(local) = self.locals.pop() {
+            self.finish_local(local, chunk.len());
+        }
+        self.finished_locals
+            .sort_by_key(|local| (local.live.start, local.index_in_stack));
+        self.finished_locals
+    }
+
+    // records that `local`, which was just removed from `self.locals`, is no longer live at `end`
+    fn finish_local(&mut self, local: Local, end: usize) {
+        self.finished_locals.push(LocalVariable {
+            name: local.name,
+            index_in_stack: self.locals.len(),
+            live: local.start..end,
+        });
+    }
+
     fn compile_stmt(&mut self, db: &dyn crate::Db, stmt: &syntax::Stmt, chunk: &mut Chunk) {
         tracing::debug!(?stmt, "compiling statement");
-        match stmt {
-            syntax::Stmt::Expr(expr) => {
+        let span = stmt.span;
+        match &stmt.kind {
+            syntax::StmtKind::Expr(expr) => {
                 self.compile_expr(db, expr, chunk);
-                chunk.emit_byte(Code::Pop);
+                chunk.emit_byte(Code::Pop, span);
             }
-            syntax::Stmt::Print(expr) => {
+            syntax::StmtKind::Print(expr) => {
                 self.compile_expr(db, expr, chunk);
-                chunk.emit_byte(Code::Print);
+                chunk.emit_byte(Code::Print, span);
             }
-            syntax::Stmt::VariableDeclaration { name, initializer } => {
+            syntax::StmtKind::VariableDeclaration { name, initializer } => {
                 if let Some(initializer) = initializer {
                     self.compile_expr(db, initializer, chunk);
                 } else {
-                    chunk.emit_byte(Code::Nil);
+                    chunk.emit_byte(Code::Nil, span);
                 }
 
-                let name_str = name.as_str(db);
-
                 // there are two types of variables: global and local, they are compiled differently
                 // they are distinguished by the lexical scope depth
                 if self.scope_depth == 0 {
-                    chunk.emit_byte(Code::GlobalVarDeclaration {
-                        name: name_str.to_string(),
-                    });
+                    chunk.emit_byte(
+                        Code::GlobalVarDeclaration {
+                            slot: self.global_slot(*name),
+                        },
+                        span,
+                    );
                 } else {
-                    let local = Local::new(name_str, self.scope_depth);
+                    let local = Local::new(name.as_str(db), self.scope_depth, chunk.len());
                     self.locals.push(local)
                 }
             }
-            syntax::Stmt::Block(stmts) => {
+            syntax::StmtKind::Block(stmts) => {
                 self.before_scope();
                 for stmt in stmts {
                     self.compile_stmt(db, stmt, chunk);
                 }
-                self.after_scope(chunk);
+                self.after_scope(chunk, span);
             }
-            syntax::Stmt::If {
+            syntax::StmtKind::If {
                 condition,
                 then_branch,
                 else_branch,
@@ -137,17 +214,18 @@ impl Compiler {
 
                 // if the condition is false, jump to the end of the then branch,
                 // but we don't know where the end of the then branch is yet, so we emit a placeholder
-                let jump_to_the_end_of_then_branch = chunk.emit_byte(Code::JumpIfFalse(0));
+                let jump_to_the_end_of_then_branch =
+                    chunk.emit_byte(Code::JumpIfFalse(0), condition.span);
 
                 // this `pop` is only executed if the condition is true,
                 // it pops the value of the condition expression
-                chunk.emit_byte(Code::Pop);
+                chunk.emit_byte(Code::Pop, span);
 
                 self.compile_stmt(db, then_branch, chunk);
 
                 // after executing the then branch, we jump to the end of the else branch,
                 // but we don't know where the end of the else branch is yet, so we emit a placeholder
-                let jump_to_the_end_of_else_branch = chunk.emit_byte(Code::Jump(0));
+                let jump_to_the_end_of_else_branch = chunk.emit_byte(Code::Jump(0), span);
 
                 // after the then branch, we know where the end of the then branch is,
                 // so we can fill in the placeholder
@@ -155,7 +233,7 @@ impl Compiler {
 
                 // this `pop` is only executed if the condition is false,
                 // it pops the value of the condition expression
-                chunk.emit_byte(Code::Pop);
+                chunk.emit_byte(Code::Pop, span);
 
                 if let Some(else_branch) = else_branch {
                     self.compile_stmt(db, else_branch, chunk);
@@ -165,7 +243,7 @@ impl Compiler {
                 // so we can fill in the placeholder
                 self.patch_jump(jump_to_the_end_of_else_branch, chunk);
             }
-            syntax::Stmt::While { condition, body } => {
+            syntax::StmtKind::While { condition, body } => {
                 //         ┌────────────────────┐
                 // ┌─────► │condition expression│
                 // │       └────────────────────┘
@@ -185,16 +263,17 @@ impl Compiler {
 
                 // if the condition is false, jump to the end of the while loop,
                 // but we don't know where the end of the while loop is yet, so we emit a placeholder
-                let jump_to_the_end_of_while_loop = chunk.emit_byte(Code::JumpIfFalse(0));
+                let jump_to_the_end_of_while_loop =
+                    chunk.emit_byte(Code::JumpIfFalse(0), condition.span);
 
                 // this `pop` is only executed if the condition is true,
                 // it pops the value of the condition expression
-                chunk.emit_byte(Code::Pop);
+                chunk.emit_byte(Code::Pop, span);
 
                 self.compile_stmt(db, body, chunk);
 
                 // after executing the body, we jump to the beginning of the condition expression,
-                chunk.emit_byte(Code::Jump(condition_offset));
+                chunk.emit_byte(Code::Jump(condition_offset), span);
 
                 // after compiling the body, we know where the end of the while loop is,
                 // so we can fill in the placeholder
@@ -202,9 +281,9 @@ impl Compiler {
 
                 // this `pop` is only executed if the condition is false,
                 // it pops the value of the condition expression
-                chunk.emit_byte(Code::Pop);
+                chunk.emit_byte(Code::Pop, span);
             }
-            syntax::Stmt::For {
+            syntax::StmtKind::For {
                 initializer,
                 condition,
                 increment,
@@ -239,132 +318,163 @@ impl Compiler {
                     self.compile_expr(db, condition, chunk);
                 } else {
                     // if there is no condition, we treat it as `true`
-                    chunk.emit_byte(Code::True);
+                    chunk.emit_byte(Code::True, span);
                 }
 
                 // if the condition is false, jump to the end of the for loop,
                 // but we don't know where the end of the for loop is yet, so we emit a placeholder
-                let jump_to_the_end_of_for_loop = chunk.emit_byte(Code::JumpIfFalse(0));
+                let jump_to_the_end_of_for_loop = chunk.emit_byte(Code::JumpIfFalse(0), span);
 
                 // this `pop` is only executed if the condition is true,
                 // it pops the value of the condition expression
-                chunk.emit_byte(Code::Pop);
+                chunk.emit_byte(Code::Pop, span);
 
                 self.compile_stmt(db, body, chunk);
 
                 if let Some(increment) = increment {
                     self.compile_expr(db, increment, chunk);
-                    chunk.emit_byte(Code::Pop);
+                    chunk.emit_byte(Code::Pop, span);
                 }
 
                 // after executing the body, we jump to the beginning of the condition expression,
-                chunk.emit_byte(Code::Jump(condition_offset));
+                chunk.emit_byte(Code::Jump(condition_offset), span);
 
                 // after compiling the body, we know where the end of the for loop is,
                 // so we can fill in the placeholder
                 self.patch_jump(jump_to_the_end_of_for_loop, chunk);
 
                 // this for loop is over, so we pop the value of the condition expression
-                chunk.emit_byte(Code::Pop);
+                chunk.emit_byte(Code::Pop, span);
             }
-            syntax::Stmt::FunctionDeclaration(function) => {
-                chunk.emit_byte(Code::Function(*function));
+            syntax::StmtKind::FunctionDeclaration(function) => {
+                chunk.emit_byte(Code::Function(*function), span);
 
-                let name = function.name(db).as_str(db).to_string();
+                let name = function.name(db);
                 // there are two types of variables: global and local, they are compiled differently
                 // they are distinguished by the lexical scope depth
                 if self.scope_depth == 0 {
-                    chunk.emit_byte(Code::GlobalVarDeclaration { name });
+                    chunk.emit_byte(
+                        Code::GlobalVarDeclaration {
+                            slot: self.global_slot(name),
+                        },
+                        span,
+                    );
                 } else {
-                    let local = Local::new(&name, self.scope_depth);
+                    let local = Local::new(name.as_str(db), self.scope_depth, chunk.len());
                     self.locals.push(local)
                 }
             }
-            syntax::Stmt::Return(expr) => {
+            syntax::StmtKind::Return(expr) => {
                 if let Some(expr) = expr {
                     self.compile_expr(db, expr, chunk);
                 } else {
-                    chunk.emit_byte(Code::Nil);
+                    chunk.emit_byte(Code::Nil, span);
                 }
-                chunk.emit_byte(Code::Return);
+                chunk.emit_byte(Code::Return, span);
             }
+            syntax::StmtKind::Error => {}
         }
         tracing::debug!(?stmt, "finished compiling statement");
     }
 
     fn compile_expr(&mut self, db: &dyn crate::Db, expr: &syntax::Expr, chunk: &mut Chunk) {
         tracing::debug!(?expr, "compiling expression");
-        match expr {
-            syntax::Expr::NumberLiteral(word) => {
-                let word_str = word.as_str(db);
-                let value = word_str.parse::<f64>().unwrap();
-                chunk.emit_byte(Code::Constant(value.into()));
+        let span = expr.span;
+        match &expr.kind {
+            syntax::ExprKind::NumberLiteral(word) => {
+                // invalid literals are compiled as `0` so that the other errors are reported
+                let value = parse_number(word.as_str(db)).unwrap_or_else(|error| {
+                    let mut diagnostic =
+                        lox_ir::error!(span.anchor_to(self.input_file), "{}", error.message);
+                    if let Some(help) = error.help {
+                        diagnostic = diagnostic.child(
+                            lox_ir::help!(span.anchor_to(self.input_file), "{}", help).finish(),
+                        );
+                    }
+                    diagnostic.emit(db);
+                    0.0
+                });
+                chunk.emit_byte(Code::Constant(value.into()), span);
             }
-            syntax::Expr::StringLiteral(word) => {
+            syntax::ExprKind::StringLiteral(word) => {
                 let word_str = word.as_str(db);
                 let value = word_str.to_string();
-                chunk.emit_byte(Code::String(value));
+                chunk.emit_byte(Code::String(value), span);
             }
-            syntax::Expr::BooleanLiteral(value) => {
+            syntax::ExprKind::BooleanLiteral(value) => {
                 if *value {
-                    chunk.emit_byte(Code::True);
+                    chunk.emit_byte(Code::True, span);
                 } else {
-                    chunk.emit_byte(Code::False);
+                    chunk.emit_byte(Code::False, span);
                 }
             }
-            syntax::Expr::NilLiteral => todo!(),
-            syntax::Expr::BinaryOp(left, op, right) => {
+            syntax::ExprKind::NilLiteral | syntax::ExprKind::Error => {
+                chunk.emit_byte(Code::Nil, span);
+            }
+            syntax::ExprKind::BinaryOp(left, op, right) => {
                 self.compile_expr(db, left, chunk);
                 self.compile_expr(db, right, chunk);
                 match op {
-                    syntax::Op::Plus => chunk.emit_byte(Code::Add),
-                    syntax::Op::Minus => chunk.emit_byte(Code::Subtract),
-                    syntax::Op::Slash => chunk.emit_byte(Code::Divide),
-                    syntax::Op::Star => chunk.emit_byte(Code::Multiply),
-                    syntax::Op::EqualEqual => chunk.emit_byte(Code::Equal),
-                    syntax::Op::NotEqual => chunk.emit_byte(Code::NotEqual),
-                    syntax::Op::Greater => chunk.emit_byte(Code::Greater),
-                    syntax::Op::GreaterEqual => chunk.emit_byte(Code::GreaterEqual),
-                    syntax::Op::Less => chunk.emit_byte(Code::Less),
-                    syntax::Op::LessEqual => chunk.emit_byte(Code::LessEqual),
+                    syntax::Op::Plus => chunk.emit_byte(Code::Add, span),
+                    syntax::Op::Minus => chunk.emit_byte(Code::Subtract, span),
+                    syntax::Op::Slash => chunk.emit_byte(Code::Divide, span),
+                    syntax::Op::Star => chunk.emit_byte(Code::Multiply, span),
+                    syntax::Op::EqualEqual => chunk.emit_byte(Code::Equal, span),
+                    syntax::Op::NotEqual => chunk.emit_byte(Code::NotEqual, span),
+                    syntax::Op::Greater => chunk.emit_byte(Code::Greater, span),
+                    syntax::Op::GreaterEqual => chunk.emit_byte(Code::GreaterEqual, span),
+                    syntax::Op::Less => chunk.emit_byte(Code::Less, span),
+                    syntax::Op::LessEqual => chunk.emit_byte(Code::LessEqual, span),
                     _ => todo!(),
                 };
             }
-            syntax::Expr::UnaryOp(op, expr) => {
+            syntax::ExprKind::UnaryOp(op, expr) => {
                 self.compile_expr(db, expr, chunk);
                 match op {
-                    syntax::Op::Minus => chunk.emit_byte(Code::Negate),
-                    syntax::Op::Bang => chunk.emit_byte(Code::Not),
+                    syntax::Op::Minus => chunk.emit_byte(Code::Negate, span),
+                    syntax::Op::Bang => chunk.emit_byte(Code::Not, span),
                     _ => todo!(),
                 };
             }
-            syntax::Expr::Parenthesized(_) => todo!(),
-            syntax::Expr::Variable(word) => {
+            syntax::ExprKind::Parenthesized(expr) => self.compile_expr(db, expr, chunk),
+            syntax::ExprKind::Variable(word) => {
                 let name = word.as_str(db);
                 if let Some(index) = self.resolve_local(name) {
-                    chunk.emit_byte(Code::ReadLocalVariable {
-                        index_in_stack: index,
-                    })
+                    chunk.emit_byte(
+                        Code::ReadLocalVariable {
+                            index_in_stack: index,
+                        },
+                        span,
+                    )
                 } else {
-                    chunk.emit_byte(Code::ReadGlobalVariable {
-                        name: name.to_string(),
-                    })
+                    chunk.emit_byte(
+                        Code::ReadGlobalVariable {
+                            slot: self.global_slot(*word),
+                        },
+                        span,
+                    )
                 };
             }
-            syntax::Expr::Assign { name, value } => {
+            syntax::ExprKind::Assign { name, value } => {
                 self.compile_expr(db, value, chunk);
                 let name_str = name.as_str(db);
                 if let Some(index) = self.resolve_local(name_str) {
-                    chunk.emit_byte(Code::WriteLocalVariable {
-                        index_in_stack: index,
-                    })
+                    chunk.emit_byte(
+                        Code::WriteLocalVariable {
+                            index_in_stack: index,
+                        },
+                        span,
+                    )
                 } else {
-                    chunk.emit_byte(Code::WriteGlobalVariable {
-                        name: name_str.to_string(),
-                    })
+                    chunk.emit_byte(
+                        Code::WriteGlobalVariable {
+                            slot: self.global_slot(*name),
+                        },
+                        span,
+                    )
                 };
             }
-            syntax::Expr::LogicalAnd(left, right) => {
+            syntax::ExprKind::LogicalAnd(left, right) => {
                 //      ┌───────────────┐
                 //      │left expression│
                 //      └───────────────┘
@@ -379,10 +489,10 @@ impl Compiler {
                 // if the left branch is false, jump to the end of the right branch,
                 // which means we don't execute the right branch
                 // for example, `false and 1 / 0` will not cause a division by zero error
-                let jump_to_the_end_of_right_branch = chunk.emit_byte(Code::JumpIfFalse(0));
+                let jump_to_the_end_of_right_branch = chunk.emit_byte(Code::JumpIfFalse(0), span);
 
                 // this `pop` is only executed if the left branch is true
-                chunk.emit_byte(Code::Pop);
+                chunk.emit_byte(Code::Pop, span);
 
                 self.compile_expr(db, right, chunk);
 
@@ -390,7 +500,7 @@ impl Compiler {
                 // so we can fill in the placeholder
                 self.patch_jump(jump_to_the_end_of_right_branch, chunk);
             }
-            syntax::Expr::LogicalOr(left, right) => {
+            syntax::ExprKind::LogicalOr(left, right) => {
                 //       ┌───────────────┐
                 //       │left expression│
                 //       └───────────────┘
@@ -402,22 +512,76 @@ impl Compiler {
                 // │     └────────────────┘
                 // └────► continues...
                 self.compile_expr(db, left, chunk);
-                let jump_if_left_is_false = chunk.emit_byte(Code::JumpIfFalse(0));
+                let jump_if_left_is_false = chunk.emit_byte(Code::JumpIfFalse(0), span);
 
                 // if the left branch is true, we don't need to execute the right branch
-                let jump_if_left_is_true = chunk.emit_byte(Code::Jump(0));
+                let jump_if_left_is_true = chunk.emit_byte(Code::Jump(0), span);
                 self.patch_jump(jump_if_left_is_false, chunk);
                 self.compile_expr(db, right, chunk);
                 self.patch_jump(jump_if_left_is_true, chunk);
             }
-            syntax::Expr::Call { callee, arguments } => {
-                self.compile_expr(db, callee, chunk);
-                for arg in arguments {
-                    self.compile_expr(db, arg, chunk);
+            syntax::ExprKind::Call {
+                callee,
+                arguments,
+                names,
+            } => {
+                // calling a property is a method call, the object is the receiver
+                if let syntax::ExprKind::Get { object, name } = &callee.kind {
+                    if !names.is_empty() {
+                        lox_ir::error!(
+                            span.anchor_to(self.input_file),
+                            "methods cannot take named arguments"
+                        )
+                        .primary_label(format!("`{}` is a method", name.as_str(db)))
+                        .emit(db);
+                    }
+                    self.compile_expr(db, object, chunk);
+                    for arg in arguments {
+                        self.compile_expr(db, arg, chunk);
+                    }
+                    chunk.emit_byte(
+                        Code::Invoke {
+                            name: name.as_str(db).to_string(),
+                            arity: arguments.len(),
+                        },
+                        span,
+                    );
+                } else {
+                    self.check_call(db, callee, arguments.len(), names, span);
+                    self.compile_expr(db, callee, chunk);
+                    for arg in arguments {
+                        self.compile_expr(db, arg, chunk);
+                    }
+                    chunk.emit_byte(
+                        Code::Call {
+                            arity: arguments.len(),
+                            names: names.clone(),
+                        },
+                        span,
+                    );
                 }
-                chunk.emit_byte(Code::Call {
-                    arity: arguments.len(),
-                });
+            }
+            syntax::ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    self.compile_expr(db, part, chunk);
+                }
+                chunk.emit_byte(Code::Concat(parts.len()), span);
+            }
+            syntax::ExprKind::Get { object, name } => {
+                self.compile_expr(db, object, chunk);
+                chunk.emit_byte(Code::GetProperty(name.as_str(db).to_string()), span);
+            }
+            syntax::ExprKind::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.compile_expr(db, object, chunk);
+                self.compile_expr(db, value, chunk);
+                chunk.emit_byte(Code::SetProperty(name.as_str(db).to_string()), span);
+            }
+            syntax::ExprKind::Lambda(function) => {
+                chunk.emit_byte(Code::Function(*function), span);
             }
         }
         tracing::debug!(?expr, "finished compiling expression");
@@ -427,14 +591,69 @@ impl Compiler {
         self.scope_depth += 1;
     }
 
-    fn after_scope(&mut self, chunk: &mut Chunk) {
+    fn after_scope(&mut self, chunk: &mut Chunk, span: Span) {
         self.scope_depth -= 1;
         while !self.locals.is_empty() && self.locals.last().unwrap().depth > self.scope_depth {
-            self.locals.pop().unwrap();
-            chunk.emit_byte(Code::Pop);
+            let local = self.locals.pop().unwrap();
+            self.finish_local(local, chunk.len());
+            chunk.emit_byte(Code::Pop, span);
+        }
+    }
+
+    // returns the slot of a global variable, all of them are resolved before compiling
+    fn global_slot(&self, name: Word) -> usize {
+        self.globals
+            .slot(name)
+            .expect("global variable should have been resolved")
+    }
+
+    // Reports the calls at `span` that would fail when they run: the calls of a literal, and
+    // the calls whose arguments do not fit the parameters of a function that is known
+    // statically, an anonymous function or a global function that is never reassigned.
+    fn check_call(
+        &self,
+        db: &dyn crate::Db,
+        callee: &syntax::Expr,
+        arity: usize,
+        names: &[Word],
+        span: Span,
+    ) {
+        let function = match &callee.kind {
+            syntax::ExprKind::NumberLiteral(_) => return self.not_callable(db, "a number", span),
+            syntax::ExprKind::StringLiteral(_) | syntax::ExprKind::Interpolation(_) => {
+                return self.not_callable(db, "a string", span)
+            }
+            syntax::ExprKind::BooleanLiteral(_) => return self.not_callable(db, "a boolean", span),
+            syntax::ExprKind::NilLiteral => return self.not_callable(db, "nil", span),
+            syntax::ExprKind::Lambda(function) => *function,
+            syntax::ExprKind::Variable(name) if self.resolve_local(name.as_str(db)).is_none() => {
+                match self.globals.function(*name) {
+                    Some(function) => function,
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+        // the parameters of a function whose parameter list has syntax errors are incomplete
+        let Some(params_span) = function.params_span(db) else {
+            return;
+        };
+        if let Err(error) = bind_arguments(&function.params(db), arity - names.len(), names) {
+            let name = function.name(db).as_str(db);
+            lox_ir::error!(span.anchor_to(self.input_file), "{}", error.message(db))
+                .primary_label(error.label(name))
+                .secondary_label(params_span, format!("the parameters of `{}`", name))
+                .emit(db);
         }
     }
 
+    // reports that the call at `span` calls a value of type `type_name`, like the VM would
+    fn not_callable(&self, db: &dyn crate::Db, type_name: &str, span: Span) {
+        lox_ir::error!(span.anchor_to(self.input_file), "can only call functions")
+            .primary_label(format!("cannot call {}", type_name))
+            .emit(db);
+    }
+
     // returns the index of the local variable
     fn resolve_local(&self, name: &str) -> Option<usize> {
         for (i, local) in self.locals.iter().enumerate().rev() {
@@ -449,7 +668,8 @@ impl Compiler {
     fn patch_jump(&self, jump: usize, chunk: &mut Chunk) {
         let offset = chunk.len();
         let jump = chunk.read_byte_mut(jump);
-        if let Code::Jump(ip) | Code::JumpIfFalse(ip) = jump {
+        if let Code::Jump(ip) | Code::JumpIfFalse(ip) | Code::JumpIfArgumentGiven { ip, .. } = jump
+        {
             *ip = offset;
         }
     }
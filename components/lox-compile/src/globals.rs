@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use lox_ir::{function::Function, input_file::InputFile, syntax, word::Word};
+use lox_parse::prelude::FunctionParseExt;
+
+use crate::compile::function_scope_depth;
+
+/// The global variables of a module, resolved to numeric slots at compile time.
+///
+/// Every name that may refer to a global variable gets a slot: the names declared
+/// in the global scope, and the names that are read or written without being declared
+/// as a local variable first. The VM stores the values of the global variables in a `Vec`
+/// indexed by slot, the names are only kept around for diagnostics and debugging.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlobalVariables {
+    names: Vec<Word>,
+    slots: HashMap<Word, usize>,
+
+    // the function that each global variable declared by `fun` holds, `None` once the
+    // variable is declared again or assigned
+    functions: HashMap<Word, Option<Function>>,
+}
+
+impl GlobalVariables {
+    /// Returns the slot of the global variable `name`, if any.
+    pub fn slot(&self, name: Word) -> Option<usize> {
+        self.slots.get(&name).copied()
+    }
+
+    /// Returns the name of the global variable stored in `slot`.
+    pub fn name(&self, slot: usize) -> Word {
+        self.names[slot]
+    }
+
+    /// Returns the function that the global variable `name` always holds, if the program
+    /// declares it with a single `fun` declaration and never assigns it.
+    pub fn function(&self, name: Word) -> Option<Function> {
+        self.functions.get(&name).copied().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    fn slot_or_insert(&mut self, name: Word) -> usize {
+        if let Some(slot) = self.slot(name) {
+            return slot;
+        }
+        let slot = self.names.len();
+        self.names.push(name);
+        self.slots.insert(name, slot);
+        slot
+    }
+}
+
+/// Assigns a slot to every global variable of `input_file`, in the order they first appear.
+///
+/// This walks the whole module, including the bodies of all the functions, and mirrors the
+/// way the compiler decides whether a name refers to a local or a global variable.
+#[salsa::tracked(return_ref)]
+pub fn global_variables(db: &dyn crate::Db, input_file: InputFile) -> GlobalVariables {
+    let mut globals = GlobalVariables::default();
+    let mut resolver = Resolver {
+        db,
+        globals: &mut globals,
+        locals: vec![],
+        scope_depth: 0,
+    };
+    for stmt in lox_parse::parse_file(db, input_file) {
+        resolver.resolve_stmt(stmt);
+    }
+    globals
+}
+
+struct Resolver<'me> {
+    db: &'me dyn crate::Db,
+    globals: &'me mut GlobalVariables,
+
+    // the local variables of the function being resolved, with their scope depth
+    locals: Vec<(Word, usize)>,
+    scope_depth: usize,
+}
+
+impl Resolver<'_> {
+    fn resolve_function(&mut self, function: Function) {
+        let scope_depth = function_scope_depth(self.db, function);
+        let mut resolver = Resolver {
+            db: self.db,
+            globals: &mut *self.globals,
+            locals: function
+                .params(self.db)
+                .into_iter()
+                .map(|param| (param.name, scope_depth))
+                .collect(),
+            scope_depth,
+        };
+        for default in function.defaults(self.db).iter().flatten() {
+            resolver.resolve_expr(default);
+        }
+        for stmt in function.parse(self.db) {
+            resolver.resolve_stmt(&stmt);
+        }
+    }
+
+    fn declare(&mut self, name: Word) {
+        if self.scope_depth == 0 {
+            self.globals.slot_or_insert(name);
+            self.globals.functions.insert(name, None);
+        } else {
+            self.locals.push((name, self.scope_depth));
+        }
+    }
+
+    fn declare_function(&mut self, function: Function) {
+        let name = function.name(self.db);
+        if self.scope_depth == 0 {
+            self.globals.slot_or_insert(name);
+            let known = !self.globals.functions.contains_key(&name);
+            self.globals
+                .functions
+                .insert(name, known.then_some(function));
+        } else {
+            self.locals.push((name, self.scope_depth));
+        }
+    }
+
+    fn reference(&mut self, name: Word) {
+        if !self.locals.iter().any(|(local, _)| *local == name) {
+            self.globals.slot_or_insert(name);
+        }
+    }
+
+    fn assign(&mut self, name: Word) {
+        if !self.locals.iter().any(|(local, _)| *local == name) {
+            self.globals.slot_or_insert(name);
+            self.globals.functions.insert(name, None);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &syntax::Stmt) {
+        match &stmt.kind {
+            syntax::StmtKind::Expr(expr) | syntax::StmtKind::Print(expr) => self.resolve_expr(expr),
+            syntax::StmtKind::VariableDeclaration { name, initializer } => {
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.declare(*name);
+            }
+            syntax::StmtKind::Block(stmts) => {
+                self.scope_depth += 1;
+                for stmt in stmts {
+                    self.resolve_stmt(stmt);
+                }
+                self.scope_depth -= 1;
+                self.locals.retain(|(_, depth)| *depth <= self.scope_depth);
+            }
+            syntax::StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            syntax::StmtKind::While { condition, body } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+            }
+            syntax::StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(initializer) = initializer {
+                    self.resolve_stmt(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.resolve_expr(condition);
+                }
+                self.resolve_stmt(body);
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment);
+                }
+            }
+            syntax::StmtKind::FunctionDeclaration(function) => {
+                self.declare_function(*function);
+                self.resolve_function(*function);
+            }
+            syntax::StmtKind::Return(expr) => {
+                if let Some(expr) = expr {
+                    self.resolve_expr(expr);
+                }
+            }
+            syntax::StmtKind::Error => {}
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &syntax::Expr) {
+        match &expr.kind {
+            syntax::ExprKind::NumberLiteral(_)
+            | syntax::ExprKind::StringLiteral(_)
+            | syntax::ExprKind::BooleanLiteral(_)
+            | syntax::ExprKind::NilLiteral
+            | syntax::ExprKind::Error => {}
+            syntax::ExprKind::BinaryOp(left, _, right)
+            | syntax::ExprKind::LogicalAnd(left, right)
+            | syntax::ExprKind::LogicalOr(left, right) => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            syntax::ExprKind::UnaryOp(_, expr) | syntax::ExprKind::Parenthesized(expr) => {
+                self.resolve_expr(expr)
+            }
+            syntax::ExprKind::Variable(name) => self.reference(*name),
+            syntax::ExprKind::Assign { name, value } => {
+                self.resolve_expr(value);
+                self.assign(*name);
+            }
+            syntax::ExprKind::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expr(callee);
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            syntax::ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    self.resolve_expr(part);
+                }
+            }
+            syntax::ExprKind::Get { object, .. } => self.resolve_expr(object),
+            syntax::ExprKind::Set { object, value, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(value);
+            }
+            syntax::ExprKind::Lambda(function) => self.resolve_function(*function),
+        }
+    }
+}
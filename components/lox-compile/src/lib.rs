@@ -1,11 +1,18 @@
 #![feature(trait_upcasting)]
 
 pub mod compile;
+pub mod globals;
+mod number;
 pub use compile::compile_file;
 pub use compile::compile_fn;
+pub use globals::global_variables;
 
 #[salsa::jar(db = Db)]
-pub struct Jar(compile::compile_file, compile::compile_fn);
+pub struct Jar(
+    compile::compile_file,
+    compile::compile_fn,
+    globals::global_variables,
+);
 
 pub trait Db: salsa::DbWithJar<Jar> + lox_ir::Db + lox_parse::Db {}
 impl<T> Db for T where T: salsa::DbWithJar<Jar> + lox_ir::Db + lox_parse::Db {}
@@ -0,0 +1,227 @@
+/// Why a number literal cannot be compiled, reported as a compile error.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NumberError {
+    pub message: String,
+
+    // extra information on the error, emitted as a help
+    pub help: Option<String>,
+}
+
+impl NumberError {
+    fn new(message: impl ToString) -> Self {
+        Self {
+            message: message.to_string(),
+            help: None,
+        }
+    }
+
+    fn with_help(mut self, help: impl ToString) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+}
+
+/// Parses the text of a `Token::Number`, see `lox_lex` for the literals it accumulates.
+///
+/// Lox numbers are `f64`s, so literals that do not fit in an `f64`, or that would silently
+/// lose digits, are errors rather than surprises at runtime.
+pub(crate) fn parse_number(text: &str) -> Result<f64, NumberError> {
+    let (radix, digits) = match text.get(..2) {
+        Some("0x" | "0X") => (16, &text[2..]),
+        Some("0b" | "0B") => (2, &text[2..]),
+        Some("0o" | "0O") => (8, &text[2..]),
+        _ => (10, text),
+    };
+    check_separators(digits, radix)?;
+    let digits: String = digits.chars().filter(|&c| c != '_').collect();
+    if radix == 10 {
+        parse_decimal(&digits)
+    } else {
+        parse_integer(&digits, radix, &text[..2])
+    }
+}
+
+// `_` can only separate two digits, `1__000`, `_1` and `1_.5` are errors
+fn check_separators(digits: &str, radix: u32) -> Result<(), NumberError> {
+    let chars: Vec<char> = digits.chars().collect();
+    let is_digit = |index: Option<usize>| {
+        index
+            .and_then(|index| chars.get(index))
+            .is_some_and(|c| c.is_digit(radix))
+    };
+    for (index, &c) in chars.iter().enumerate() {
+        if c == '_' && !(is_digit(index.checked_sub(1)) && is_digit(Some(index + 1))) {
+            return Err(NumberError::new("`_` must be between two digits"));
+        }
+    }
+    Ok(())
+}
+
+fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        16 => "hexadecimal",
+        _ => "decimal",
+    }
+}
+
+fn parse_integer(digits: &str, radix: u32, prefix: &str) -> Result<f64, NumberError> {
+    if digits.is_empty() {
+        return Err(NumberError::new(format!(
+            "expected digits after `{}`",
+            prefix
+        )));
+    }
+    let mut value: u128 = 0;
+    for c in digits.chars() {
+        let Some(digit) = c.to_digit(radix) else {
+            return Err(NumberError::new(format!(
+                "invalid digit `{}` in {} literal",
+                c,
+                radix_name(radix)
+            )));
+        };
+        value = value
+            .checked_mul(radix as u128)
+            .and_then(|value| value.checked_add(digit as u128))
+            .ok_or_else(|| NumberError::new("integer literal is too large"))?;
+    }
+    let number = value as f64;
+    if number as u128 != value {
+        return Err(not_exact(number));
+    }
+    Ok(number)
+}
+
+fn not_exact(number: f64) -> NumberError {
+    NumberError::new("integer literal cannot be represented exactly")
+        .with_help(format!("the closest number is {}", number))
+}
+
+fn parse_decimal(text: &str) -> Result<f64, NumberError> {
+    let is_digit = |c: char| c.is_ascii_digit();
+    let integer_end = text.find(|c| !is_digit(c)).unwrap_or(text.len());
+    let mut end = integer_end;
+
+    // the lexer only includes a `.` when it is followed by a digit
+    if text[end..].starts_with('.') {
+        end += 1;
+        end += text[end..]
+            .find(|c| !is_digit(c))
+            .unwrap_or(text.len() - end);
+    }
+    let mantissa_end = end;
+
+    if text[end..].starts_with(['e', 'E']) {
+        end += 1;
+        if text[end..].starts_with(['+', '-']) {
+            end += 1;
+        }
+        let exponent_len = text[end..]
+            .find(|c| !is_digit(c))
+            .unwrap_or(text.len() - end);
+        if exponent_len == 0 {
+            return Err(NumberError::new("expected digits in the exponent"));
+        }
+        end += exponent_len;
+    }
+
+    let suffix = &text[end..];
+    if !suffix.is_empty() {
+        return Err(NumberError::new(format!(
+            "invalid suffix `{}` for number literal",
+            suffix
+        )));
+    }
+
+    let number: f64 = text.parse().unwrap();
+    if number.is_infinite() {
+        return Err(NumberError::new("number literal is too large"));
+    }
+    let mantissa = &text[..mantissa_end];
+    if number == 0.0 && mantissa.contains(|c| matches!(c, '1'..='9')) {
+        return Err(NumberError::new(
+            "number literal is too small, it would be 0",
+        ));
+    }
+
+    // integers, unlike fractions, are expected to be exact
+    if end == integer_end {
+        let integer = text.trim_start_matches('0');
+        let integer = if integer.is_empty() { "0" } else { integer };
+        if format!("{:.0}", number) != integer {
+            return Err(not_exact(number));
+        }
+    }
+    Ok(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_number;
+
+    fn error(text: &str) -> String {
+        parse_number(text).unwrap_err().message
+    }
+
+    #[test]
+    fn valid_literals() {
+        assert_eq!(parse_number("0").unwrap(), 0.0);
+        assert_eq!(parse_number("007").unwrap(), 7.0);
+        assert_eq!(parse_number("1_000_000").unwrap(), 1_000_000.0);
+        assert_eq!(parse_number("3.14").unwrap(), 3.14);
+        assert_eq!(parse_number("1e9").unwrap(), 1e9);
+        assert_eq!(parse_number("2.5E-3").unwrap(), 2.5e-3);
+        assert_eq!(parse_number("1e+3").unwrap(), 1000.0);
+        assert_eq!(parse_number("0xff").unwrap(), 255.0);
+        assert_eq!(parse_number("0XdEaD_BEEF").unwrap(), 3735928559.0);
+        assert_eq!(parse_number("0b1010").unwrap(), 10.0);
+        assert_eq!(parse_number("0o777").unwrap(), 511.0);
+        assert_eq!(
+            parse_number("9007199254740992").unwrap(),
+            9007199254740992.0
+        );
+        assert_eq!(parse_number("0.0e5").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn invalid_literals() {
+        assert_eq!(error("12px"), "invalid suffix `px` for number literal");
+        assert_eq!(error("1e"), "expected digits in the exponent");
+        assert_eq!(error("0x"), "expected digits after `0x`");
+        assert_eq!(error("0xfg"), "invalid digit `g` in hexadecimal literal");
+        assert_eq!(error("0b102"), "invalid digit `2` in binary literal");
+        assert_eq!(error("1__000"), "`_` must be between two digits");
+        assert_eq!(error("1_"), "`_` must be between two digits");
+        assert_eq!(error("0x_1"), "`_` must be between two digits");
+        assert_eq!(error("1_.5"), "`_` must be between two digits");
+    }
+
+    #[test]
+    fn overflow_and_precision() {
+        assert_eq!(error("1e400"), "number literal is too large");
+        assert_eq!(
+            error("1e-400"),
+            "number literal is too small, it would be 0"
+        );
+        assert_eq!(
+            error("0x1_0000_0000_0000_0000_0000_0000_0000_0000"),
+            "integer literal is too large"
+        );
+
+        let error = parse_number("9007199254740993").unwrap_err();
+        assert_eq!(
+            error.message,
+            "integer literal cannot be represented exactly"
+        );
+        assert_eq!(
+            error.help.as_deref(),
+            Some("the closest number is 9007199254740992")
+        );
+        assert_eq!(
+            parse_number("0x20000000000001").unwrap_err().message,
+            "integer literal cannot be represented exactly"
+        );
+    }
+}
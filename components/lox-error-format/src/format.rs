@@ -1,7 +1,11 @@
 use std::{collections::HashMap, io::Cursor};
 
-use ariadne::{Config, Label, Report, ReportKind, Source};
-use lox_ir::input_file::InputFile;
+use ariadne::{Color, Config, Fmt, Label, Report, ReportKind, Source};
+use lox_ir::{
+    diagnostic::{Diagnostic, Severity},
+    input_file::InputFile,
+    span::FileSpan,
+};
 
 /// Options for controlling error formatting when they are printed.
 #[derive(Clone, Copy)]
@@ -23,7 +27,11 @@ pub fn print_diagnostic(
     db: &dyn crate::Db,
     diagnostic: &lox_ir::diagnostic::Diagnostic,
 ) -> eyre::Result<()> {
-    Ok(ariadne_diagnostic(db, diagnostic, DEFAULT_FORMATTING)?.print(SourceCache::new(db))?)
+    let mut cache = SourceCache::new(db);
+    for report in ariadne_reports(db, diagnostic, DEFAULT_FORMATTING) {
+        report.print(&mut cache)?;
+    }
+    Ok(())
 }
 
 pub fn format_diagnostics(
@@ -42,30 +50,127 @@ pub fn format_diagnostics_with_options(
     let mut cursor = Cursor::new(&mut output);
     let mut cache = SourceCache::new(db);
     for diagnostic in diagnostics {
-        let ariadne = ariadne_diagnostic(db, diagnostic, options)?;
-        ariadne.write(&mut cache, &mut cursor)?;
+        for report in ariadne_reports(db, diagnostic, options) {
+            report.write(&mut cache, &mut cursor)?;
+        }
     }
     Ok(String::from_utf8(output)?)
 }
 
-fn ariadne_diagnostic<'a>(
-    _db: &'a dyn crate::Db,
-    diagnostic: &'a lox_ir::diagnostic::Diagnostic,
+fn report_kind(severity: Severity, options: FormatOptions) -> ReportKind<'static> {
+    // unlike the built-in kinds, custom kinds are colored even when colors are disabled
+    let color = |color| {
+        if options.with_color {
+            color
+        } else {
+            Color::Unset
+        }
+    };
+    match severity {
+        Severity::Error => ReportKind::Error,
+        Severity::Warning => ReportKind::Warning,
+        Severity::Note => ReportKind::Custom("Note", color(Color::Blue)),
+        Severity::Help => ReportKind::Custom("Help", color(Color::Cyan)),
+    }
+}
+
+// Builds the report of `diagnostic`, followed by the reports of its error and warning children.
+//
+// The note and help children on the span of the diagnostic become its note and help footers,
+// the ones elsewhere become labels on their own span.
+fn ariadne_reports<'d>(
+    db: &dyn crate::Db,
+    diagnostic: &'d Diagnostic,
     options: FormatOptions,
-) -> eyre::Result<ariadne::Report<'a, ASpan>> {
+) -> Vec<Report<'d, ASpan>> {
     let mut builder = Report::<ASpan>::build(
-        ReportKind::Error,
+        report_kind(diagnostic.severity, options),
         diagnostic.span.input_file,
         diagnostic.span.start.into(),
     )
     .with_message(&diagnostic.message)
     .with_config(Config::default().with_color(options.with_color));
 
+    let mut label_spans = vec![];
     for label in &diagnostic.labels {
+        label_spans.push(label.span());
         builder = builder.with_label(Label::new(ASpan(label.span())).with_message(&label.message));
     }
 
-    Ok(builder.finish())
+    let mut notes = vec![];
+    let mut help = vec![];
+    let mut nested_reports = vec![];
+    for child in &diagnostic.children {
+        let footer = match child.severity {
+            Severity::Note => &mut notes,
+            Severity::Help => &mut help,
+            Severity::Warning | Severity::Error => {
+                nested_reports.extend(ariadne_reports(db, child, options));
+                continue;
+            }
+        };
+        if child.span == diagnostic.span {
+            footer.push(child.message.as_str());
+        } else {
+            let message = format!("{}: {}", report_kind_name(child.severity), child.message);
+            label_spans.push(child.span);
+            builder = builder.with_label(Label::new(ASpan(child.span)).with_message(message));
+        }
+    }
+
+    // ariadne only supports a single note and a single help per report, and prints the lines
+    // after the first one of each at the start of the line, so we draw their margin ourselves
+    let continuation = footer_continuation(db, &label_spans, options);
+    if !notes.is_empty() {
+        builder = builder.with_note(footer_lines(&notes).join(&continuation));
+    }
+    if !help.is_empty() {
+        builder = builder.with_help(footer_lines(&help).join(&continuation));
+    }
+
+    let mut reports = vec![builder.finish()];
+    reports.extend(nested_reports);
+    reports
+}
+
+fn footer_lines<'d>(messages: &[&'d str]) -> Vec<&'d str> {
+    messages
+        .iter()
+        .flat_map(|message| message.lines())
+        .collect()
+}
+
+// Starts a new line of a note or help footer: the margin of the report, as ariadne draws it on
+// the lines without a line number, and the width of `Note: ` so that the text lines up.
+fn footer_continuation(db: &dyn crate::Db, spans: &[FileSpan], options: FormatOptions) -> String {
+    // ariadne makes room for the largest line number among the labels
+    let line_no_width = spans
+        .iter()
+        .map(|span| {
+            let line_index = lox_ir::line_index::line_index(db, span.input_file);
+            let last = usize::from(span.end)
+                .saturating_sub(1)
+                .max(span.start.into());
+            line_index.line(last.into()) + 1
+        })
+        .max()
+        .map_or(0, |last_line| last_line.to_string().len());
+    let margin = format!("{}│", " ".repeat(line_no_width + 2));
+    let margin_color = options.with_color.then_some(Color::Fixed(246));
+    format!(
+        "\n{} {}",
+        margin.fg(margin_color),
+        " ".repeat("Note: ".len())
+    )
+}
+
+fn report_kind_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    }
 }
 
 struct SourceCache<'me> {
@@ -113,3 +218,206 @@ impl ariadne::Span for ASpan {
         self.0.end.into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+    use lox_ir::{
+        diagnostic::Diagnostic,
+        input_file::InputFile,
+        span::{FileSpan, Span},
+        word::Word,
+    };
+
+    use crate::{format_diagnostics_with_options, FormatOptions};
+
+    #[salsa::db(lox_ir::Jar, crate::Jar)]
+    #[derive(Default)]
+    struct Database {
+        storage: salsa::Storage<Self>,
+    }
+
+    impl salsa::Database for Database {}
+
+    impl lox_ir::Db for Database {}
+
+    struct Files {
+        main: InputFile,
+        lib: InputFile,
+    }
+
+    fn files(db: &Database) -> Files {
+        Files {
+            main: InputFile::new(
+                db,
+                Word::intern(db, "main.lox"),
+                "var answer = 42;\nprint answer + nil;\n".to_string(),
+            ),
+            lib: InputFile::new(
+                db,
+                Word::intern(db, "lib.lox"),
+                "fun helper() {\n    return nil;\n}\n".to_string(),
+            ),
+        }
+    }
+
+    fn span(input_file: InputFile, start: usize, end: usize) -> FileSpan {
+        Span::from(start, end).anchor_to(input_file)
+    }
+
+    fn render(db: &Database, diagnostics: &[Diagnostic]) -> String {
+        format_diagnostics_with_options(db, diagnostics, FormatOptions::no_color()).unwrap()
+    }
+
+    #[test]
+    fn severities() {
+        let db = Database::default();
+        let files = files(&db);
+        let answer = span(files.main, 4, 10);
+        let diagnostics = [
+            lox_ir::warning!(answer, "variable `answer` is never reassigned").finish(),
+            lox_ir::note!(answer, "`answer` is a global variable").finish(),
+            lox_ir::help!(answer, "declare it inside a block to make it local").finish(),
+        ];
+        let expected = expect![[r#"
+            Warning: variable `answer` is never reassigned
+               ╭─[main.lox:1:5]
+               │
+             1 │ var answer = 42;
+               │     ───┬──  
+               │        ╰──── here
+            ───╯
+            Note: `answer` is a global variable
+               ╭─[main.lox:1:5]
+               │
+             1 │ var answer = 42;
+               │     ───┬──  
+               │        ╰──── here
+            ───╯
+            Help: declare it inside a block to make it local
+               ╭─[main.lox:1:5]
+               │
+             1 │ var answer = 42;
+               │     ───┬──  
+               │        ╰──── here
+            ───╯
+        "#]];
+        expected.assert_eq(&render(&db, &diagnostics));
+    }
+
+    #[test]
+    fn notes_and_help_on_the_same_span() {
+        let db = Database::default();
+        let files = files(&db);
+        let addition = span(files.main, 23, 35);
+        let diagnostic = lox_ir::error!(addition, "operands must be two numbers or two strings")
+            .primary_label("cannot add a number and nil")
+            .child(lox_ir::note!(addition, "`nil` is not a number").finish())
+            .child(lox_ir::note!(addition, "neither is it a string").finish())
+            .child(lox_ir::help!(addition, "give the variable a value before using it").finish())
+            .finish();
+        let expected = expect![[r#"
+            Error: operands must be two numbers or two strings
+               ╭─[main.lox:2:7]
+               │
+             2 │ print answer + nil;
+               │       ──────┬─────  
+               │             ╰─────── cannot add a number and nil
+               │ 
+               │ Help: give the variable a value before using it
+               │ 
+               │ Note: `nil` is not a number
+               │       neither is it a string
+            ───╯
+        "#]];
+        expected.assert_eq(&render(&db, &[diagnostic]));
+    }
+
+    #[test]
+    fn notes_and_help_elsewhere() {
+        let db = Database::default();
+        let files = files(&db);
+        let diagnostic = lox_ir::error!(
+            span(files.main, 23, 35),
+            "operands must be two numbers or two strings"
+        )
+        .primary_label("cannot add a number and nil")
+        .child(lox_ir::note!(span(files.main, 4, 10), "`answer` is declared here").finish())
+        .child(lox_ir::help!(span(files.main, 32, 35), "replace `nil` with a number").finish())
+        .finish();
+        let expected = expect![[r#"
+            Error: operands must be two numbers or two strings
+               ╭─[main.lox:2:7]
+               │
+             1 │ var answer = 42;
+               │     ───┬──  
+               │        ╰──── note: `answer` is declared here
+             2 │ print answer + nil;
+               │       ──────┬───┬─  
+               │             ╰─────── cannot add a number and nil
+               │                 │   
+               │                 ╰─── help: replace `nil` with a number
+            ───╯
+        "#]];
+        expected.assert_eq(&render(&db, &[diagnostic]));
+    }
+
+    #[test]
+    fn label_in_another_file() {
+        let db = Database::default();
+        let files = files(&db);
+        let diagnostic = lox_ir::error!(span(files.main, 23, 29), "`answer` shadows a function")
+            .primary_label("here")
+            .secondary_label(span(files.lib, 4, 10), "the function is defined here")
+            .finish();
+        let expected = expect![[r#"
+            Error: `answer` shadows a function
+               ╭─[main.lox:2:7]
+               │
+             2 │ print answer + nil;
+               │       ───┬──  
+               │          ╰──── here
+               │
+               ├─[lib.lox:1:5]
+               │
+             1 │ fun helper() {
+               │     ───┬──  
+               │        ╰──── the function is defined here
+            ───╯
+        "#]];
+        expected.assert_eq(&render(&db, &[diagnostic]));
+    }
+
+    #[test]
+    fn error_and_warning_children() {
+        let db = Database::default();
+        let files = files(&db);
+        let diagnostic = lox_ir::error!(
+            span(files.main, 23, 35),
+            "operands must be two numbers or two strings"
+        )
+        .child(
+            lox_ir::warning!(span(files.lib, 26, 29), "`helper` returns nil")
+                .primary_label("this is returned")
+                .finish(),
+        )
+        .finish();
+        let expected = expect![[r#"
+            Error: operands must be two numbers or two strings
+               ╭─[main.lox:2:7]
+               │
+             2 │ print answer + nil;
+               │       ──────┬─────  
+               │             ╰─────── here
+            ───╯
+            Warning: `helper` returns nil
+               ╭─[lib.lox:2:12]
+               │
+             2 │     return nil;
+               │            ─┬─  
+               │             ╰─── this is returned
+            ───╯
+        "#]];
+        expected.assert_eq(&render(&db, &[diagnostic]));
+    }
+}
@@ -3,6 +3,7 @@
 #![allow(incomplete_features)]
 
 mod format;
+mod structured;
 
 #[salsa::jar(db = Db)]
 pub struct Jar();
@@ -14,3 +15,5 @@ pub use format::format_diagnostics;
 pub use format::format_diagnostics_with_options;
 pub use format::print_diagnostic;
 pub use format::FormatOptions;
+pub use structured::format_diagnostics_json;
+pub use structured::format_diagnostics_sarif;
@@ -0,0 +1,157 @@
+use lox_ir::{
+    diagnostic::{Diagnostic, Severity},
+    line_index::{line_index, ColumnUnit},
+    span::FileSpan,
+};
+use serde_json::{json, Value};
+
+// The lines and columns are 1-based, `column` counts characters and `utf16Column` counts
+// UTF-16 code units, like language servers. The end of a range is exclusive.
+fn range(db: &dyn crate::Db, span: FileSpan) -> Value {
+    let line_index = line_index(db, span.input_file);
+    let position = |offset| {
+        let (line, column) = line_index.line_column(offset);
+        let (_, utf16_column) = line_index.line_column_in(offset, ColumnUnit::Utf16);
+        json!({ "line": line + 1, "column": column + 1, "utf16Column": utf16_column + 1 })
+    };
+    json!({ "start": position(span.start), "end": position(span.end) })
+}
+
+fn file_name(db: &dyn crate::Db, span: FileSpan) -> &str {
+    span.input_file.name(db).as_str(db)
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Help => "help",
+        Severity::Note => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+fn json_diagnostic(db: &dyn crate::Db, diagnostic: &Diagnostic) -> Value {
+    let labels: Vec<Value> = diagnostic
+        .labels
+        .iter()
+        .map(|label| {
+            json!({
+                "message": label.message,
+                "file": file_name(db, label.span),
+                "range": range(db, label.span),
+            })
+        })
+        .collect();
+    let children: Vec<Value> = diagnostic
+        .children
+        .iter()
+        .map(|child| json_diagnostic(db, child))
+        .collect();
+    json!({
+        "severity": severity_name(diagnostic.severity),
+        "message": diagnostic.message,
+        "file": file_name(db, diagnostic.span),
+        "range": range(db, diagnostic.span),
+        "labels": labels,
+        "children": children,
+    })
+}
+
+/// Renders the diagnostics as a JSON array, for tools that read the diagnostics of `lox check`.
+///
+/// Each diagnostic has its severity, message, file and range, its labels and its children,
+/// which are diagnostics themselves. Ranges have a 1-based `line`, `column` and `utf16Column`
+/// for their `start` and `end`, `column` counts characters and `utf16Column` counts UTF-16
+/// code units, and the end is exclusive.
+pub fn format_diagnostics_json(db: &dyn crate::Db, diagnostics: &[Diagnostic]) -> String {
+    let diagnostics: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| json_diagnostic(db, diagnostic))
+        .collect();
+    serde_json::to_string_pretty(&diagnostics).unwrap()
+}
+
+fn sarif_location(db: &dyn crate::Db, span: FileSpan, message: Option<&str>) -> Value {
+    let line_index = line_index(db, span.input_file);
+    let (start_line, start_column) = line_index.line_column(span.start);
+    let (end_line, end_column) = line_index.line_column(span.end);
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": file_name(db, span) },
+            "region": {
+                "startLine": start_line + 1,
+                "startColumn": start_column + 1,
+                "endLine": end_line + 1,
+                "endColumn": end_column + 1,
+            },
+        },
+    });
+    if let Some(message) = message {
+        location["message"] = json!({ "text": message });
+    }
+    location
+}
+
+// SARIF has no help severity, and no nested results: the labels that are not on the span of
+// the diagnostic and the children become related locations.
+fn sarif_result(db: &dyn crate::Db, diagnostic: &Diagnostic) -> Value {
+    let level = match diagnostic.severity {
+        Severity::Help | Severity::Note => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+
+    // the labels on the span of the diagnostic add to its message
+    let mut text = diagnostic.message.clone();
+    for label in &diagnostic.labels {
+        if label.span == diagnostic.span && label.message != "here" {
+            text.push_str(": ");
+            text.push_str(&label.message);
+        }
+    }
+
+    let mut related = vec![];
+    for label in &diagnostic.labels {
+        if label.span != diagnostic.span {
+            related.push(sarif_location(db, label.span, Some(&label.message)));
+        }
+    }
+    for child in &diagnostic.children {
+        let message = format!("{}: {}", severity_name(child.severity), child.message);
+        related.push(sarif_location(db, child.span, Some(&message)));
+    }
+    for (id, location) in related.iter_mut().enumerate() {
+        location["id"] = json!(id);
+    }
+
+    json!({
+        "level": level,
+        "message": { "text": text },
+        "locations": [sarif_location(db, diagnostic.span, None)],
+        "relatedLocations": related,
+    })
+}
+
+/// Renders the diagnostics as a SARIF 2.1.0 log with a single run, for code scanning tools.
+pub fn format_diagnostics_sarif(db: &dyn crate::Db, diagnostics: &[Diagnostic]) -> String {
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| sarif_result(db, diagnostic))
+        .collect();
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "lox",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            },
+            // offsets count characters, not UTF-16 code units
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&log).unwrap()
+}
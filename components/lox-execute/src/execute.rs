@@ -1,7 +1,10 @@
-use lox_ir::{bytecode, input_file::InputFile};
+use lox_error_format::FormatOptions;
+use lox_ir::input_file::InputFile;
 
 use crate::{
     kernel::Kernel,
+    limits::ExecutionLimits,
+    observer::Observer,
     vm::{ControlFlow, VM},
 };
 
@@ -9,18 +12,61 @@ use crate::{
 pub fn main_function(db: &dyn crate::Db, input_file: InputFile) -> lox_ir::function::Function {
     let tree = lox_lex::lex_file(db, input_file);
     let name = lox_ir::word::Word::new(db, "main".to_string());
-    lox_ir::function::Function::new(db, name, vec![], tree)
+    let body = lox_ir::function::FunctionBody::Block(tree);
+    lox_ir::function::Function::new(db, name, vec![], body)
 }
 
-pub fn execute_file(
+/// Runs `input_file` to completion while `observer` follows it, and returns the VM so that it
+/// can be inspected afterwards.
+pub fn execute_file<K: Kernel>(
     db: &impl crate::Db,
     input_file: InputFile,
-    kernel: &mut impl Kernel,
+    kernel: &mut K,
     diagnostic_with_color: bool,
-    step_inspect: Option<impl FnMut(Option<bytecode::Code>, &VM) + Clone>,
-) {
+    observer: &mut impl Observer<K>,
+) -> VM {
+    execute_file_with_limits(
+        db,
+        input_file,
+        kernel,
+        diagnostic_with_color,
+        ExecutionLimits::default(),
+        observer,
+    )
+}
+
+/// Like [`execute_file`], but stops the program as soon as it exceeds one of `limits`,
+/// and prints a diagnostic explaining why through the `kernel`.
+pub fn execute_file_with_limits<K: Kernel>(
+    db: &impl crate::Db,
+    input_file: InputFile,
+    kernel: &mut K,
+    diagnostic_with_color: bool,
+    limits: ExecutionLimits,
+    observer: &mut impl Observer<K>,
+) -> VM {
     let main = main_function(db, input_file);
-    let mut vm = VM::new(db, main, diagnostic_with_color);
+    let mut vm = VM::new(db, main, diagnostic_with_color).with_limits(limits);
+
+    loop {
+        match vm.run_with(db, kernel, observer) {
+            // the program runs to completion, even if the observer pauses it
+            Ok(ControlFlow::Next) => {}
+            Ok(ControlFlow::Done) => break,
+            Err(diagnostic) => {
+                let output = lox_error_format::format_diagnostics_with_options(
+                    db,
+                    &[diagnostic],
+                    FormatOptions {
+                        with_color: diagnostic_with_color,
+                    },
+                )
+                .unwrap();
+                kernel.print(&output);
+                break;
+            }
+        }
+    }
 
-    while let ControlFlow::Next = vm.step(db, kernel, step_inspect.clone()) {}
+    vm
 }
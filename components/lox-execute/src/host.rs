@@ -0,0 +1,172 @@
+use std::any::Any;
+
+use crate::value::Value;
+
+/// A Rust value exposed to lox programs, like a logger or a configuration object.
+///
+/// Programs read and write its properties with `object.name` and `object.name = value`, and
+/// call its methods with `object.name(arguments)`. The `Err` messages of these functions are
+/// reported to the program as runtime errors.
+///
+/// Host objects are shared between the program and the host as [`Value::Host`], create one
+/// with [`Value::host`] and register it as a global variable, see
+/// [`crate::interpreter::InterpreterBuilder::global`]. Hosts that get a [`Value::Host`] back can
+/// downcast it to the type they created through [`Any`].
+pub trait HostObject: Any {
+    /// The name of the type of the object, used in error messages and when it is printed.
+    fn type_name(&self) -> &str;
+
+    /// Returns the value of the property `name`.
+    fn get(&self, name: &str) -> Result<Value, String> {
+        Err(format!("{} has no property `{}`", self.type_name(), name))
+    }
+
+    /// Sets the property `name` to `value`.
+    fn set(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let _ = value;
+        Err(format!(
+            "cannot set property `{}` of {}",
+            name,
+            self.type_name()
+        ))
+    }
+
+    /// Calls the method `name` with `arguments`, and returns its result.
+    fn call_method(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, String> {
+        let _ = arguments;
+        Err(format!("{} has no method `{}`", self.type_name(), name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{interpreter::Error, kernel::BufferKernel, test_db::Database, Interpreter, Value};
+
+    use super::HostObject;
+
+    // records the logged lines where the test can see them
+    struct Logger {
+        lines: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl HostObject for Logger {
+        fn type_name(&self) -> &str {
+            "Logger"
+        }
+
+        fn call_method(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, String> {
+            match (name, arguments.as_slice()) {
+                ("info", [message]) => {
+                    self.lines.borrow_mut().push(format!("info: {}", message));
+                    Ok(Value::Nil)
+                }
+                ("count", []) => Ok(Value::from(self.lines.borrow().len() as f64)),
+                ("info", _) => Err("`info` expects 1 argument".to_string()),
+                _ => Err(format!("Logger has no method `{}`", name)),
+            }
+        }
+    }
+
+    struct Config {
+        retries: f64,
+    }
+
+    impl HostObject for Config {
+        fn type_name(&self) -> &str {
+            "Config"
+        }
+
+        fn get(&self, name: &str) -> Result<Value, String> {
+            match name {
+                "retries" => Ok(Value::from(self.retries)),
+                "name" => Ok(Value::from("test")),
+                _ => Err(format!("Config has no property `{}`", name)),
+            }
+        }
+
+        fn set(&mut self, name: &str, value: Value) -> Result<(), String> {
+            match (name, value) {
+                ("retries", Value::Number(retries)) => {
+                    self.retries = retries;
+                    Ok(())
+                }
+                ("retries", value) => Err(format!(
+                    "`retries` must be a number, not {}",
+                    value.type_name()
+                )),
+                _ => Err(format!("cannot set property `{}` of Config", name)),
+            }
+        }
+    }
+
+    fn load<'db>(
+        db: &'db Database,
+        source: &str,
+        lines: &Rc<RefCell<Vec<String>>>,
+    ) -> Result<Interpreter<'db, BufferKernel>, Error> {
+        let input_file = db.new_input_file("test.lox", source.to_string());
+        Interpreter::builder(db, input_file, BufferKernel::new())
+            .global(
+                "logger",
+                Value::host(Logger {
+                    lines: lines.clone(),
+                }),
+            )
+            .global("config", Value::host(Config { retries: 3.0 }))
+            .load()
+    }
+
+    #[test]
+    fn methods_and_properties() {
+        let db = Database::default();
+        let lines = Rc::default();
+        let interpreter = load(
+            &db,
+            "\
+logger.info(\"starting \" + config.name);
+config.retries = config.retries + 1;
+logger.info(config.retries);
+print logger.count();
+print logger;
+",
+            &lines,
+        )
+        .unwrap();
+        assert_eq!(
+            *lines.borrow(),
+            vec!["info: starting test".to_string(), "info: 4".to_string()]
+        );
+        assert_eq!(interpreter.kernel().buffer(), "2\n<Logger>\n");
+    }
+
+    #[test]
+    fn host_errors() {
+        let db = Database::default();
+        let lines = Rc::default();
+        let error_message = |source| match load(&db, source, &lines) {
+            Err(Error::Runtime(diagnostic)) => diagnostic.message,
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("expected a runtime error"),
+        };
+
+        assert_eq!(
+            error_message("logger.warn(\"x\");"),
+            "Logger has no method `warn`"
+        );
+        assert_eq!(error_message("logger.info();"), "`info` expects 1 argument");
+        assert_eq!(
+            error_message("config.retries = \"many\";"),
+            "`retries` must be a number, not a string"
+        );
+        assert_eq!(
+            error_message("print logger.level;"),
+            "Logger has no property `level`"
+        );
+        assert_eq!(
+            error_message("var a = 1; print a.b;"),
+            "only objects have properties"
+        );
+    }
+}
@@ -0,0 +1,606 @@
+use lox_ir::{
+    bytecode::Code,
+    diagnostic::{Diagnostic, Diagnostics},
+    function::{bind_arguments, ArgumentError, Function, ParamKind},
+    input_file::InputFile,
+};
+
+use crate::{
+    execute::main_function,
+    kernel::Kernel,
+    limits::ExecutionLimits,
+    value::{ConversionError, Value},
+    vm::VM,
+};
+
+/// The errors of an [`Interpreter`].
+#[derive(Debug)]
+pub enum Error {
+    /// The program has compile errors.
+    Compile(Vec<Diagnostic>),
+
+    /// The program stopped with an error while running.
+    Runtime(Diagnostic),
+
+    /// The program has no global variable with this name.
+    UndefinedGlobal(String),
+
+    /// The global variable is not a function, it holds a value of this type.
+    NotAFunction { name: String, found: &'static str },
+
+    /// The function was called with the wrong number of arguments, `expected` is the closest
+    /// number of arguments that it takes.
+    WrongArity {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// A value could not be converted to the requested Rust type.
+    Conversion(ConversionError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Compile(diagnostics) => {
+                write!(f, "the program has {} compile errors", diagnostics.len())
+            }
+            Error::Runtime(diagnostic) => write!(f, "runtime error: {}", diagnostic.message),
+            Error::UndefinedGlobal(name) => write!(f, "undefined global variable `{}`", name),
+            Error::NotAFunction { name, found } => {
+                write!(f, "`{}` is not a function, it is {}", name, found)
+            }
+            Error::WrongArity {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` expects {} but got {}",
+                function,
+                lox_ir::function::argument_count(*expected),
+                found
+            ),
+            Error::Conversion(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ConversionError> for Error {
+    fn from(error: ConversionError) -> Self {
+        Error::Conversion(error)
+    }
+}
+
+/// Runs a lox program on behalf of a Rust application, which can then read and write the
+/// global variables of the program and call its functions.
+///
+/// Errors are returned as [`Error`]s. The diagnostics they contain can be rendered with
+/// `lox_error_format`, nothing is printed through the kernel.
+pub struct Interpreter<'db, K: Kernel> {
+    db: &'db dyn crate::Db,
+    vm: VM,
+    kernel: K,
+}
+
+/// Configures an [`Interpreter`] before its program runs, see [`Interpreter::builder`].
+pub struct InterpreterBuilder<'db, K: Kernel> {
+    db: &'db dyn crate::Db,
+    input_file: InputFile,
+    kernel: K,
+    limits: ExecutionLimits,
+    globals: Vec<(String, Value)>,
+}
+
+impl<'db, K: Kernel> InterpreterBuilder<'db, K> {
+    pub fn limits(mut self, limits: ExecutionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Defines the global variable `name` before the program runs, which is how host objects
+    /// are handed to programs. Programs that never mention the variable ignore it, so the same
+    /// globals can be given to every program.
+    pub fn global(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.globals.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Compiles the program and runs its top level code, which defines its functions and
+    /// global variables.
+    pub fn load(self) -> Result<Interpreter<'db, K>, Error> {
+        let db = self.db;
+        let main = main_function(db, self.input_file);
+        let mut diagnostics = vec![];
+        compile_diagnostics(db, main, &mut diagnostics);
+        if !diagnostics.is_empty() {
+            return Err(Error::Compile(diagnostics));
+        }
+
+        let mut vm = VM::new(db, main, false).with_limits(self.limits);
+        for (name, value) in self.globals {
+            vm.set_global(db, &name, value);
+        }
+        let mut interpreter = Interpreter {
+            db,
+            vm,
+            kernel: self.kernel,
+        };
+        match interpreter.vm.run(db, &mut interpreter.kernel) {
+            Ok(()) => Ok(interpreter),
+            Err(diagnostic) => Err(Error::Runtime(diagnostic)),
+        }
+    }
+}
+
+impl<'db, K: Kernel> Interpreter<'db, K> {
+    /// Starts configuring an interpreter for `input_file`, which prints through `kernel`.
+    pub fn builder(
+        db: &'db dyn crate::Db,
+        input_file: InputFile,
+        kernel: K,
+    ) -> InterpreterBuilder<'db, K> {
+        InterpreterBuilder {
+            db,
+            input_file,
+            kernel,
+            limits: ExecutionLimits::default(),
+            globals: vec![],
+        }
+    }
+
+    /// Compiles `input_file` and runs its top level code, which defines its functions and
+    /// global variables. The program prints through `kernel`.
+    pub fn load(
+        db: &'db dyn crate::Db,
+        input_file: InputFile,
+        kernel: K,
+        limits: ExecutionLimits,
+    ) -> Result<Self, Error> {
+        Self::builder(db, input_file, kernel).limits(limits).load()
+    }
+
+    /// Returns the value of the global variable `name`, converted to `T`.
+    pub fn global<T>(&self, name: &str) -> Result<T, Error>
+    where
+        T: TryFrom<Value, Error = ConversionError>,
+    {
+        let value = self
+            .vm
+            .global(self.db, name)
+            .ok_or_else(|| Error::UndefinedGlobal(name.to_string()))?;
+        Ok(T::try_from(value.clone())?)
+    }
+
+    /// Sets the global variable `name` to `value`. The program must mention the variable,
+    /// either by declaring it or by reading it, otherwise there is no room for it.
+    pub fn set_global(&mut self, name: &str, value: impl Into<Value>) -> Result<(), Error> {
+        if self.vm.set_global(self.db, name, value.into()) {
+            Ok(())
+        } else {
+            Err(Error::UndefinedGlobal(name.to_string()))
+        }
+    }
+
+    /// Calls the global function `name` with `arguments`, and converts its result to `T`.
+    pub fn call<T>(
+        &mut self,
+        name: &str,
+        arguments: impl IntoIterator<Item = Value>,
+    ) -> Result<T, Error>
+    where
+        T: TryFrom<Value, Error = ConversionError>,
+    {
+        let function = match self.vm.global(self.db, name) {
+            Some(Value::Function(function)) => *function,
+            Some(value) => {
+                return Err(Error::NotAFunction {
+                    name: name.to_string(),
+                    found: value.type_name(),
+                })
+            }
+            None => return Err(Error::UndefinedGlobal(name.to_string())),
+        };
+
+        let arguments: Vec<Value> = arguments.into_iter().collect();
+        let params = function.params(self.db);
+        if let Err(error) = bind_arguments(&params, arguments.len(), &[]) {
+            let expected = match error {
+                ArgumentError::WrongCount { expected, .. } => expected,
+                ArgumentError::TooMany { max, .. } => max,
+                _ => params
+                    .iter()
+                    .filter(|param| param.kind == ParamKind::Required)
+                    .count(),
+            };
+            return Err(Error::WrongArity {
+                function: name.to_string(),
+                expected,
+                found: arguments.len(),
+            });
+        }
+
+        let result = self
+            .vm
+            .call(self.db, &mut self.kernel, function, arguments)
+            .map_err(Error::Runtime)?;
+        Ok(T::try_from(result)?)
+    }
+
+    pub fn kernel(&self) -> &K {
+        &self.kernel
+    }
+
+    pub fn kernel_mut(&mut self) -> &mut K {
+        &mut self.kernel
+    }
+}
+
+// Collects the compile errors of `function` and of all the functions it defines.
+fn compile_diagnostics(db: &dyn crate::Db, function: Function, diagnostics: &mut Vec<Diagnostic>) {
+    let compiled_function = lox_compile::compile_fn(db, function);
+    diagnostics.extend(lox_compile::compile_fn::accumulated::<Diagnostics>(
+        db, function,
+    ));
+    for ip in 0..compiled_function.chunk.len() {
+        if let Code::Function(function) = compiled_function.chunk.read_byte(ip) {
+            compile_diagnostics(db, function, diagnostics);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        execute::main_function, kernel::BufferKernel, test_db::Database, value::ConversionError,
+        ExecutionLimits, Value, VM,
+    };
+
+    use super::{Error, Interpreter};
+
+    const PROGRAM: &str = "\
+var greeting = \"hello\";
+var count = 0;
+
+fun add(a, b) {
+    return a + b;
+}
+
+fun greet(name) {
+    count = count + 1;
+    return greeting + \", \" + name;
+}
+
+fun is_big(n) {
+    return n > limit;
+}
+
+fun nothing() {}
+
+fun all(...values) {
+    return values;
+}
+
+print \"loaded\";
+";
+
+    fn load(db: &Database) -> Interpreter<'_, BufferKernel> {
+        let input_file = db.new_input_file("test.lox", PROGRAM.to_string());
+        Interpreter::load(
+            db,
+            input_file,
+            BufferKernel::new(),
+            ExecutionLimits::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn call_functions() {
+        let db = Database::default();
+        let mut interpreter = load(&db);
+        assert_eq!(interpreter.kernel().buffer(), "loaded\n");
+
+        let sum: f64 = interpreter
+            .call("add", [Value::from(1.0), Value::from(2.0)])
+            .unwrap();
+        assert_eq!(sum, 3.0);
+
+        let greeting: String = interpreter.call("greet", ["world".into()]).unwrap();
+        assert_eq!(greeting, "hello, world");
+        let () = interpreter.call("nothing", []).unwrap();
+    }
+
+    #[test]
+    fn globals() {
+        let db = Database::default();
+        let mut interpreter = load(&db);
+
+        interpreter.set_global("greeting", "hi").unwrap();
+        let greeting: String = interpreter.call("greet", ["lox".into()]).unwrap();
+        assert_eq!(greeting, "hi, lox");
+        assert_eq!(interpreter.global::<f64>("count").unwrap(), 1.0);
+
+        // `limit` is only read by the program, the host has to define it
+        assert!(matches!(
+            interpreter.call::<bool>("is_big", [Value::from(5.0)]),
+            Err(Error::Runtime(_))
+        ));
+        interpreter.set_global("limit", 3.0).unwrap();
+        assert!(interpreter
+            .call::<bool>("is_big", [Value::from(5.0)])
+            .unwrap());
+
+        assert!(matches!(
+            interpreter.set_global("unknown", 1.0),
+            Err(Error::UndefinedGlobal(_))
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let db = Database::default();
+        let mut interpreter = load(&db);
+
+        assert!(matches!(
+            interpreter.call::<f64>("missing", []),
+            Err(Error::UndefinedGlobal(_))
+        ));
+        assert!(matches!(
+            interpreter.call::<f64>("count", []),
+            Err(Error::NotAFunction {
+                found: "a number",
+                ..
+            })
+        ));
+        assert!(matches!(
+            interpreter.call::<f64>("add", [Value::from(1.0)]),
+            Err(Error::WrongArity {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            interpreter.call::<f64>("greet", ["lox".into()]),
+            Err(Error::Conversion(ConversionError {
+                expected: "a number",
+                found: "a string",
+            }))
+        ));
+
+        // type errors are reported instead of panicking, and the interpreter stays usable
+        let Err(Error::Runtime(diagnostic)) =
+            interpreter.call::<f64>("add", [Value::from(1.0), Value::from(true)])
+        else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(
+            diagnostic.message,
+            "operands must be two numbers or two strings"
+        );
+        assert_eq!(
+            interpreter
+                .call::<f64>("add", [Value::from(1.0), Value::from(2.0)])
+                .unwrap(),
+            3.0
+        );
+    }
+
+    #[test]
+    fn lists() {
+        let db = Database::default();
+        let mut interpreter = load(&db);
+
+        let numbers: Vec<f64> = interpreter
+            .call("all", [Value::from(1.0), Value::from(2.0)])
+            .unwrap();
+        assert_eq!(numbers, [1.0, 2.0]);
+        let values: Vec<Value> = interpreter
+            .call("all", [Value::from("a"), Value::from(true)])
+            .unwrap();
+        assert_eq!(values, [Value::from("a"), Value::from(true)]);
+        let empty: Vec<String> = interpreter.call("all", []).unwrap();
+        assert!(empty.is_empty());
+
+        assert!(matches!(
+            interpreter.call::<Vec<f64>>("all", ["a".into()]),
+            Err(Error::Conversion(ConversionError {
+                expected: "a number",
+                found: "a string",
+            }))
+        ));
+        assert!(matches!(
+            interpreter.call::<Vec<f64>>("add", [Value::from(1.0), Value::from(2.0)]),
+            Err(Error::Conversion(ConversionError {
+                expected: "a list",
+                found: "a number",
+            }))
+        ));
+    }
+
+    #[test]
+    fn vm_call_errors() {
+        let db = Database::default();
+        let mut interpreter = load(&db);
+        let Some(&Value::Function(add)) = interpreter.vm.global(&db, "add") else {
+            panic!("expected `add` to be a function");
+        };
+
+        let diagnostic = interpreter
+            .vm
+            .call(&db, &mut interpreter.kernel, add, vec![Value::from(1.0)])
+            .unwrap_err();
+        assert_eq!(diagnostic.message, "expected 2 arguments but got 1");
+
+        // the program of a new VM has not run yet
+        let main = main_function(&db, add.body(&db).input_file(&db));
+        let mut vm = VM::new(&db, main, false);
+        let diagnostic = vm
+            .call(&db, &mut BufferKernel::new(), add, vec![Value::from(1.0)])
+            .unwrap_err();
+        assert_eq!(
+            diagnostic.message,
+            "cannot call `add` while the program is running"
+        );
+    }
+
+    #[test]
+    fn compile_errors() {
+        let db = Database::default();
+        let input_file = db.new_input_file("test.lox", "fun f() { 1 = 2; }".to_string());
+        let result = Interpreter::load(
+            &db,
+            input_file,
+            BufferKernel::new(),
+            ExecutionLimits::default(),
+        );
+        assert!(matches!(result, Err(Error::Compile(_))));
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let db = Database::default();
+        let input_file = db.new_input_file(
+            "test.lox",
+            "\
+var a = 1;
+var b = 2.5;
+fun name() { return \"lox\"; }
+print \"total: ${a + b}, ${a < b} for ${name()}${nil}\";
+print \"${\"nested ${a}\"} \\${a}\";
+"
+            .to_string(),
+        );
+        let interpreter = Interpreter::load(
+            &db,
+            input_file,
+            BufferKernel::new(),
+            ExecutionLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            interpreter.kernel().buffer(),
+            "total: 3.5, true for loxnil\nnested 1 ${a}\n"
+        );
+    }
+
+    #[test]
+    fn lambdas() {
+        let db = Database::default();
+        let input_file = db.new_input_file(
+            "test.lox",
+            "\
+fun twice(f, x) { return f(f(x)); }
+fun pick(add) {
+  if (add) return (a, b) => a + b;
+  return (a, b) => { return a - b; };
+}
+var double = (x) => x * 2;
+print twice(double, 3);
+print twice(fun (s) { return s + \"!\"; }, \"hi\");
+print pick(true)(5, 3);
+print pick(false)(5, 3);
+print double;
+"
+            .to_string(),
+        );
+        let interpreter = Interpreter::load(
+            &db,
+            input_file,
+            BufferKernel::new(),
+            ExecutionLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            interpreter.kernel().buffer(),
+            "12\nhi!!\n8\n2\n<fn anonymous>\n"
+        );
+    }
+
+    #[test]
+    fn default_named_and_rest_arguments() {
+        let db = Database::default();
+        let input_file = db.new_input_file(
+            "test.lox",
+            "\
+var calls = 0;
+fun counted() { calls = calls + 1; return calls; }
+fun greet(name, greeting = \"hello\", punctuation = \"!\") {
+  return greeting + \", \" + name + punctuation;
+}
+fun total(first, ...rest) {
+  var sum = first;
+  for (var i = 0; i < rest.length; i = i + 1) sum = sum + rest.get(i);
+  return sum;
+}
+fun count(a = counted(), b = a * 10) { return a + b; }
+print greet(\"lox\");
+print greet(\"lox\", \"hi\");
+print greet(\"lox\", punctuation: \"?\");
+print greet(punctuation: \".\", name: \"lox\");
+print total(1);
+print total(1, 2, 3);
+print count();
+print count(5);
+print count(b: 1);
+print calls;
+"
+            .to_string(),
+        );
+        let interpreter = Interpreter::load(
+            &db,
+            input_file,
+            BufferKernel::new(),
+            ExecutionLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            interpreter.kernel().buffer(),
+            "hello, lox!\nhi, lox!\nhello, lox?\nhello, lox.\n1\n6\n11\n55\n3\n2\n"
+        );
+    }
+
+    #[test]
+    fn argument_errors() {
+        let db = Database::default();
+        let run = |source: &str| {
+            let input_file = db.new_input_file("test.lox", source.to_string());
+            let result = Interpreter::load(
+                &db,
+                input_file,
+                BufferKernel::new(),
+                ExecutionLimits::default(),
+            );
+            match result {
+                Err(Error::Runtime(diagnostic)) => diagnostic.message,
+                Err(error) => panic!("expected a runtime error, got {}", error),
+                Ok(_) => panic!("expected a runtime error"),
+            }
+        };
+        let function = "fun f(a, b = 2) { return a + b; }\nvar g = f;\n";
+        assert_eq!(
+            run(&format!("{}g(1, 2, 3);", function)),
+            "expected at most 2 arguments but got 3"
+        );
+        assert_eq!(
+            run(&format!("{}g(b: 1);", function)),
+            "missing an argument for `a`"
+        );
+        assert_eq!(
+            run(&format!("{}g(1, c: 1);", function)),
+            "there is no parameter named `c`"
+        );
+        assert_eq!(
+            run(&format!("{}g(1, a: 1);", function)),
+            "`a` is given more than one argument"
+        );
+        assert_eq!(
+            run("fun h(a) {}\nvar k = h;\nk();"),
+            "expected 1 argument but got 0"
+        );
+    }
+}
@@ -1,10 +1,137 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead, Write},
+};
+
+/// An operation of the kernel that the host has to grant to programs, see [`Capabilities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    ReadStdin,
+    WriteStderr,
+    ReadFiles,
+    WriteFiles,
+    Environment,
+    Exit,
+}
+
+impl Capability {
+    /// Returns the name of the capability, as used in error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::ReadStdin => "read stdin",
+            Capability::WriteStderr => "write stderr",
+            Capability::ReadFiles => "read files",
+            Capability::WriteFiles => "write files",
+            Capability::Environment => "environment",
+            Capability::Exit => "exit",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The set of capabilities granted to a program.
+///
+/// The VM refuses the operations of the capabilities that are not granted before they reach
+/// the kernel, so kernels do not have to check them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    granted: u8,
+}
+
+impl Capabilities {
+    pub fn none() -> Self {
+        Self { granted: 0 }
+    }
+
+    pub fn all() -> Self {
+        Self { granted: u8::MAX }
+    }
+
+    pub fn with(self, capability: Capability) -> Self {
+        Self {
+            granted: self.granted | capability.bit(),
+        }
+    }
+
+    pub fn without(self, capability: Capability) -> Self {
+        Self {
+            granted: self.granted & !capability.bit(),
+        }
+    }
+
+    pub fn allows(self, capability: Capability) -> bool {
+        self.granted & capability.bit() != 0
+    }
+}
+
+/// The operations lox programs use to talk to the outside world.
+///
+/// Only `print` is required, the other operations are refused unless the kernel grants their
+/// capability in [`Kernel::capabilities`] and implements them.
 pub trait Kernel {
     // Implementation for `print` intrinsic, that prints a line of text.
     fn print(&mut self, text: &str);
+
+    /// The capabilities granted to programs, none by default.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::none()
+    }
+
+    /// Reads a line from stdin, without its line terminator. Returns `None` at the end of input.
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        Err(unsupported("reading stdin"))
+    }
+
+    /// Prints a line of text to stderr.
+    fn eprint(&mut self, text: &str) -> io::Result<()> {
+        let _ = text;
+        Err(unsupported("writing stderr"))
+    }
+
+    fn read_file(&mut self, path: &str) -> io::Result<String> {
+        let _ = path;
+        Err(unsupported("reading files"))
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> io::Result<()> {
+        let _ = (path, contents);
+        Err(unsupported("writing files"))
+    }
+
+    /// Returns the value of the environment variable `name`, if it is set.
+    fn env_var(&mut self, name: &str) -> Option<String> {
+        let _ = name;
+        None
+    }
+
+    /// Called when the program exits with `code`, the VM stops right after.
+    fn exit(&mut self, code: i32) {
+        let _ = code;
+    }
+}
+
+fn unsupported(operation: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("the kernel does not support {}", operation),
+    )
 }
 
+/// A kernel that keeps everything in memory: the printed output, stdin, stderr, the files and
+/// the environment variables. It is used by tests and by hosts that capture the output.
+///
+/// It grants all the capabilities by default, since it never touches the real system.
 pub struct BufferKernel {
     buffer: String,
+    capabilities: Capabilities,
+    stdin: VecDeque<String>,
+    stderr: String,
+    files: HashMap<String, String>,
+    env: HashMap<String, String>,
+    exit_code: Option<i32>,
 }
 
 impl Default for BufferKernel {
@@ -17,9 +144,36 @@ impl BufferKernel {
     pub fn new() -> Self {
         Self {
             buffer: String::new(),
+            capabilities: Capabilities::all(),
+            stdin: VecDeque::new(),
+            stderr: String::new(),
+            files: HashMap::new(),
+            env: HashMap::new(),
+            exit_code: None,
         }
     }
 
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Adds the lines of `text` to the input read by the program.
+    pub fn with_stdin(mut self, text: &str) -> Self {
+        self.stdin.extend(text.lines().map(str::to_string));
+        self
+    }
+
+    pub fn with_file(mut self, path: &str, contents: &str) -> Self {
+        self.files.insert(path.to_string(), contents.to_string());
+        self
+    }
+
+    pub fn with_env_var(mut self, name: &str, value: &str) -> Self {
+        self.env.insert(name.to_string(), value.to_string());
+        self
+    }
+
     pub fn buffer(&self) -> &str {
         &self.buffer
     }
@@ -27,6 +181,20 @@ impl BufferKernel {
     pub fn take_buffer(self) -> String {
         self.buffer
     }
+
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    /// Returns the contents of the file at `path`, including the files written by the program.
+    pub fn file(&self, path: &str) -> Option<&str> {
+        self.files.get(path).map(String::as_str)
+    }
+
+    /// Returns the code the program exited with, if it called `exit`.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
 }
 
 impl Kernel for BufferKernel {
@@ -34,12 +202,112 @@ impl Kernel for BufferKernel {
         self.buffer.push_str(text);
         self.buffer.push('\n');
     }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        Ok(self.stdin.pop_front())
+    }
+
+    fn eprint(&mut self, text: &str) -> io::Result<()> {
+        self.stderr.push_str(text);
+        self.stderr.push('\n');
+        Ok(())
+    }
+
+    fn read_file(&mut self, path: &str) -> io::Result<String> {
+        self.files.get(path).cloned().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no such file: {}", path))
+        })
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> io::Result<()> {
+        self.files.insert(path.to_string(), contents.to_string());
+        Ok(())
+    }
+
+    fn env_var(&mut self, name: &str) -> Option<String> {
+        self.env.get(name).cloned()
+    }
+
+    fn exit(&mut self, code: i32) {
+        self.exit_code = Some(code);
+    }
+}
+
+/// A kernel that uses the real stdin, stdout, stderr, file system and environment of the
+/// process. It grants all the capabilities by default.
+pub struct StdoutKernel {
+    capabilities: Capabilities,
+    exit_code: Option<i32>,
+}
+
+impl Default for StdoutKernel {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-pub struct StdoutKernel;
+impl StdoutKernel {
+    pub fn new() -> Self {
+        Self {
+            capabilities: Capabilities::all(),
+            exit_code: None,
+        }
+    }
+
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Returns the code the program exited with, if it called `exit`. The process itself is
+    /// left running, it is up to the host to exit with the code.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+}
 
 impl Kernel for StdoutKernel {
     fn print(&mut self, text: &str) {
-        print!("{}", text);
+        println!("{}", text);
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        // stdout is line buffered, flush it so that prompts are visible before reading
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let len = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(len);
+        Ok(Some(line))
+    }
+
+    fn eprint(&mut self, text: &str) -> io::Result<()> {
+        writeln!(io::stderr(), "{}", text)
+    }
+
+    fn read_file(&mut self, path: &str) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    fn env_var(&mut self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+
+    fn exit(&mut self, code: i32) {
+        self.exit_code = Some(code);
     }
 }
@@ -1,11 +1,27 @@
 #![feature(trait_upcasting)]
 
 pub mod execute;
+pub mod host;
+pub mod interpreter;
 pub mod kernel;
+pub mod limits;
+mod list;
+pub mod native;
+pub mod observer;
+mod stack_trace;
+#[cfg(test)]
+mod test_db;
+pub mod value;
 pub mod vm;
 
 pub use execute::execute_file;
-pub use vm::VM;
+pub use execute::execute_file_with_limits;
+pub use host::HostObject;
+pub use interpreter::{Interpreter, InterpreterBuilder};
+pub use limits::ExecutionLimits;
+pub use observer::Observer;
+pub use value::Value;
+pub use vm::{ControlFlow, Frame, VM};
 
 #[salsa::jar(db = Db)]
 pub struct Jar(execute::main_function);
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+/// The maximum call depth of [`ExecutionLimits::default`].
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1024;
+
+/// Limits on the resources a program may use while it runs.
+///
+/// Every limit is optional. By default only the call depth is limited, so that unbounded
+/// recursion is reported as a stack overflow. When a program exceeds one of the limits,
+/// the VM stops it and reports a diagnostic pointing at the instruction it was about to
+/// execute, instead of running on forever.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    /// The maximum number of instructions the program may execute.
+    pub max_instructions: Option<u64>,
+
+    /// The maximum number of nested function calls, counting the call to the main function.
+    /// Exceeding it is reported as a stack overflow.
+    pub max_call_depth: Option<usize>,
+
+    /// The maximum number of objects alive on the heap at the same time: the strings and the
+    /// lists of rest parameters that the program creates. The host objects that the host
+    /// gives to the program are not counted.
+    pub max_heap_objects: Option<usize>,
+
+    /// How long the program may run, measured from the moment the limits are set with
+    /// [`VM::with_limits`](crate::VM::with_limits).
+    ///
+    /// Reading the clock panics on `wasm32-unknown-unknown`, so this must be `None` there,
+    /// use `max_instructions` to bound the running time instead.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        Self {
+            max_instructions: None,
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            max_heap_objects: None,
+            timeout: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{execute::execute_file_with_limits, kernel::BufferKernel, test_db::Database};
+
+    use super::ExecutionLimits;
+
+    fn run(source_text: &str, limits: ExecutionLimits) -> String {
+        let db = Database::default();
+        let input_file = db.new_input_file("test.lox", source_text.to_string());
+        let mut kernel = BufferKernel::new();
+        execute_file_with_limits(&db, input_file, &mut kernel, false, limits, &mut ());
+        kernel.take_buffer()
+    }
+
+    #[test]
+    fn no_limits() {
+        let output = run(
+            "var a = 0; while (a < 100) { a = a + 1; } print a;",
+            ExecutionLimits::default(),
+        );
+        assert_eq!(output, "100\n");
+    }
+
+    #[test]
+    fn instruction_limit() {
+        let limits = ExecutionLimits {
+            max_instructions: Some(1000),
+            ..Default::default()
+        };
+        let output = run("print 1; while (true) {}", limits);
+        assert!(output.starts_with("1\n"), "{}", output);
+        assert!(output.contains("instruction limit exceeded"), "{}", output);
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let limits = ExecutionLimits {
+            max_call_depth: Some(10),
+            ..Default::default()
+        };
+        let output = run("fun f(n) { return f(n + 1); }\nf(0);", limits);
+        assert!(output.contains("stack overflow"), "{}", output);
+        assert!(
+            output.contains("  f at test.lox:1:19\n  ... the frame above repeated 8 more times\n  main at test.lox:2:1"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn stack_overflow_by_default() {
+        let output = run(
+            "fun f(n) { return f(n + 1); } f(0);",
+            ExecutionLimits::default(),
+        );
+        assert!(output.contains("stack overflow"), "{}", output);
+    }
+
+    #[test]
+    fn heap_objects_limit() {
+        let limits = ExecutionLimits {
+            max_heap_objects: Some(100),
+            ..Default::default()
+        };
+
+        // the intermediate strings are garbage, so they do not count against the limit
+        let output = run(
+            "var s = \"\"; for (var i = 0; i < 1000; i = i + 1) { s = s + \"x\"; } print \"done\";",
+            limits,
+        );
+        assert_eq!(output, "done\n");
+
+        let output = run(
+            "fun keep(s, n) { if (n > 0) { keep(s + \"x\", n - 1); } } keep(\"\", 1000);",
+            limits,
+        );
+        assert!(output.contains("heap object limit exceeded"), "{}", output);
+
+        // so are the lists of rest parameters
+        let output = run(
+            "fun f(...rest) {} for (var i = 0; i < 1000; i = i + 1) { f(i); } print \"done\";",
+            limits,
+        );
+        assert_eq!(output, "done\n");
+
+        let output = run(
+            "fun keep(n, ...rest) { if (n > 0) { keep(n - 1, n); } } keep(1000);",
+            limits,
+        );
+        assert!(output.contains("heap object limit exceeded"), "{}", output);
+    }
+
+    #[test]
+    fn timeout() {
+        let limits = ExecutionLimits {
+            timeout: Some(std::time::Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let output = run("while (true) {}", limits);
+        assert!(output.contains("time limit exceeded"), "{}", output);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{host::HostObject, value::Value};
+
+/// The arguments that a rest parameter like `...rest` gets. Programs read the number of
+/// arguments with `rest.length` and each of them with `rest.get(index)`.
+pub(crate) struct List {
+    values: Vec<Value>,
+}
+
+impl List {
+    pub(crate) fn new(values: Vec<Value>) -> Self {
+        Self { values }
+    }
+
+    pub(crate) fn values(&self) -> &[Value] {
+        &self.values
+    }
+}
+
+impl HostObject for List {
+    fn type_name(&self) -> &str {
+        "list"
+    }
+
+    fn get(&self, name: &str) -> Result<Value, String> {
+        match name {
+            "length" => Ok(Value::from(self.values.len() as f64)),
+            _ => Err(format!("list has no property `{}`", name)),
+        }
+    }
+
+    fn call_method(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, String> {
+        match (name, arguments.as_slice()) {
+            ("get", [Value::Number(index)]) => {
+                let value = (index.fract() == 0.0 && *index >= 0.0)
+                    .then(|| self.values.get(*index as usize))
+                    .flatten();
+                value.cloned().ok_or_else(|| {
+                    format!(
+                        "index {} is out of bounds for a list of length {}",
+                        index,
+                        self.values.len()
+                    )
+                })
+            }
+            ("get", [index]) => Err(format!(
+                "the index must be a number, not {}",
+                index.type_name()
+            )),
+            ("get", _) => Err("`get` expects 1 argument".to_string()),
+            _ => Err(format!("list has no method `{}`", name)),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{
+    kernel::{Capability, Kernel},
+    value::Value,
+};
+
+/// A function implemented by the VM on top of the [`Kernel`].
+///
+/// Natives are global variables that programs can redefine: a program that declares its own
+/// `exit` function uses it instead of the native one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Native {
+    // `readLine()`, returns the next line of stdin, or nil at the end of input
+    ReadLine,
+
+    // `eprint(value)`, prints `value` to stderr
+    Eprint,
+
+    // `readFile(path)`, returns the contents of the file
+    ReadFile,
+
+    // `writeFile(path, contents)`, replaces the contents of the file
+    WriteFile,
+
+    // `env(name)`, returns the value of the environment variable, or nil if it is not set
+    Env,
+
+    // `exit(code)`, stops the program
+    Exit,
+}
+
+impl Native {
+    pub const ALL: [Native; 6] = [
+        Native::ReadLine,
+        Native::Eprint,
+        Native::ReadFile,
+        Native::WriteFile,
+        Native::Env,
+        Native::Exit,
+    ];
+
+    /// Returns the native function called `name`, if any.
+    pub fn from_name(name: &str) -> Option<Native> {
+        Self::ALL.into_iter().find(|native| native.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Native::ReadLine => "readLine",
+            Native::Eprint => "eprint",
+            Native::ReadFile => "readFile",
+            Native::WriteFile => "writeFile",
+            Native::Env => "env",
+            Native::Exit => "exit",
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            Native::ReadLine => 0,
+            Native::Eprint | Native::ReadFile | Native::Env | Native::Exit => 1,
+            Native::WriteFile => 2,
+        }
+    }
+
+    /// The capability the kernel has to grant for the function to be called.
+    pub fn capability(self) -> Capability {
+        match self {
+            Native::ReadLine => Capability::ReadStdin,
+            Native::Eprint => Capability::WriteStderr,
+            Native::ReadFile => Capability::ReadFiles,
+            Native::WriteFile => Capability::WriteFiles,
+            Native::Env => Capability::Environment,
+            Native::Exit => Capability::Exit,
+        }
+    }
+
+    /// Calls the function through `kernel`, the number of arguments and the capability are
+    /// checked by the caller. Errors are returned as the message of a runtime error.
+    pub(crate) fn call(
+        self,
+        db: &dyn lox_ir::Db,
+        kernel: &mut impl Kernel,
+        arguments: &[Value],
+    ) -> Result<Value, String> {
+        let io_error = |error: std::io::Error| format!("`{}` failed: {}", self.name(), error);
+        match self {
+            Native::ReadLine => Ok(kernel.read_line().map_err(io_error)?.into()),
+            Native::Eprint => {
+                kernel
+                    .eprint(&arguments[0].display(db).to_string())
+                    .map_err(io_error)?;
+                Ok(Value::Nil)
+            }
+            Native::ReadFile => {
+                let path = self.string_argument(arguments, 0)?;
+                Ok(kernel.read_file(path).map_err(io_error)?.into())
+            }
+            Native::WriteFile => {
+                let path = self.string_argument(arguments, 0)?;
+                let contents = self.string_argument(arguments, 1)?;
+                kernel.write_file(path, contents).map_err(io_error)?;
+                Ok(Value::Nil)
+            }
+            Native::Env => {
+                let name = self.string_argument(arguments, 0)?;
+                Ok(kernel.env_var(name).into())
+            }
+            Native::Exit => match arguments[0] {
+                Value::Number(code) if code.fract() == 0.0 && code.abs() <= i32::MAX as f64 => {
+                    kernel.exit(code as i32);
+                    Ok(Value::Nil)
+                }
+                ref code => Err(format!(
+                    "`exit` expects an integer exit code, not {}",
+                    code.type_name()
+                )),
+            },
+        }
+    }
+
+    fn string_argument(self, arguments: &[Value], index: usize) -> Result<&str, String> {
+        match &arguments[index] {
+            Value::String(s) => Ok(s),
+            value => Err(format!(
+                "argument {} of `{}` must be a string, not {}",
+                index + 1,
+                self.name(),
+                value.type_name()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        execute::execute_file,
+        kernel::{BufferKernel, Capabilities, Capability},
+        test_db::Database,
+    };
+
+    fn run(source_text: &str, mut kernel: BufferKernel) -> BufferKernel {
+        let db = Database::default();
+        let input_file = db.new_input_file("test.lox", source_text.to_string());
+        execute_file(&db, input_file, &mut kernel, false, &mut ());
+        kernel
+    }
+
+    #[test]
+    fn io() {
+        let kernel = BufferKernel::new()
+            .with_stdin("alice\nbob\n")
+            .with_file("in.txt", "hello")
+            .with_env_var("HOME", "/home/lox");
+        let kernel = run(
+            "\
+print readLine() + \" and \" + readLine();
+print readLine();
+writeFile(\"out.txt\", readFile(\"in.txt\") + \" world\");
+print env(\"HOME\");
+print env(\"MISSING\");
+eprint(\"warning\");
+exit(3);
+print \"unreachable\";
+",
+            kernel,
+        );
+        assert_eq!(kernel.buffer(), "alice and bob\nnil\n/home/lox\nnil\n");
+        assert_eq!(kernel.stderr(), "warning\n");
+        assert_eq!(kernel.file("out.txt"), Some("hello world"));
+        assert_eq!(kernel.exit_code(), Some(3));
+    }
+
+    #[test]
+    fn denied_capability() {
+        let kernel = BufferKernel::new()
+            .with_file("secret.txt", "hunter2")
+            .with_capabilities(Capabilities::all().without(Capability::ReadFiles));
+        let kernel = run("print env(\"X\");\nprint readFile(\"secret.txt\");", kernel);
+        let output = kernel.take_buffer();
+        assert!(output.starts_with("nil\n"), "{}", output);
+        assert!(output.contains("`readFile` is not allowed"), "{}", output);
+        assert!(!output.contains("hunter2"), "{}", output);
+    }
+
+    #[test]
+    fn errors() {
+        let output = run("readFile(\"missing.txt\");", BufferKernel::new()).take_buffer();
+        assert!(
+            output.contains("`readFile` failed: no such file: missing.txt"),
+            "{}",
+            output
+        );
+
+        let output = run("writeFile(\"a.txt\", 1);", BufferKernel::new()).take_buffer();
+        assert!(
+            output.contains("argument 2 of `writeFile` must be a string, not a number"),
+            "{}",
+            output
+        );
+
+        // programs can define their own functions with the names of natives
+        let output = run(
+            "fun exit(code) { print code; }\nexit(1);",
+            BufferKernel::new(),
+        );
+        assert_eq!(output.exit_code(), None);
+        assert_eq!(output.buffer(), "1\n");
+    }
+}
@@ -0,0 +1,82 @@
+use lox_ir::bytecode::Code;
+
+use crate::vm::VM;
+
+/// Follows a program as [`VM::run_with`] runs it, which is how tracers, profilers, coverage
+/// and debuggers see what the program does.
+///
+/// `K` is the kernel the program prints through, so that observers can look at the output of
+/// each step. `()` observes nothing.
+pub trait Observer<K> {
+    /// Called before each step, while [`VM::current_span`] is the span of the instruction
+    /// about to be executed.
+    fn before_step(&mut self, vm: &VM) {
+        let _ = vm;
+    }
+
+    /// Called after each step that succeeded. `instruction` is the instruction that was
+    /// executed, it is `None` if the step returned from a function that ran out of instructions.
+    ///
+    /// Returns whether the program keeps running, it is paused otherwise and [`VM::run_with`]
+    /// can be called again to resume it.
+    fn after_step(&mut self, vm: &VM, kernel: &K, instruction: Option<&Code>) -> bool {
+        let _ = (vm, kernel, instruction);
+        true
+    }
+}
+
+impl<K> Observer<K> for () {}
+
+#[cfg(test)]
+mod tests {
+    use lox_ir::bytecode::Code;
+
+    use crate::{execute::main_function, kernel::BufferKernel, test_db::Database, ControlFlow, VM};
+
+    use super::Observer;
+
+    // pauses the program after every `print`, and records what it printed by then
+    #[derive(Default)]
+    struct Prints {
+        outputs: Vec<String>,
+        steps: usize,
+    }
+
+    impl Observer<BufferKernel> for Prints {
+        fn before_step(&mut self, _: &VM) {
+            self.steps += 1;
+        }
+
+        fn after_step(&mut self, _: &VM, kernel: &BufferKernel, code: Option<&Code>) -> bool {
+            if code != Some(&Code::Print) {
+                return true;
+            }
+            self.outputs.push(kernel.buffer().to_string());
+            false
+        }
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let db = Database::default();
+        let input_file = db.new_input_file("test.lox", "print 1;\nprint 2;".to_string());
+        let mut vm = VM::new(&db, main_function(&db, input_file), false);
+        let mut kernel = BufferKernel::new();
+        let mut prints = Prints::default();
+
+        let mut results = vec![];
+        loop {
+            let result = vm.run_with(&db, &mut kernel, &mut prints).unwrap();
+            results.push(result);
+            if result == ControlFlow::Done {
+                break;
+            }
+        }
+        assert_eq!(
+            results,
+            [ControlFlow::Next, ControlFlow::Next, ControlFlow::Done]
+        );
+        assert_eq!(prints.outputs, ["1\n", "1\n2\n"]);
+        assert_eq!(prints.steps as u64, vm.instruction_count() + 1);
+    }
+}
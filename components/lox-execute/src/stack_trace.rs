@@ -0,0 +1,142 @@
+use lox_ir::{line_index::line_index, span::FileSpan};
+
+// a stack trace longer than twice this many entries only shows this many entries at each end
+const TRACE_EDGE_ENTRIES: usize = 10;
+
+// the longest cycle of recursive calls that is collapsed into a repetition count
+const MAX_CYCLE_LEN: usize = 4;
+
+/// A frame of the call stack, as shown in stack traces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TraceFrame {
+    pub(crate) function: String,
+
+    // the span of the instruction the frame is executing
+    pub(crate) span: FileSpan,
+}
+
+/// Renders a stack trace of `frames`, which are ordered from the most recent call to the oldest.
+///
+/// Recursive calls that repeat the same cycle of frames are shown once along with a repetition
+/// count, and if the trace is still long, only its first and last entries are shown.
+pub(crate) fn format_stack_trace(db: &dyn crate::Db, frames: &[TraceFrame]) -> String {
+    let mut lines = vec![];
+    for entry in collapse_cycles(frames) {
+        match entry {
+            TraceEntry::Frame(frame) => {
+                let input_file = frame.span.input_file;
+                let (line, column) = line_index(db, input_file).line_column(frame.span.start);
+                lines.push(format!(
+                    "  {} at {}:{}:{}",
+                    frame.function,
+                    input_file.name(db).as_str(db),
+                    line + 1,
+                    column + 1
+                ));
+            }
+            TraceEntry::Repeated { len, times } => {
+                let frames = if len == 1 {
+                    "frame".to_string()
+                } else {
+                    format!("{} frames", len)
+                };
+                lines.push(format!(
+                    "  ... the {} above repeated {} more times",
+                    frames, times
+                ));
+            }
+        }
+    }
+
+    if lines.len() > 2 * TRACE_EDGE_ENTRIES {
+        let omitted = lines.len() - 2 * TRACE_EDGE_ENTRIES;
+        lines.splice(
+            TRACE_EDGE_ENTRIES..lines.len() - TRACE_EDGE_ENTRIES,
+            [format!("  ... {} more entries", omitted)],
+        );
+    }
+
+    format!("call stack, most recent call first:\n{}", lines.join("\n"))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum TraceEntry<'a, T> {
+    Frame(&'a T),
+
+    // the `len` entries above are repeated `times` more times
+    Repeated { len: usize, times: usize },
+}
+
+fn collapse_cycles<T: PartialEq>(frames: &[T]) -> Vec<TraceEntry<'_, T>> {
+    let mut entries = vec![];
+    let mut rest = frames;
+    while !rest.is_empty() {
+        let (len, repeats) = find_cycle(rest);
+        entries.extend(rest[..len].iter().map(TraceEntry::Frame));
+        if repeats > 1 {
+            entries.push(TraceEntry::Repeated {
+                len,
+                times: repeats - 1,
+            });
+        }
+        rest = &rest[len * repeats..];
+    }
+    entries
+}
+
+// Returns the length of the shortest cycle at the start of `frames`,
+// and how many times in a row it occurs.
+fn find_cycle<T: PartialEq>(frames: &[T]) -> (usize, usize) {
+    for len in 1..=MAX_CYCLE_LEN.min(frames.len() / 2) {
+        let cycle = &frames[..len];
+        let repeats = frames
+            .chunks_exact(len)
+            .take_while(|chunk| *chunk == cycle)
+            .count();
+        if repeats > 1 {
+            return (len, repeats);
+        }
+    }
+    (1, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collapse_cycles, TraceEntry};
+
+    #[test]
+    fn collapse_recursion() {
+        let frames = [1, 1, 1, 1, 0];
+        assert_eq!(
+            collapse_cycles(&frames),
+            vec![
+                TraceEntry::Frame(&1),
+                TraceEntry::Repeated { len: 1, times: 3 },
+                TraceEntry::Frame(&0),
+            ]
+        );
+    }
+
+    #[test]
+    fn collapse_mutual_recursion() {
+        let frames = [2, 1, 2, 1, 2, 1, 0];
+        assert_eq!(
+            collapse_cycles(&frames),
+            vec![
+                TraceEntry::Frame(&2),
+                TraceEntry::Frame(&1),
+                TraceEntry::Repeated { len: 2, times: 2 },
+                TraceEntry::Frame(&0),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_cycles() {
+        let frames = [3, 2, 1, 0];
+        assert_eq!(
+            collapse_cycles(&frames),
+            frames.iter().map(TraceEntry::Frame).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use lox_ir::{input_file::InputFile, word::Word};
+
+// The database of the unit tests. `lox_db::Database` depends on this crate, so the tests have
+// their own with the same jars.
+#[salsa::db(
+    crate::Jar,
+    lox_ir::Jar,
+    lox_lex::Jar,
+    lox_parse::Jar,
+    lox_compile::Jar,
+    lox_error_format::Jar
+)]
+#[derive(Default)]
+pub(crate) struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+impl lox_ir::Db for Database {}
+
+impl lox_lex::Db for Database {}
+
+impl Database {
+    pub(crate) fn new_input_file(&self, name: impl ToString, source_text: String) -> InputFile {
+        let name = Word::intern(self, name);
+        InputFile::new(self, name, source_text)
+    }
+}
@@ -0,0 +1,391 @@
+use std::{any::Any, cell::RefCell, collections::HashSet, convert::Infallible, rc::Rc};
+
+use lox_ir::function::Function;
+
+use crate::{host::HostObject, list::List, native::Native};
+
+/// A Lox value.
+///
+/// Numbers, booleans and nil are stored inline, so pushing them to the stack never allocates.
+/// Strings are reference-counted and interned by the VM (see [`Strings`]), so copying a string
+/// value only bumps a counter, and equal strings usually share the same allocation.
+/// Functions are salsa structs, which are just ids, so they are as cheap to copy as a number.
+/// Native functions are implemented by the VM, see [`Native`].
+/// Host objects are shared with the host that created them.
+#[derive(Clone)]
+pub enum Value {
+    Number(f64),
+    Boolean(bool),
+    Nil,
+    String(Rc<str>),
+    Function(Function),
+    Native(Native),
+    Host(Rc<RefCell<dyn HostObject>>),
+}
+
+impl Value {
+    /// Wraps a Rust value so that lox programs can use its properties and methods.
+    pub fn host(object: impl HostObject + 'static) -> Self {
+        Value::Host(Rc::new(RefCell::new(object)))
+    }
+
+    /// Returns the name of the type of the value, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "a number",
+            Value::Boolean(_) => "a boolean",
+            Value::Nil => "nil",
+            Value::String(_) => "a string",
+            Value::Function(_) | Value::Native(_) => "a function",
+            Value::Host(_) => "an object",
+        }
+    }
+
+    /// Renders the value the way `print` shows it. Functions are named after their
+    /// declaration, which takes `db` to look up.
+    pub fn display<'a>(&'a self, db: &'a dyn lox_ir::Db) -> impl std::fmt::Display + 'a {
+        DisplayValue { value: self, db }
+    }
+}
+
+struct DisplayValue<'a> {
+    value: &'a Value,
+    db: &'a dyn lox_ir::Db,
+}
+
+impl std::fmt::Display for DisplayValue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            Value::Function(function) => {
+                write!(f, "<fn {}>", function.name(self.db).as_str(self.db))
+            }
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Nil => write!(f, "nil"),
+            Value::String(s) => write!(f, "{}", s),
+            // the name of a function is in the database, see `Value::display`
+            Value::Function(_) => write!(f, "<fn>"),
+            Value::Native(native) => write!(f, "<native fn {}>", native.name()),
+            // the object is borrowed while one of its methods runs, which may print it
+            Value::Host(object) => match object.try_borrow() {
+                Ok(object) => write!(f, "<{}>", object.type_name()),
+                Err(_) => write!(f, "<object>"),
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+// Unlike `Value::display`, this quotes strings, which is what debuggers and execution
+// traces want.
+impl<'db> salsa::DebugWithDb<dyn lox_ir::Db + 'db> for Value {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        db: &dyn lox_ir::Db,
+        _include_all_fields: bool,
+    ) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{:?}", s),
+            _ => write!(f, "{}", self.display(db)),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(num: f64) -> Self {
+        Value::Number(num)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl From<Rc<str>> for Value {
+    fn from(s: Rc<str>) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s.into())
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Nil, Into::into)
+    }
+}
+
+/// The error of converting a [`Value`] to a Rust type that cannot hold it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConversionError {
+    /// What the Rust type can hold, for example "a number".
+    pub expected: &'static str,
+
+    /// The type of the value, as returned by [`Value::type_name`].
+    pub found: &'static str,
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+// converting a `Value` to itself cannot fail, which lets `Vec<Value>` use the impl of `Vec<T>`
+impl From<Infallible> for ConversionError {
+    fn from(never: Infallible) -> Self {
+        match never {}
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(n) => Ok(n),
+            _ => Err(ConversionError {
+                expected: "a number",
+                found: value.type_name(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(ConversionError {
+                expected: "a boolean",
+                found: value.type_name(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(s) => Ok(s.to_string()),
+            _ => Err(ConversionError {
+                expected: "a string",
+                found: value.type_name(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for () {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Nil => Ok(()),
+            _ => Err(ConversionError {
+                expected: "nil",
+                found: value.type_name(),
+            }),
+        }
+    }
+}
+
+/// Converts the list that a rest parameter like `...rest` gets, converting each of its values
+/// to `T`.
+impl<T> TryFrom<Value> for Vec<T>
+where
+    T: TryFrom<Value>,
+    ConversionError: From<T::Error>,
+{
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        if let Value::Host(object) = &value {
+            let object = object.borrow();
+            let object: &dyn Any = &*object;
+            if let Some(list) = object.downcast_ref::<List>() {
+                return list
+                    .values()
+                    .iter()
+                    .map(|value| Ok(T::try_from(value.clone())?))
+                    .collect();
+            }
+        }
+        Err(ConversionError {
+            expected: "a list",
+            found: value.type_name(),
+        })
+    }
+}
+
+impl std::cmp::PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            // interned strings can be compared by address, the content is only
+            // compared for strings that did not go through the interner
+            (Value::String(a), Value::String(b)) => Rc::ptr_eq(a, b) || a == b,
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::Native(a), Value::Native(b)) => a == b,
+            (Value::Host(a), Value::Host(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl std::cmp::PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+/// The string interner of the VM.
+///
+/// Strings that are no longer referenced by any value are dropped from time to time,
+/// so that building many temporary strings does not grow the interner forever.
+pub(crate) struct Strings {
+    strings: HashSet<Rc<str>>,
+
+    // the number of strings at which unreferenced strings are dropped next
+    next_collection: usize,
+}
+
+const MIN_COLLECTION_THRESHOLD: usize = 1024;
+
+impl Default for Strings {
+    fn default() -> Self {
+        Self {
+            strings: HashSet::new(),
+            next_collection: MIN_COLLECTION_THRESHOLD,
+        }
+    }
+}
+
+impl Strings {
+    pub(crate) fn intern(&mut self, s: &str) -> Rc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone();
+        }
+
+        if self.strings.len() >= self.next_collection {
+            self.collect();
+        }
+
+        let interned: Rc<str> = Rc::from(s);
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    /// Like [`Self::intern`], but reuses the allocation of `s` if it is not interned yet.
+    pub(crate) fn intern_rc(&mut self, s: Rc<str>) -> Rc<str> {
+        if let Some(interned) = self.strings.get(&s) {
+            return interned.clone();
+        }
+
+        if self.strings.len() >= self.next_collection {
+            self.collect();
+        }
+
+        self.strings.insert(s.clone());
+        s
+    }
+
+    /// Returns the number of interned strings, including the ones no longer referenced
+    /// by any value that have not been dropped yet.
+    pub(crate) fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Drops the strings that are only referenced by the interner itself.
+    pub(crate) fn collect(&mut self) {
+        self.strings.retain(|s| Rc::strong_count(s) > 1);
+        self.next_collection = (self.strings.len() * 2).max(MIN_COLLECTION_THRESHOLD);
+    }
+}
+
+/// The objects that the VM allocates for programs, like the lists of rest parameters.
+///
+/// Like [`Strings`], it only keeps the objects to count the ones that are alive, and drops
+/// the ones no longer referenced by any value from time to time.
+pub(crate) struct Objects {
+    objects: Vec<Rc<RefCell<dyn HostObject>>>,
+
+    // the number of objects at which unreferenced objects are dropped next
+    next_collection: usize,
+}
+
+impl Default for Objects {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            next_collection: MIN_COLLECTION_THRESHOLD,
+        }
+    }
+}
+
+impl Objects {
+    pub(crate) fn allocate(&mut self, object: impl HostObject + 'static) -> Value {
+        if self.objects.len() >= self.next_collection {
+            self.collect();
+        }
+
+        let object: Rc<RefCell<dyn HostObject>> = Rc::new(RefCell::new(object));
+        self.objects.push(object.clone());
+        Value::Host(object)
+    }
+
+    /// Returns the number of objects, including the ones no longer referenced by any value
+    /// that have not been dropped yet.
+    pub(crate) fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Drops the objects that are only referenced by `self`.
+    pub(crate) fn collect(&mut self) {
+        self.objects.retain(|object| Rc::strong_count(object) > 1);
+        self.next_collection = (self.objects.len() * 2).max(MIN_COLLECTION_THRESHOLD);
+    }
+}
@@ -1,220 +1,221 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, rc::Rc, time::Instant};
 
-use lox_compile::compile_fn;
+use lox_compile::{compile_fn, globals::GlobalVariables};
 use lox_error_format::FormatOptions;
 use lox_ir::{
     bytecode::{self, CompiledFunction},
-    diagnostic::Diagnostics,
-    function::Function,
+    diagnostic::{Diagnostic, Diagnostics},
+    function::{bind_arguments, ArgumentError, Binding, Function},
+    input_file::InputFile,
+    span::FileSpan,
+    word::Word,
 };
 
-use crate::kernel::Kernel;
+use crate::{
+    host::HostObject,
+    kernel::Kernel,
+    limits::ExecutionLimits,
+    list::List,
+    native::Native,
+    observer::Observer,
+    stack_trace::{format_stack_trace, TraceFrame},
+    value::{Objects, Strings, Value},
+};
 
-#[derive(Clone)]
-pub enum Value {
-    Number(f64),
-    Boolean(bool),
-    Nil,
-    String(String),
-    Function(Function),
-}
+// reading the clock is slow compared to executing an instruction,
+// so the deadline is only checked once every this many instructions
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
 
-impl std::fmt::Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.clone() {
-            Value::Number(n) => write!(f, "{}", n),
-            Value::Boolean(b) => write!(f, "{}", b),
-            Value::Nil => write!(f, "nil"),
-            Value::String(s) => write!(f, "{}", s),
-            Value::Function(func) => write!(f, "<func {:?}>", func),
-        }
-    }
-}
+/// What the VM does after executing an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFlow {
+    /// There are more instructions to execute.
+    Next,
 
-impl std::fmt::Debug for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.clone() {
-            Value::Number(n) => write!(f, "{}", n),
-            Value::Boolean(b) => write!(f, "{}", b),
-            Value::Nil => write!(f, "nil"),
-            Value::String(s) => write!(f, "{}", s),
-            Value::Function(func) => write!(f, "<func {:?}>", func),
-        }
-    }
+    /// The program ran to completion, the VM must not be run any further.
+    Done,
 }
 
-impl From<f64> for Value {
-    fn from(num: f64) -> Self {
-        Value::Number(num)
-    }
-}
+#[derive(Debug, Clone)]
+struct CallFrame {
+    function: CompiledFunction,
+    ip: usize,
+    fp: usize,
 
-impl From<bool> for Value {
-    fn from(b: bool) -> Self {
-        Value::Boolean(b)
-    }
+    // whether the call gave an argument to each parameter, the others get their default value
+    given_arguments: Vec<bool>,
 }
 
-impl From<String> for Value {
-    fn from(s: String) -> Self {
-        Value::String(s)
+impl CallFrame {
+    fn read_byte(&mut self) -> bytecode::Code {
+        let byte = self.function.chunk.read_byte(self.ip);
+        self.ip += 1;
+        byte
     }
-}
-
-impl std::ops::Add for Value {
-    type Output = Self;
 
-    fn add(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
-            (Value::String(a), Value::String(b)) => Value::String(a.clone() + b),
-            _ => panic!("Cannot add {:?} and {:?}", self, rhs),
-        }
+    // index of the local variable in the stack
+    fn local_variable(&self, index: usize) -> usize {
+        self.fp + index + 1
     }
-}
-
-impl std::ops::Sub for Value {
-    type Output = Self;
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Value::Number(a), Value::Number(b)) => Value::Number(a - b),
-            _ => panic!("Cannot subtract {:?} and {:?}", self, rhs),
-        }
+    // the span of the source code the instruction at `ip` was compiled from
+    fn span(&self, input_file: InputFile, ip: usize) -> FileSpan {
+        self.function.chunk.span(ip).anchor_to(input_file)
     }
 }
 
-impl std::ops::Mul for Value {
-    type Output = Self;
+/// A frame of the call stack of a [`VM`], as seen by debuggers.
+pub struct Frame<'vm> {
+    vm: &'vm VM,
+    frame: &'vm CallFrame,
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Value::Number(a), Value::Number(b)) => Value::Number(a * b),
-            _ => panic!("Cannot multiply {:?} and {:?}", self, rhs),
-        }
-    }
+    // the offset of the instruction the frame is executing
+    ip: Option<usize>,
 }
 
-impl std::ops::Div for Value {
-    type Output = Self;
-
-    fn div(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Value::Number(a), Value::Number(b)) => Value::Number(a / b),
-            _ => panic!("Cannot divide {:?} and {:?}", self, rhs),
-        }
+impl<'vm> Frame<'vm> {
+    pub fn function_name(&self) -> &'vm str {
+        &self.frame.function.name
     }
-}
-
-impl std::ops::Neg for Value {
-    type Output = Self;
 
-    fn neg(self) -> Self::Output {
-        match self {
-            Value::Number(a) => Value::Number(-a),
-            _ => panic!("Cannot negate {:?}", self),
-        }
+    /// Returns the span of the instruction the frame is executing: the next instruction for
+    /// the innermost frame, and the call that is in progress for the other frames.
+    /// Returns `None` if the innermost frame has no instructions left.
+    pub fn span(&self) -> Option<FileSpan> {
+        self.ip.map(|ip| self.frame.span(self.vm.input_file, ip))
     }
-}
-
-impl std::ops::Not for Value {
-    type Output = Self;
 
-    fn not(self) -> Self::Output {
-        match self {
-            Value::Boolean(a) => Value::Boolean(!a),
-            _ => panic!("Cannot negate {:?}", self),
-        }
+    /// Returns the offset in the chunk of the function of the instruction [`Frame::span`] is
+    /// the span of.
+    pub fn ip(&self) -> Option<usize> {
+        self.ip
     }
-}
 
-impl std::cmp::PartialEq for Value {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Value::Number(a), Value::Number(b)) => a == b,
-            (Value::Boolean(a), Value::Boolean(b)) => a == b,
-            (Value::Nil, Value::Nil) => true,
-            _ => false,
-        }
+    /// Returns the index in [`VM::stack_values`] of the function this frame is executing,
+    /// its arguments and the other values introduced by the frame follow it.
+    pub fn stack_start(&self) -> usize {
+        self.frame.fp
     }
-}
 
-impl std::cmp::PartialOrd for Value {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
-            _ => None,
-        }
+    /// Returns the local variables that hold a value at this point of the function,
+    /// in the order they are stored on the stack.
+    pub fn locals(&self) -> Vec<(&'vm str, &'vm Value)> {
+        let Some(ip) = self.ip else {
+            return vec![];
+        };
+        let mut locals: Vec<_> = self
+            .frame
+            .function
+            .locals
+            .iter()
+            .filter(|local| local.live.contains(&ip))
+            .filter_map(|local| {
+                let value = self
+                    .vm
+                    .stack
+                    .get(self.frame.local_variable(local.index_in_stack))?;
+                Some((local.index_in_stack, local.name.as_str(), value))
+            })
+            .collect();
+        locals.sort_by_key(|(index, _, _)| *index);
+        locals
+            .into_iter()
+            .map(|(_, name, value)| (name, value))
+            .collect()
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) enum ControlFlow {
-    Next,
-    Done,
-}
+pub struct VM {
+    frames: Vec<CallFrame>,
 
-#[derive(Debug, Clone)]
-struct CallFrame {
-    function: CompiledFunction,
-    ip: usize,
-    fp: usize,
-}
+    pub stack: Vec<Value>,
 
-impl CallFrame {
-    fn read_byte(&mut self) -> bytecode::Code {
-        let byte = self.function.chunk.read_byte(self.ip);
-        self.ip += 1;
-        byte
-    }
+    strings: Strings,
 
-    fn local_variable(
-        &self,
-        stack: &[generational_arena::Index],
-        index: usize,
-    ) -> generational_arena::Index {
-        stack[self.fp + index + 1]
-    }
-}
+    // the objects allocated by the program, counted against the heap object limit
+    objects: Objects,
 
-pub struct VM {
-    frames: Vec<CallFrame>,
+    diagnostic_with_color: bool,
 
-    heap: generational_arena::Arena<Value>,
+    // values of the global variables, indexed by the slots resolved at compile time
+    globals: Vec<Option<Value>>,
 
-    pub stack: Vec<generational_arena::Index>,
+    // names of the global variables, used for diagnostics and debugging
+    global_variables: GlobalVariables,
 
-    diagnostic_with_color: bool,
+    // number of instructions executed so far
+    instruction_count: u64,
 
-    // global variables
-    globals: HashMap<String, Value>,
+    // the file the program being executed was loaded from
+    input_file: InputFile,
+
+    limits: ExecutionLimits,
+
+    // the point in time at which the program is stopped, if `limits` has a timeout
+    deadline: Option<Instant>,
 }
 
 impl VM {
     pub fn new(db: &dyn crate::Db, main: Function, diagnostic_with_color: bool) -> Self {
         let function = compile_fn(db, main);
+        let input_file = main.body(db).input_file(db);
+        let global_variables = lox_compile::global_variables(db, input_file).clone();
         let frame = CallFrame {
             function,
             ip: 0,
             fp: 0,
+            given_arguments: vec![],
         };
 
-        let mut heap = generational_arena::Arena::new();
-        let index_of_main = heap.insert(Value::Function(main));
         // push the value of the main function to the stack to a call to the main function,
         // making it is consistent with other function calls.
-        let stack = vec![index_of_main];
+        let stack = vec![Value::Function(main)];
+
+        // the natives are defined before the program runs, the program may redefine them
+        let globals = (0..global_variables.len())
+            .map(|slot| {
+                Native::from_name(global_variables.name(slot).as_str(db)).map(Value::Native)
+            })
+            .collect();
 
         Self {
             frames: vec![frame],
-            heap,
             stack,
+            strings: Strings::default(),
+            objects: Objects::default(),
             diagnostic_with_color,
-            globals: HashMap::new(),
+            globals,
+            global_variables,
+            instruction_count: 0,
+            input_file,
+            limits: ExecutionLimits::default(),
+            deadline: None,
         }
     }
 
-    pub fn push_frame(&mut self, function: CompiledFunction) {
+    /// Sets the limits on the resources the program may use,
+    /// the timeout starts counting when this is called.
+    pub fn with_limits(mut self, limits: ExecutionLimits) -> Self {
+        self.deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.limits = limits;
+        self
+    }
+
+    /// Pushes a frame to call `function`, whose parameters are at the top of the stack, and
+    /// `given_arguments` tells which of them the call gave an argument.
+    /// Returns a "stack overflow" diagnostic if that would exceed the maximum call depth.
+    pub fn push_frame(
+        &mut self,
+        db: &dyn crate::Db,
+        function: CompiledFunction,
+        given_arguments: Vec<bool>,
+    ) -> Result<(), Diagnostic> {
+        if let Some(max_call_depth) = self.limits.max_call_depth {
+            if self.frames.len() >= max_call_depth {
+                return Err(self.stack_overflow(db, max_call_depth));
+            }
+        }
+
         let arity = function.arity;
 
         let frame = CallFrame {
@@ -234,24 +235,73 @@ impl VM {
             //
             // the the fp is `6 - 3 - 1 = 2`, -1 for the function itself.
             fp: self.stack.len() - arity - 1,
+            given_arguments,
         };
         tracing::debug!("pushing frame: {:?}", frame);
         self.frames.push(frame);
+        Ok(())
+    }
+
+    fn stack_overflow(&self, db: &dyn crate::Db, max_call_depth: usize) -> Diagnostic {
+        // every frame on the stack is executing a call, which is the instruction before its `ip`
+        let frames: Vec<TraceFrame> = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| TraceFrame {
+                function: frame.function.name.clone(),
+                span: frame.span(self.input_file, frame.ip.saturating_sub(1)),
+            })
+            .collect();
+        let span = frames[0].span;
+        lox_ir::error!(span, "stack overflow")
+            .primary_label(format!(
+                "this call exceeds the maximum call depth of {}",
+                max_call_depth
+            ))
+            .child(lox_ir::note!(span, "{}", format_stack_trace(db, &frames)).finish())
+            .finish()
+    }
+
+    // Replaces the `arity` arguments at the top of the stack, the last ones named by `names`,
+    // with the values of the parameters of `function`. Returns whether each parameter was
+    // given an argument, the others are `nil` until the function evaluates their default value.
+    fn bind_arguments(
+        &mut self,
+        db: &dyn crate::Db,
+        function: Function,
+        arity: usize,
+        names: &[Word],
+    ) -> Result<Vec<bool>, ArgumentError> {
+        let bindings = bind_arguments(&function.params(db), arity - names.len(), names)?;
+        let arguments = self.stack.split_off(self.stack.len() - arity);
+        let mut given_arguments = Vec::with_capacity(bindings.len());
+        for binding in bindings {
+            given_arguments.push(binding != Binding::Default);
+            let value = match binding {
+                Binding::Argument(index) => arguments[index].clone(),
+                Binding::Default => Value::Nil,
+                Binding::Rest(indices) => {
+                    let values = indices.into_iter().map(|i| arguments[i].clone()).collect();
+                    self.objects.allocate(List::new(values))
+                }
+            };
+            self.push(value);
+        }
+        Ok(given_arguments)
     }
 
-    fn current_frame(&self) -> Option<CallFrame> {
-        self.frames.last().cloned()
+    fn current_frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
     }
 
-    fn update_frame(&mut self, frame_index: usize, frame: CallFrame) {
-        self.frames[frame_index] = frame;
+    fn current_frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
     }
 
-    // clear the values introduced by the current frame from the stack
-    fn clear_stack(&mut self, frame: CallFrame) {
-        // FIXME: As the value in the stack is just an index into the heap,
-        // truncating the stack does not free the memory in the heap.
-        // This is a memory leak.
+    // pop the current frame, and clear the values introduced by it from the stack
+    fn pop_frame(&mut self) {
+        let frame = self.frames.pop().unwrap();
         self.stack.truncate(frame.fp);
     }
 
@@ -263,92 +313,175 @@ impl VM {
         }
     }
 
-    // `step_inspect` is a callback that is called after each instruction is executed.
-    //  It is useful for debugging.
-    pub(crate) fn step<F>(
+    fn check_instruction_limits(&self, span: FileSpan) -> Result<(), Diagnostic> {
+        if let Some(max_instructions) = self.limits.max_instructions {
+            if self.instruction_count > max_instructions {
+                return Err(lox_ir::error!(span, "instruction limit exceeded")
+                    .primary_label(format!(
+                        "the program was stopped here after executing {} instructions",
+                        max_instructions
+                    ))
+                    .finish());
+            }
+        }
+
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.limits.timeout) {
+            if self.instruction_count % DEADLINE_CHECK_INTERVAL == 0 && Instant::now() >= deadline {
+                return Err(lox_ir::error!(span, "time limit exceeded")
+                    .primary_label(format!(
+                        "the program was stopped here after running for {:?}",
+                        timeout
+                    ))
+                    .finish());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_heap_objects_limit(&mut self, span: FileSpan) -> Result<(), Diagnostic> {
+        if let Some(max_heap_objects) = self.limits.max_heap_objects {
+            if self.heap_objects() > max_heap_objects {
+                // only the objects that are still alive count against the limit
+                self.strings.collect();
+                self.objects.collect();
+            }
+            if self.heap_objects() > max_heap_objects {
+                return Err(lox_ir::error!(span, "heap object limit exceeded")
+                    .primary_label(format!(
+                        "this allocation would keep more than {} objects alive",
+                        max_heap_objects
+                    ))
+                    .finish());
+            }
+        }
+        Ok(())
+    }
+
+    // the number of strings and objects allocated by the program, see `check_heap_objects_limit`
+    fn heap_objects(&self) -> usize {
+        self.strings.len() + self.objects.len()
+    }
+
+    /// Runs the program until it finishes, see [`VM::run_with`].
+    pub fn run(&mut self, db: &dyn crate::Db, kernel: &mut impl Kernel) -> Result<(), Diagnostic> {
+        self.run_with(db, kernel, &mut ()).map(|_| ())
+    }
+
+    /// Runs the program, calling `observer` around every step, until it finishes or the
+    /// observer pauses it. Returns [`ControlFlow::Next`] if it was paused, calling `run_with`
+    /// again resumes it.
+    ///
+    /// Returns an error if the program fails or exceeds one of the execution limits,
+    /// the VM must not be run any further after that.
+    pub fn run_with<K: Kernel>(
         &mut self,
         db: &dyn crate::Db,
-        kernel: &mut impl Kernel,
-        mut step_inspect: Option<F>,
-    ) -> ControlFlow
-    where
-        F: FnMut(Option<bytecode::Code>, &VM),
-    {
-        let mut inspect_step = |instruction, self_| {
-            if let Some(step_inspect) = &mut step_inspect {
-                step_inspect(instruction, self_);
+        kernel: &mut K,
+        observer: &mut impl Observer<K>,
+    ) -> Result<ControlFlow, Diagnostic> {
+        loop {
+            observer.before_step(self);
+            let (control_flow, instruction) = self.step(db, kernel)?;
+            let keep_running = observer.after_step(self, kernel, instruction.as_ref());
+            if control_flow == ControlFlow::Done {
+                return Ok(ControlFlow::Done);
             }
-        };
+            if !keep_running {
+                return Ok(ControlFlow::Next);
+            }
+        }
+    }
 
-        let mut frame = self.current_frame().unwrap();
-        let frame_index = self.frames.len() - 1;
+    // Executes the next instruction, and returns it along with what the VM does next.
+    // No instruction is executed when the current function has run out of instructions,
+    // the step returns from it instead.
+    fn step(
+        &mut self,
+        db: &dyn crate::Db,
+        kernel: &mut impl Kernel,
+    ) -> Result<(ControlFlow, Option<bytecode::Code>), Diagnostic> {
+        let frame = self.current_frame();
         tracing::debug!("current frame: {:#?}", frame);
         if frame.function.chunk.len() <= frame.ip {
-            // clear the values introduced by the current frame from the stack
-            self.clear_stack(frame);
-
-            // pop the current frame
-            self.frames.pop();
-
-            inspect_step(None, self);
-
-            return self.done_or_next();
+            self.pop_frame();
+            return Ok((self.done_or_next(), None));
         }
+        let span = frame.span(self.input_file, frame.ip);
+        self.instruction_count += 1;
+        self.check_instruction_limits(span)?;
+
+        let frame = self.current_frame_mut();
         let instruction = frame.read_byte();
         tracing::debug!("ip: {}", frame.ip);
-        tracing::debug!("stack: {:?}", &self.stack_values()[frame.fp..]);
+        let fp = frame.fp;
+        tracing::debug!("stack: {:?}", &self.stack[fp..]);
         tracing::debug!("instruction: {:?}", instruction);
         match instruction.clone() {
             bytecode::Code::Return => {
                 // return the value at the top of the stack
                 let value = self.pop();
 
-                // clear the values introduced by the current frame from the stack
-                self.clear_stack(frame);
-                // pop the current frame
-                self.frames.pop();
+                self.pop_frame();
 
                 // push the return value to the stack
                 self.push(value);
 
-                inspect_step(Some(instruction), self);
-                return self.done_or_next();
+                return Ok((self.done_or_next(), Some(instruction)));
             }
             bytecode::Code::Constant(value) => self.push(value.0),
             bytecode::Code::Add => {
                 let b = self.pop();
                 let a = self.pop();
-                self.push(a + b);
-            }
-            bytecode::Code::Subtract => {
-                let b = self.pop();
-                let a = self.pop();
-                self.push(a - b);
-            }
-            bytecode::Code::Multiply => {
-                let b = self.pop();
-                let a = self.pop();
-                self.push(a * b);
-            }
-            bytecode::Code::Divide => {
-                let b = self.pop();
-                let a = self.pop();
-                self.push(a / b);
+                match (a, b) {
+                    (Value::Number(a), Value::Number(b)) => self.push(a + b),
+                    (Value::String(a), Value::String(b)) => {
+                        let mut s = String::with_capacity(a.len() + b.len());
+                        s.push_str(&a);
+                        s.push_str(&b);
+                        let s = self.strings.intern_rc(s.into());
+                        self.push(s);
+                        self.check_heap_objects_limit(span)?;
+                    }
+                    (a, b) => {
+                        return Err(lox_ir::error!(
+                            span,
+                            "operands must be two numbers or two strings"
+                        )
+                        .primary_label(format!(
+                            "cannot add {} and {}",
+                            a.type_name(),
+                            b.type_name()
+                        ))
+                        .finish())
+                    }
+                }
             }
+            bytecode::Code::Subtract => self.arithmetic(span, "subtract", |a, b| a - b)?,
+            bytecode::Code::Multiply => self.arithmetic(span, "multiply", |a, b| a * b)?,
+            bytecode::Code::Divide => self.arithmetic(span, "divide", |a, b| a / b)?,
             bytecode::Code::True => {
                 self.push(true);
             }
             bytecode::Code::False => {
                 self.push(false);
             }
-            bytecode::Code::Negate => {
-                let a = self.pop();
-                self.push(-a);
-            }
-            bytecode::Code::Not => {
-                let a = self.pop();
-                self.push(!a);
-            }
+            bytecode::Code::Negate => match self.pop() {
+                Value::Number(a) => self.push(-a),
+                a => {
+                    return Err(lox_ir::error!(span, "operand must be a number")
+                        .primary_label(format!("cannot negate {}", a.type_name()))
+                        .finish())
+                }
+            },
+            bytecode::Code::Not => match self.pop() {
+                Value::Boolean(a) => self.push(!a),
+                a => {
+                    return Err(lox_ir::error!(span, "operand must be a boolean")
+                        .primary_label(format!("cannot apply `!` to {}", a.type_name()))
+                        .finish())
+                }
+            },
             bytecode::Code::Equal => {
                 let b = self.pop();
                 let a = self.pop();
@@ -380,36 +513,55 @@ impl VM {
                 self.push(a <= b);
             }
             bytecode::Code::String(s) => {
+                let s = self.strings.intern(&s);
                 self.push(s);
+                self.check_heap_objects_limit(span)?;
+            }
+            bytecode::Code::Concat(count) => {
+                let parts = self.stack.split_off(self.stack.len() - count);
+                let s: String = parts
+                    .iter()
+                    .map(|part| part.display(db).to_string())
+                    .collect();
+                let s = self.strings.intern_rc(s.into());
+                self.push(s);
+                self.check_heap_objects_limit(span)?;
             }
             bytecode::Code::Print => {
                 let value = self.pop();
-                kernel.print(&format!("{}", value));
+                kernel.print(&value.display(db).to_string());
             }
-            bytecode::Code::GlobalVarDeclaration { name } => {
+            bytecode::Code::GlobalVarDeclaration { slot } => {
                 let value = self.pop();
-                self.globals.insert(name, value);
+                self.globals[slot] = Some(value);
             }
             bytecode::Code::Nil => {
                 self.push(Value::Nil);
             }
-            bytecode::Code::ReadGlobalVariable { name } => {
-                let value = self.globals.get(&name).expect("variable not found");
+            bytecode::Code::ReadGlobalVariable { slot } => {
+                let Some(value) = &self.globals[slot] else {
+                    let name = self.global_variables.name(slot);
+                    return Err(
+                        lox_ir::error!(span, "undefined variable `{}`", name.as_str(db))
+                            .primary_label("the variable is read before it is defined")
+                            .finish(),
+                    );
+                };
                 self.push(value.clone());
             }
-            bytecode::Code::WriteGlobalVariable { name } => {
+            bytecode::Code::WriteGlobalVariable { slot } => {
                 let value = self.peek();
-                self.globals.insert(name, value.clone());
+                self.globals[slot] = Some(value.clone());
             }
             bytecode::Code::ReadLocalVariable { index_in_stack } => {
-                let value_idx = frame.local_variable(&self.stack, index_in_stack);
-                let value = self.heap[value_idx].clone();
+                let index = self.current_frame().local_variable(index_in_stack);
+                let value = self.stack[index].clone();
                 self.push(value);
             }
             bytecode::Code::WriteLocalVariable { index_in_stack } => {
-                let value = self.peek();
-                let value_idx = frame.local_variable(&self.stack, index_in_stack);
-                self.heap[value_idx] = value.clone();
+                let value = self.peek().clone();
+                let index = self.current_frame().local_variable(index_in_stack);
+                self.stack[index] = value;
             }
             bytecode::Code::Pop => {
                 self.pop();
@@ -417,20 +569,35 @@ impl VM {
             bytecode::Code::JumpIfFalse(ip) => {
                 let value = self.peek();
                 if value == &Value::Boolean(false) {
-                    frame.ip = ip;
+                    self.current_frame_mut().ip = ip;
                 }
             }
             bytecode::Code::Jump(ip) => {
-                frame.ip = ip;
+                self.current_frame_mut().ip = ip;
+            }
+            bytecode::Code::JumpIfArgumentGiven { index_in_stack, ip } => {
+                if self.current_frame().given_arguments[index_in_stack] {
+                    self.current_frame_mut().ip = ip;
+                }
             }
-            bytecode::Code::Call { arity } => {
+            bytecode::Code::Call { arity, names } => {
                 let closure = self.peek_n_from_top(arity);
                 match closure {
                     Value::Function(function) => {
-                        let compiled_function = compile_fn(db, *function);
-                        let diagnostics = compile_fn::accumulated::<Diagnostics>(db, *function);
+                        let function = *function;
+                        let compiled_function = compile_fn(db, function);
+                        let given_arguments = self
+                            .bind_arguments(db, function, arity, &names)
+                            .map_err(|error| {
+                                lox_ir::error!(span, "{}", error.message(db))
+                                    .primary_label(error.label(&compiled_function.name))
+                                    .finish()
+                            })?;
+                        // a rest parameter allocates a list
+                        self.check_heap_objects_limit(span)?;
+                        let diagnostics = compile_fn::accumulated::<Diagnostics>(db, function);
                         if diagnostics.is_empty() {
-                            self.push_frame(compiled_function);
+                            self.push_frame(db, compiled_function, given_arguments)?;
                         } else {
                             let output = lox_error_format::format_diagnostics_with_options(
                                 db,
@@ -443,48 +610,286 @@ impl VM {
                             kernel.print(&output);
                         }
                     }
-                    _ => panic!("Cannot call {:?}", closure),
+                    Value::Native(native) => {
+                        let native = *native;
+                        if !names.is_empty() {
+                            return Err(lox_ir::error!(
+                                span,
+                                "`{}` does not take named arguments",
+                                native.name()
+                            )
+                            .primary_label("named arguments are only for functions written in lox")
+                            .finish());
+                        }
+                        if native.arity() != arity {
+                            return Err(lox_ir::error!(
+                                span,
+                                "expected {} but got {}",
+                                lox_ir::function::argument_count(native.arity()),
+                                arity
+                            )
+                            .primary_label(format!(
+                                "`{}` is called with the wrong number of arguments",
+                                native.name()
+                            ))
+                            .finish());
+                        }
+                        if !kernel.capabilities().allows(native.capability()) {
+                            return Err(lox_ir::error!(span, "`{}` is not allowed", native.name())
+                                .primary_label(format!(
+                                    "the host does not grant the `{}` capability",
+                                    native.capability().name()
+                                ))
+                                .finish());
+                        }
+                        let arguments = self.stack.split_off(self.stack.len() - arity);
+                        self.pop();
+                        let value = native.call(db, kernel, &arguments).map_err(|message| {
+                            lox_ir::error!(span, "{}", message)
+                                .primary_label(format!("in this call to `{}`", native.name()))
+                                .finish()
+                        })?;
+                        if native == Native::Exit {
+                            self.frames.clear();
+                            self.stack.clear();
+                            return Ok((ControlFlow::Done, Some(instruction)));
+                        }
+                        self.push_external_value(value, span)?;
+                    }
+                    _ => {
+                        return Err(lox_ir::error!(span, "can only call functions")
+                            .primary_label(format!("cannot call {}", closure.type_name()))
+                            .finish())
+                    }
                 }
             }
             bytecode::Code::Function(function) => {
                 self.push(Value::Function(function));
             }
+            bytecode::Code::GetProperty(name) => {
+                let object = self.pop();
+                let host = host_object(&object, span, &format!("read property `{}` of", name))?;
+                let result = host.borrow().get(&name);
+                let value = result.map_err(|message| host_error(span, host, message))?;
+                self.push_external_value(value, span)?;
+            }
+            bytecode::Code::SetProperty(name) => {
+                let value = self.pop();
+                let object = self.pop();
+                let host = host_object(&object, span, &format!("set property `{}` of", name))?;
+                let result = host.borrow_mut().set(&name, value.clone());
+                result.map_err(|message| host_error(span, host, message))?;
+                self.push(value);
+            }
+            bytecode::Code::Invoke { name, arity } => {
+                let arguments = self.stack.split_off(self.stack.len() - arity);
+                let object = self.pop();
+                let host = host_object(&object, span, &format!("call method `{}` of", name))?;
+                let result = host.borrow_mut().call_method(&name, arguments);
+                let value = result.map_err(|message| host_error(span, host, message))?;
+                self.push_external_value(value, span)?;
+            }
         }
 
-        inspect_step(Some(instruction), self);
-        self.update_frame(frame_index, frame);
-        ControlFlow::Next
+        Ok((ControlFlow::Next, Some(instruction)))
+    }
+
+    // Pops two numbers and pushes the result of `op` on them.
+    fn arithmetic(
+        &mut self,
+        span: FileSpan,
+        verb: &str,
+        op: fn(f64, f64) -> f64,
+    ) -> Result<(), Diagnostic> {
+        let b = self.pop();
+        let a = self.pop();
+        match (&a, &b) {
+            (Value::Number(a), Value::Number(b)) => {
+                self.push(op(*a, *b));
+                Ok(())
+            }
+            _ => Err(lox_ir::error!(span, "operands must be numbers")
+                .primary_label(format!(
+                    "cannot {} {} and {}",
+                    verb,
+                    a.type_name(),
+                    b.type_name()
+                ))
+                .finish()),
+        }
+    }
+
+    // Pushes a value returned by a host object or a native, interning it if it is a string.
+    fn push_external_value(&mut self, value: Value, span: FileSpan) -> Result<(), Diagnostic> {
+        match value {
+            Value::String(s) => {
+                let s = self.strings.intern_rc(s);
+                self.push(s);
+                self.check_heap_objects_limit(span)
+            }
+            value => {
+                self.push(value);
+                Ok(())
+            }
+        }
     }
 
     fn pop(&mut self) -> Value {
-        let index = self.stack.pop().unwrap();
-        self.heap.remove(index).unwrap()
+        self.stack.pop().unwrap()
     }
 
     fn peek(&self) -> &Value {
-        let index = self.stack.last().unwrap();
-        self.heap.get(*index).unwrap()
+        self.stack.last().unwrap()
     }
 
     fn peek_n_from_top(&self, n: usize) -> &Value {
-        let index = self.stack[self.stack.len() - n - 1];
-        self.heap.get(index).unwrap()
+        &self.stack[self.stack.len() - n - 1]
     }
 
     fn push<T>(&mut self, value: T)
     where
         T: Into<Value>,
     {
-        let index = self.heap.insert(value.into());
-        self.stack.push(index);
+        self.stack.push(value.into());
+    }
+
+    /// Returns the number of instructions executed so far.
+    pub fn instruction_count(&self) -> u64 {
+        self.instruction_count
     }
 
     // Returns the values in the stack in the order they were pushed.
     // This is useful for debugging.
-    pub fn stack_values(&self) -> Vec<Value> {
-        self.stack
+    pub fn stack_values(&self) -> &[Value] {
+        &self.stack
+    }
+
+    /// Returns the frames of the call stack, from the innermost to the outermost one.
+    pub fn frames(&self) -> impl Iterator<Item = Frame<'_>> {
+        self.frames.iter().rev().enumerate().map(|(depth, frame)| {
+            let ip = if depth == 0 {
+                // the innermost frame is about to execute the instruction at `ip`
+                (frame.ip < frame.function.chunk.len()).then_some(frame.ip)
+            } else {
+                // the other frames are executing a call, which is the instruction before `ip`
+                frame.ip.checked_sub(1)
+            };
+            Frame {
+                vm: self,
+                frame,
+                ip,
+            }
+        })
+    }
+
+    /// Returns the number of frames on the call stack.
+    pub fn call_depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the span of the next instruction to execute, if any.
+    pub fn current_span(&self) -> Option<FileSpan> {
+        self.frames().next()?.span()
+    }
+
+    /// Returns the global variables that have been defined so far, with their values.
+    pub fn globals(&self) -> impl Iterator<Item = (Word, &Value)> {
+        self.globals
             .iter()
-            .map(|index| self.heap[*index].clone())
-            .collect()
+            .enumerate()
+            .filter_map(|(slot, value)| Some((self.global_variables.name(slot), value.as_ref()?)))
+    }
+
+    /// Returns the file the program being executed was loaded from.
+    pub fn input_file(&self) -> InputFile {
+        self.input_file
+    }
+
+    /// Returns the value of the global variable `name`, if the program has defined it.
+    pub fn global(&self, db: &dyn crate::Db, name: &str) -> Option<&Value> {
+        let slot = self.global_variables.slot(Word::intern(db, name))?;
+        self.globals[slot].as_ref()
+    }
+
+    /// Sets the global variable `name` to `value`.
+    /// Returns `false` if the program never mentions a global variable with that name,
+    /// since the slots of the global variables are resolved when the program is compiled.
+    pub fn set_global(&mut self, db: &dyn crate::Db, name: &str, value: Value) -> bool {
+        let Some(slot) = self.global_variables.slot(Word::intern(db, name)) else {
+            return false;
+        };
+        self.globals[slot] = Some(value);
+        true
+    }
+
+    /// Calls `function` with `arguments`, runs it until it returns and returns its result.
+    ///
+    /// Returns an error pointing at the body of `function` if the VM has not run the program
+    /// to completion yet, or if `arguments` do not fit the parameters of `function`.
+    /// If the call fails, the call stack is cleared, so the VM can be used for other calls.
+    pub fn call(
+        &mut self,
+        db: &dyn crate::Db,
+        kernel: &mut impl Kernel,
+        function: Function,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Diagnostic> {
+        let compiled_function = compile_fn(db, function);
+        let span = function.body(db).span(db);
+        if !self.frames.is_empty() {
+            return Err(lox_ir::error!(
+                span,
+                "cannot call `{}` while the program is running",
+                compiled_function.name
+            )
+            .primary_label("functions can only be called once the program has finished")
+            .finish());
+        }
+        let arity = arguments.len();
+
+        self.stack.push(Value::Function(function));
+        self.stack.extend(arguments);
+        let result = self
+            .bind_arguments(db, function, arity, &[])
+            .map_err(|error| {
+                lox_ir::error!(span, "{}", error.message(db))
+                    .primary_label(error.label(&compiled_function.name))
+                    .finish()
+            })
+            .and_then(|given_arguments| {
+                self.check_heap_objects_limit(span)?;
+                self.push_frame(db, compiled_function, given_arguments)
+            })
+            .and_then(|()| self.run(db, kernel));
+        if let Err(diagnostic) = result {
+            self.frames.clear();
+            self.stack.clear();
+            return Err(diagnostic);
+        }
+
+        // a function without a `return` statement leaves nothing on the stack
+        Ok(self.stack.pop().unwrap_or(Value::Nil))
+    }
+}
+
+// Returns the host object that `value` holds, `action` describes what the program tried to do
+// with it, like "read property `x` of".
+fn host_object<'v>(
+    value: &'v Value,
+    span: FileSpan,
+    action: &str,
+) -> Result<&'v Rc<RefCell<dyn HostObject>>, Diagnostic> {
+    match value {
+        Value::Host(host) => Ok(host),
+        _ => Err(lox_ir::error!(span, "only objects have properties")
+            .primary_label(format!("cannot {} {}", action, value.type_name()))
+            .finish()),
     }
 }
+
+// Reports the error `message` returned by a host object.
+fn host_error(span: FileSpan, host: &RefCell<dyn HostObject>, message: String) -> Diagnostic {
+    lox_ir::error!(span, "{}", message)
+        .primary_label(format!("reported by {}", host.borrow().type_name()))
+        .finish()
+}
@@ -0,0 +1,502 @@
+//! A typed view of the concrete syntax tree, see `cst`.
+//!
+//! Each type wraps a `SyntaxNode` of the matching kind. The accessors return `None` for the
+//! parts that are missing because of a syntax error, which the parser already reported.
+
+use crate::{
+    cst::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken},
+    syntax::Op,
+    token::Token,
+    token_tree::TokenTree,
+};
+
+macro_rules! ast_nodes {
+    ($($name:ident => $kind:ident,)*) => {
+        $(
+            #[derive(Clone, Debug, PartialEq, Eq)]
+            pub struct $name(SyntaxNode);
+
+            impl $name {
+                pub fn cast(node: SyntaxNode) -> Option<Self> {
+                    if node.kind() == SyntaxKind::$kind {
+                        Some(Self(node))
+                    } else {
+                        None
+                    }
+                }
+
+                pub fn syntax(&self) -> &SyntaxNode {
+                    &self.0
+                }
+            }
+        )*
+    };
+}
+
+ast_nodes! {
+    Tree => TokenTree,
+    VarDecl => VarDecl,
+    FunDecl => FunDecl,
+    Param => Param,
+    ExprStmt => ExprStmt,
+    PrintStmt => PrintStmt,
+    Block => Block,
+    IfStmt => IfStmt,
+    WhileStmt => WhileStmt,
+    ForStmt => ForStmt,
+    ReturnStmt => ReturnStmt,
+    ErrorStmt => ErrorStmt,
+    Literal => Literal,
+    NameRef => NameRef,
+    InterpolatedString => InterpolatedString,
+    ParenExpr => ParenExpr,
+    UnaryExpr => UnaryExpr,
+    BinaryExpr => BinaryExpr,
+    AssignExpr => AssignExpr,
+    CallExpr => CallExpr,
+    NamedArg => NamedArg,
+    FieldExpr => FieldExpr,
+    Lambda => Lambda,
+    ErrorExpr => ErrorExpr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stmt {
+    Var(VarDecl),
+    Fun(FunDecl),
+    Expr(ExprStmt),
+    Print(PrintStmt),
+    Block(Block),
+    If(IfStmt),
+    While(WhileStmt),
+    For(ForStmt),
+    Return(ReturnStmt),
+    Error(ErrorStmt),
+}
+
+impl Stmt {
+    pub fn cast(node: SyntaxNode) -> Option<Self> {
+        let stmt = match node.kind() {
+            SyntaxKind::VarDecl => Stmt::Var(VarDecl(node)),
+            SyntaxKind::FunDecl => Stmt::Fun(FunDecl(node)),
+            SyntaxKind::ExprStmt => Stmt::Expr(ExprStmt(node)),
+            SyntaxKind::PrintStmt => Stmt::Print(PrintStmt(node)),
+            SyntaxKind::Block => Stmt::Block(Block(node)),
+            SyntaxKind::IfStmt => Stmt::If(IfStmt(node)),
+            SyntaxKind::WhileStmt => Stmt::While(WhileStmt(node)),
+            SyntaxKind::ForStmt => Stmt::For(ForStmt(node)),
+            SyntaxKind::ReturnStmt => Stmt::Return(ReturnStmt(node)),
+            SyntaxKind::ErrorStmt => Stmt::Error(ErrorStmt(node)),
+            _ => return None,
+        };
+        Some(stmt)
+    }
+
+    pub fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Stmt::Var(stmt) => stmt.syntax(),
+            Stmt::Fun(stmt) => stmt.syntax(),
+            Stmt::Expr(stmt) => stmt.syntax(),
+            Stmt::Print(stmt) => stmt.syntax(),
+            Stmt::Block(stmt) => stmt.syntax(),
+            Stmt::If(stmt) => stmt.syntax(),
+            Stmt::While(stmt) => stmt.syntax(),
+            Stmt::For(stmt) => stmt.syntax(),
+            Stmt::Return(stmt) => stmt.syntax(),
+            Stmt::Error(stmt) => stmt.syntax(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Literal(Literal),
+    NameRef(NameRef),
+    InterpolatedString(InterpolatedString),
+    Paren(ParenExpr),
+    Unary(UnaryExpr),
+    Binary(BinaryExpr),
+    Assign(AssignExpr),
+    Call(CallExpr),
+    Field(FieldExpr),
+    Lambda(Lambda),
+    Error(ErrorExpr),
+}
+
+impl Expr {
+    pub fn cast(node: SyntaxNode) -> Option<Self> {
+        let expr = match node.kind() {
+            SyntaxKind::Literal => Expr::Literal(Literal(node)),
+            SyntaxKind::NameRef => Expr::NameRef(NameRef(node)),
+            SyntaxKind::InterpolatedString => Expr::InterpolatedString(InterpolatedString(node)),
+            SyntaxKind::ParenExpr => Expr::Paren(ParenExpr(node)),
+            SyntaxKind::UnaryExpr => Expr::Unary(UnaryExpr(node)),
+            SyntaxKind::BinaryExpr => Expr::Binary(BinaryExpr(node)),
+            SyntaxKind::AssignExpr => Expr::Assign(AssignExpr(node)),
+            SyntaxKind::CallExpr => Expr::Call(CallExpr(node)),
+            SyntaxKind::FieldExpr => Expr::Field(FieldExpr(node)),
+            SyntaxKind::Lambda => Expr::Lambda(Lambda(node)),
+            SyntaxKind::ErrorExpr => Expr::Error(ErrorExpr(node)),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    pub fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Expr::Literal(expr) => expr.syntax(),
+            Expr::NameRef(expr) => expr.syntax(),
+            Expr::InterpolatedString(expr) => expr.syntax(),
+            Expr::Paren(expr) => expr.syntax(),
+            Expr::Unary(expr) => expr.syntax(),
+            Expr::Binary(expr) => expr.syntax(),
+            Expr::Assign(expr) => expr.syntax(),
+            Expr::Call(expr) => expr.syntax(),
+            Expr::Field(expr) => expr.syntax(),
+            Expr::Lambda(expr) => expr.syntax(),
+            Expr::Error(expr) => expr.syntax(),
+        }
+    }
+}
+
+// the typed children of a node, in order
+fn children<T>(node: &SyntaxNode, cast: fn(SyntaxNode) -> Option<T>) -> Vec<T> {
+    node.children().filter_map(cast).collect()
+}
+
+fn child<T>(node: &SyntaxNode, cast: fn(SyntaxNode) -> Option<T>) -> Option<T> {
+    node.children().find_map(cast)
+}
+
+// the alphabetic tokens of a node, keywords included
+fn words(node: &SyntaxNode) -> Vec<SyntaxToken> {
+    node.tokens()
+        .filter(|token| matches!(token.token, Token::Alphabetic(_)))
+        .collect()
+}
+
+impl Tree {
+    /// The tree that the node was built from.
+    pub fn token_tree(&self) -> TokenTree {
+        self.0.token_tree().unwrap()
+    }
+
+    pub fn stmts(&self) -> Vec<Stmt> {
+        children(&self.0, Stmt::cast)
+    }
+
+    pub fn exprs(&self) -> Vec<Expr> {
+        children(&self.0, Expr::cast)
+    }
+
+    /// The expression that fills the tree, like the condition of an `if`.
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+}
+
+impl VarDecl {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        // the first word is `var`
+        words(&self.0).into_iter().nth(1)
+    }
+
+    pub fn initializer(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+}
+
+impl FunDecl {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        // the first word is `fun`
+        words(&self.0).into_iter().nth(1)
+    }
+
+    /// The parameters that parsed, an invalid parameter list ends at the first error.
+    pub fn params(&self) -> Vec<Param> {
+        params(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Block> {
+        child(&self.0, Block::cast)
+    }
+}
+
+// the parameters in the first tree of a function, see `FunDecl::params`
+fn params(node: &SyntaxNode) -> Vec<Param> {
+    child(node, Tree::cast)
+        .map(|tree| children(&tree.0, Param::cast))
+        .unwrap_or_default()
+}
+
+impl Param {
+    pub fn name(&self) -> SyntaxToken {
+        words(&self.0).into_iter().next().unwrap()
+    }
+
+    /// The default value of `b = 2`.
+    pub fn default_value(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+
+    /// Whether this is the rest parameter, like `...rest`.
+    pub fn is_rest(&self) -> bool {
+        self.0.tokens().any(|token| token.token == Token::Ellipsis)
+    }
+}
+
+impl ExprStmt {
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+}
+
+impl PrintStmt {
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+}
+
+impl Block {
+    /// The contents of the braces.
+    pub fn tree(&self) -> Option<Tree> {
+        child(&self.0, Tree::cast)
+    }
+
+    pub fn stmts(&self) -> Vec<Stmt> {
+        self.tree().map(|tree| tree.stmts()).unwrap_or_default()
+    }
+}
+
+impl IfStmt {
+    /// The contents of the parentheses.
+    pub fn condition(&self) -> Option<Tree> {
+        child(&self.0, Tree::cast)
+    }
+
+    pub fn then_branch(&self) -> Option<Stmt> {
+        children(&self.0, Stmt::cast).into_iter().next()
+    }
+
+    pub fn else_branch(&self) -> Option<Stmt> {
+        children(&self.0, Stmt::cast).into_iter().nth(1)
+    }
+}
+
+impl WhileStmt {
+    /// The contents of the parentheses.
+    pub fn condition(&self) -> Option<Tree> {
+        child(&self.0, Tree::cast)
+    }
+
+    pub fn body(&self) -> Option<Stmt> {
+        child(&self.0, Stmt::cast)
+    }
+}
+
+impl ForStmt {
+    /// The contents of the parentheses.
+    pub fn header(&self) -> Option<Tree> {
+        child(&self.0, Tree::cast)
+    }
+
+    pub fn initializer(&self) -> Option<Stmt> {
+        self.header()
+            .and_then(|header| child(&header.0, Stmt::cast))
+    }
+
+    /// The expression before the second `;` of the header.
+    pub fn condition(&self) -> Option<Expr> {
+        self.clauses().0
+    }
+
+    /// The expression after the second `;` of the header.
+    pub fn increment(&self) -> Option<Expr> {
+        self.clauses().1
+    }
+
+    // the condition and the increment, the `;` of the initializer is inside its statement
+    // unless there is no initializer
+    fn clauses(&self) -> (Option<Expr>, Option<Expr>) {
+        let Some(header) = self.header() else {
+            return (None, None);
+        };
+        let mut semicolons = 0;
+        let mut condition = None;
+        let mut increment = None;
+        for child in header.0.children_with_tokens() {
+            match child {
+                SyntaxElement::Token(token) if token.token == Token::Semicolon => semicolons += 1,
+                SyntaxElement::Token(_) => {}
+                SyntaxElement::Node(node) => {
+                    if Stmt::cast(node.clone()).is_some() {
+                        // the initializer
+                        semicolons += 1;
+                    } else if let Some(expr) = Expr::cast(node) {
+                        if semicolons < 2 && condition.is_none() {
+                            condition = Some(expr);
+                        } else {
+                            increment = Some(expr);
+                        }
+                    }
+                }
+            }
+        }
+        (condition, increment)
+    }
+
+    pub fn body(&self) -> Option<Stmt> {
+        child(&self.0, Stmt::cast)
+    }
+}
+
+impl ReturnStmt {
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+}
+
+impl Literal {
+    pub fn token(&self) -> SyntaxToken {
+        self.0.tokens().next().unwrap()
+    }
+}
+
+impl NameRef {
+    pub fn name(&self) -> SyntaxToken {
+        self.0.tokens().next().unwrap()
+    }
+}
+
+impl InterpolatedString {
+    /// The pieces of text, as `Token::String`s, and the trees of the interpolated expressions.
+    pub fn tree(&self) -> Tree {
+        child(&self.0, Tree::cast).unwrap()
+    }
+}
+
+impl ParenExpr {
+    /// The contents of the parentheses.
+    pub fn tree(&self) -> Option<Tree> {
+        child(&self.0, Tree::cast)
+    }
+}
+
+impl UnaryExpr {
+    pub fn op(&self) -> Option<Op> {
+        op(&self.0)
+    }
+
+    pub fn operand(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+}
+
+/// The operator of a `BinaryExpr`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Op(Op),
+    And,
+    Or,
+}
+
+impl BinaryExpr {
+    pub fn op(&self, db: &dyn crate::Db) -> Option<BinaryOp> {
+        match words(&self.0).first() {
+            Some(word) => match word.token.alphabetic_str(db)? {
+                "and" => Some(BinaryOp::And),
+                "or" => Some(BinaryOp::Or),
+                _ => None,
+            },
+            None => op(&self.0).map(BinaryOp::Op),
+        }
+    }
+
+    pub fn lhs(&self) -> Option<Expr> {
+        children(&self.0, Expr::cast).into_iter().next()
+    }
+
+    pub fn rhs(&self) -> Option<Expr> {
+        children(&self.0, Expr::cast).into_iter().nth(1)
+    }
+}
+
+// the operator made of the `Token::Op`s of a node, like `==`
+fn op(node: &SyntaxNode) -> Option<Op> {
+    let str: String = node
+        .tokens()
+        .filter_map(|token| match token.token {
+            Token::Op(ch) => Some(ch),
+            _ => None,
+        })
+        .collect();
+    Op::all().find(|op| op.str() == str)
+}
+
+impl AssignExpr {
+    pub fn target(&self) -> Option<Expr> {
+        children(&self.0, Expr::cast).into_iter().next()
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        children(&self.0, Expr::cast).into_iter().nth(1)
+    }
+}
+
+impl CallExpr {
+    pub fn callee(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+
+    /// The positional arguments that parsed, an invalid argument list ends at the first
+    /// missing `,`.
+    pub fn arguments(&self) -> Vec<Expr> {
+        child(&self.0, Tree::cast)
+            .map(|tree| tree.exprs())
+            .unwrap_or_default()
+    }
+
+    /// The arguments passed by name, like `name: 1`.
+    pub fn named_arguments(&self) -> Vec<NamedArg> {
+        child(&self.0, Tree::cast)
+            .map(|tree| children(&tree.0, NamedArg::cast))
+            .unwrap_or_default()
+    }
+}
+
+impl NamedArg {
+    pub fn name(&self) -> SyntaxToken {
+        words(&self.0).into_iter().next().unwrap()
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+}
+
+impl FieldExpr {
+    pub fn object(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+
+    pub fn name(&self) -> Option<SyntaxToken> {
+        words(&self.0).into_iter().last()
+    }
+}
+
+impl Lambda {
+    /// The parameters that parsed, see `FunDecl::params`.
+    pub fn params(&self) -> Vec<Param> {
+        params(&self.0)
+    }
+
+    /// The block of `fun (x) { ... }`, or of `(x) => { ... }`.
+    pub fn body_block(&self) -> Option<Block> {
+        child(&self.0, Block::cast)
+    }
+
+    /// The expression that `(x) => x * 2` returns.
+    pub fn body_expr(&self) -> Option<Expr> {
+        child(&self.0, Expr::cast)
+    }
+}
@@ -1,5 +1,7 @@
 use salsa::DebugWithDb;
 
+use crate::span::Span;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Code {
     Return,
@@ -19,19 +21,21 @@ pub enum Code {
     Less,
     LessEqual,
     String(String),
+    // pops that many values and pushes the concatenation of their text, as printed by `print`
+    Concat(usize),
     Print,
     GlobalVarDeclaration {
-        name: String, // name of the variable
+        slot: usize, // slot of the variable in the global variables of the module
     },
     ReadGlobalVariable {
-        name: String, // name of the variable
+        slot: usize, // slot of the variable in the global variables of the module
     },
     ReadLocalVariable {
         index_in_stack: usize, // index of the variable in the stack
     },
     Nil,
     WriteGlobalVariable {
-        name: String, // name of the variable
+        slot: usize, // slot of the variable in the global variables of the module
     },
     WriteLocalVariable {
         index_in_stack: usize, // index of the variable in the stack
@@ -39,12 +43,69 @@ pub enum Code {
     Pop,
     JumpIfFalse(usize),
     Jump(usize),
+    // jumps over the default value of the parameter at `index_in_stack` if the call gave
+    // it an argument
+    JumpIfArgumentGiven {
+        index_in_stack: usize,
+        ip: usize,
+    },
     Function(crate::function::Function),
     Call {
         arity: usize,
+        // the names of the last `names.len()` arguments, which are passed by name
+        names: Vec<crate::word::Word>,
+    },
+    GetProperty(String),
+    SetProperty(String),
+    // calls a method of the object below the arguments, like `foo.bar(1, 2)`
+    Invoke {
+        name: String,
+        arity: usize,
     },
 }
 
+impl Code {
+    /// Returns the name of the instruction, without its operands.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Code::Return => "return",
+            Code::Constant(_) => "constant",
+            Code::True => "true",
+            Code::False => "false",
+            Code::Add => "add",
+            Code::Subtract => "subtract",
+            Code::Multiply => "multiply",
+            Code::Divide => "divide",
+            Code::Negate => "negate",
+            Code::Not => "not",
+            Code::Equal => "equal",
+            Code::NotEqual => "not_equal",
+            Code::Greater => "greater",
+            Code::GreaterEqual => "greater_equal",
+            Code::Less => "less",
+            Code::LessEqual => "less_equal",
+            Code::String(_) => "string",
+            Code::Concat(_) => "concat",
+            Code::Print => "print",
+            Code::GlobalVarDeclaration { .. } => "global_var_declaration",
+            Code::ReadGlobalVariable { .. } => "read_global_variable",
+            Code::ReadLocalVariable { .. } => "read_local_variable",
+            Code::Nil => "nil",
+            Code::WriteGlobalVariable { .. } => "write_global_variable",
+            Code::WriteLocalVariable { .. } => "write_local_variable",
+            Code::Pop => "pop",
+            Code::JumpIfFalse(_) => "jump_if_false",
+            Code::Jump(_) => "jump",
+            Code::JumpIfArgumentGiven { .. } => "jump_if_argument_given",
+            Code::Function(_) => "function",
+            Code::Call { .. } => "call",
+            Code::GetProperty(_) => "get_property",
+            Code::SetProperty(_) => "set_property",
+            Code::Invoke { .. } => "invoke",
+        }
+    }
+}
+
 impl<'db> DebugWithDb<dyn crate::Db + 'db> for Code {
     fn fmt(
         &self,
@@ -70,14 +131,15 @@ impl<'db> DebugWithDb<dyn crate::Db + 'db> for Code {
             Code::Less => write!(f, "less"),
             Code::LessEqual => write!(f, "less_equal"),
             Code::String(s) => write!(f, "string({:?})", s),
+            Code::Concat(count) => write!(f, "concat({})", count),
             Code::Print => write!(f, "print"),
-            Code::GlobalVarDeclaration { name } => write!(f, "global_var_declaration({})", name),
-            Code::ReadGlobalVariable { name } => write!(f, "read_global_variable({})", name),
+            Code::GlobalVarDeclaration { slot } => write!(f, "global_var_declaration({})", slot),
+            Code::ReadGlobalVariable { slot } => write!(f, "read_global_variable({})", slot),
             Code::ReadLocalVariable { index_in_stack } => {
                 write!(f, "read_local_variable({})", index_in_stack)
             }
             Code::Nil => write!(f, "nil"),
-            Code::WriteGlobalVariable { name } => write!(f, "write_global_variable({})", name),
+            Code::WriteGlobalVariable { slot } => write!(f, "write_global_variable({})", slot),
             Code::WriteLocalVariable { index_in_stack } => {
                 write!(f, "write_local_variable({})", index_in_stack)
             }
@@ -85,7 +147,17 @@ impl<'db> DebugWithDb<dyn crate::Db + 'db> for Code {
             Code::JumpIfFalse(ip) => write!(f, "jump_if_false({})", ip),
             Code::Jump(ip) => write!(f, "jump({})", ip),
             Code::Function(function) => write!(f, "function({:?})", &function.debug(db)),
-            Code::Call { arity } => write!(f, "call({})", arity),
+            Code::JumpIfArgumentGiven { index_in_stack, ip } => {
+                write!(f, "jump_if_argument_given({}, {})", index_in_stack, ip)
+            }
+            Code::Call { arity, names } if names.is_empty() => write!(f, "call({})", arity),
+            Code::Call { arity, names } => {
+                let names: Vec<&str> = names.iter().map(|name| name.as_str(db)).collect();
+                write!(f, "call({}, {:?})", arity, names)
+            }
+            Code::GetProperty(name) => write!(f, "get_property({:?})", name),
+            Code::SetProperty(name) => write!(f, "set_property({:?})", name),
+            Code::Invoke { name, arity } => write!(f, "invoke({:?}, {})", name, arity),
         }
     }
 }
@@ -93,6 +165,9 @@ impl<'db> DebugWithDb<dyn crate::Db + 'db> for Code {
 #[derive(PartialEq, Eq, Debug, Clone, Default)]
 pub struct Chunk {
     code: Vec<Code>,
+
+    // the span of the source code each instruction was compiled from, indexed like `code`
+    spans: Vec<Span>,
 }
 
 impl<'db> DebugWithDb<dyn crate::Db + 'db> for Chunk {
@@ -109,9 +184,10 @@ impl<'db> DebugWithDb<dyn crate::Db + 'db> for Chunk {
 }
 
 impl Chunk {
-    pub fn emit_byte(&mut self, byte: Code) -> usize {
+    pub fn emit_byte(&mut self, byte: Code, span: Span) -> usize {
         tracing::debug!(?byte, "emitting byte");
         self.code.push(byte);
+        self.spans.push(span);
         self.len() - 1
     }
 
@@ -119,6 +195,11 @@ impl Chunk {
         self.code[ip].clone()
     }
 
+    /// Returns the span of the source code the instruction at `ip` was compiled from.
+    pub fn span(&self, ip: usize) -> Span {
+        self.spans[ip]
+    }
+
     pub fn read_byte_mut(&mut self, ip: usize) -> &mut Code {
         &mut self.code[ip]
     }
@@ -135,8 +216,26 @@ impl Chunk {
 #[derive(PartialEq, Eq, Clone, Debug, Default)]
 pub struct CompiledFunction {
     pub name: String,
+
+    // the number of parameters, a call binds its arguments to each of them
     pub arity: usize,
     pub chunk: Chunk,
+
+    // debug info of the local variables, including the parameters
+    pub locals: Vec<LocalVariable>,
+}
+
+/// Debug info of a local variable of a compiled function, used by debuggers to find
+/// the value of the variable on the stack.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct LocalVariable {
+    pub name: String,
+
+    // the `index_in_stack` used to read and write the variable
+    pub index_in_stack: usize,
+
+    // the offsets of the instructions during which the variable holds a value
+    pub live: std::ops::Range<usize>,
 }
 
 impl DebugWithDb<dyn crate::Db> for CompiledFunction {
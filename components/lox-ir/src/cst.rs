@@ -0,0 +1,354 @@
+//! The concrete syntax tree, built by the parser from the `TokenTree`s of the lexer.
+//!
+//! Unlike `syntax`, the tree is lossless: it keeps every token, including whitespace, comments,
+//! delimiters and the tokens of statements that failed to parse, so the tokens of a tree cover
+//! its source text without gaps. Like rowan, the tree has two layers: `GreenNode`s are
+//! immutable and only know their length, `SyntaxNode`s are created while walking the tree and
+//! know their offset and their parent. `ast` gives a typed view of the nodes.
+
+use std::sync::Arc;
+
+use salsa::DebugWithDb;
+
+use crate::{
+    span::{Offset, Span},
+    token::Token,
+    token_tree::TokenTree,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    // the contents of a `TokenTree`, like a file, the inside of delimiters or a string with
+    // interpolations
+    TokenTree,
+
+    // `var foo = 1;`
+    VarDecl,
+
+    // `fun foo(a, b) { ... }`
+    FunDecl,
+
+    // a parameter of a `FunDecl` or a `Lambda`: `a`, `b = 2` or `...rest`
+    Param,
+
+    // `1 + 2;`
+    ExprStmt,
+
+    // `print 1;`
+    PrintStmt,
+
+    // `{ ... }`
+    Block,
+
+    // `if (...) ... else ...`
+    IfStmt,
+
+    // `while (...) ...`
+    WhileStmt,
+
+    // `for (...; ...; ...) ...`
+    ForStmt,
+
+    // `return 1;`
+    ReturnStmt,
+
+    // a statement that failed to parse, along with the tokens skipped to recover
+    ErrorStmt,
+
+    // `1`, `"foo"`, `true`, `false` or `nil`
+    Literal,
+
+    // `foo`
+    NameRef,
+
+    // `"total: ${a + b}"`
+    InterpolatedString,
+
+    // `(1 + 2)`
+    ParenExpr,
+
+    // `-foo`
+    UnaryExpr,
+
+    // `1 + 2`, `a and b`
+    BinaryExpr,
+
+    // `foo = 1`, `foo.bar = 1`
+    AssignExpr,
+
+    // `foo(1, 2)`
+    CallExpr,
+
+    // `name: 1`, an argument of a call passed by name
+    NamedArg,
+
+    // `foo.bar`
+    FieldExpr,
+
+    // `fun (x) { return x * 2; }`, `(x) => x * 2`
+    Lambda,
+
+    // an expression that failed to parse
+    ErrorExpr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+
+    // the tree that the node was built from, for `SyntaxKind::TokenTree` nodes
+    token_tree: Option<TokenTree>,
+
+    // the sum of the lengths of the children
+    len: u32,
+
+    children: Vec<GreenElement>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(GreenToken),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GreenToken {
+    pub token: Token,
+    pub len: u32,
+}
+
+impl GreenElement {
+    fn len(&self) -> u32 {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.len,
+        }
+    }
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let len = children.iter().map(GreenElement::len).sum();
+        Self {
+            kind,
+            token_tree: None,
+            len,
+            children,
+        }
+    }
+
+    /// Creates the node of kind `SyntaxKind::TokenTree` for the contents of `token_tree`.
+    pub fn token_tree(token_tree: TokenTree, children: Vec<GreenElement>) -> Self {
+        Self {
+            token_tree: Some(token_tree),
+            ..Self::new(SyntaxKind::TokenTree, children)
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// A node of the tree along with its position, see the module documentation.
+///
+/// Nodes other than `SyntaxKind::TokenTree` start and end with a token that is not trivia,
+/// so their span is the span of the code they stand for.
+#[derive(Clone)]
+pub struct SyntaxNode(Arc<NodeData>);
+
+struct NodeData {
+    green: Arc<GreenNode>,
+    offset: Offset,
+    parent: Option<SyntaxNode>,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+/// A token of the tree along with its span.
+#[derive(Clone, Debug)]
+pub struct SyntaxToken {
+    pub token: Token,
+    pub span: Span,
+    pub parent: SyntaxNode,
+}
+
+impl SyntaxToken {
+    /// True for whitespace and comments, which the parser skips.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.token, Token::Whitespace(_) | Token::Comment(_))
+    }
+}
+
+impl SyntaxNode {
+    /// Creates the root of a tree, `offset` is where the text of `green` starts in its file.
+    pub fn new_root(green: Arc<GreenNode>, offset: Offset) -> Self {
+        Self(Arc::new(NodeData {
+            green,
+            offset,
+            parent: None,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    /// The tree that a `SyntaxKind::TokenTree` node was built from.
+    pub fn token_tree(&self) -> Option<TokenTree> {
+        self.0.green.token_tree
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    pub fn span(&self) -> Span {
+        Span::from(self.0.offset, self.0.offset + self.0.green.len)
+    }
+
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+        self.0.green.children.iter().map(move |child| {
+            let start = offset;
+            offset = offset + child.len();
+            match child {
+                GreenElement::Node(green) => SyntaxElement::Node(Self(Arc::new(NodeData {
+                    green: green.clone(),
+                    offset: start,
+                    parent: Some(self.clone()),
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    token: green.token,
+                    span: Span::from(start, offset),
+                    parent: self.clone(),
+                }),
+            }
+        })
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// The tokens that are direct children of the node, trivia left out.
+    pub fn tokens(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Token(token) if !token.is_trivia() => Some(token),
+            _ => None,
+        })
+    }
+
+    /// The node and all the nodes below it, in the order of the source text.
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut descendants = vec![self.clone()];
+        for child in self.children() {
+            descendants.extend(child.descendants());
+        }
+        descendants
+    }
+
+    /// All the tokens below the node, trivia included, in the order of the source text.
+    pub fn descendant_tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = vec![];
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.descendant_tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// The source text of the node, `source_text` is the text of its file.
+    pub fn text(&self, source_text: &str) -> String {
+        let span = self.span();
+        source_text
+            .chars()
+            .skip(usize::from(span.start))
+            .take(span.len() as usize)
+            .collect()
+    }
+
+    fn ancestors_count(&self) -> usize {
+        let mut count = 0;
+        let mut node = self;
+        while let Some(parent) = node.parent() {
+            count += 1;
+            node = parent;
+        }
+        count
+    }
+}
+
+// nodes are equal when they are the same part of the same tree, so that salsa can tell when a
+// reparsed tree did not change
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.offset == other.0.offset && self.0.green == other.0.green
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+impl std::fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let span = self.span();
+        write!(
+            f,
+            "{:?}@{}..{}",
+            self.kind(),
+            u32::from(span.start),
+            u32::from(span.end)
+        )
+    }
+}
+
+// prints the tree with one node or token per line, indented by depth
+impl<'db> DebugWithDb<dyn crate::Db + 'db> for SyntaxNode {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        db: &dyn crate::Db,
+        include_all_fields: bool,
+    ) -> std::fmt::Result {
+        let depth = self.ancestors_count();
+        writeln!(f, "{:indent$}{:?}", "", self, indent = depth * 2)?;
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => {
+                    DebugWithDb::fmt(&node, f, db, include_all_fields)?;
+                }
+                SyntaxElement::Token(token) => writeln!(
+                    f,
+                    "{:indent$}{:?}@{}..{}",
+                    "",
+                    token.token.debug(db),
+                    u32::from(token.span.start),
+                    u32::from(token.span.end),
+                    indent = (depth + 1) * 2
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
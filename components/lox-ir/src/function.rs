@@ -1,12 +1,209 @@
 use salsa::DebugWithDb;
 
-use crate::{token_tree::TokenTree, word::Word};
+use crate::{
+    input_file::InputFile,
+    span::{FileSpan, Span},
+    token_tree::TokenTree,
+    word::Word,
+};
 
 #[salsa::tracked]
 pub struct Function {
     pub name: Word,
-    pub params: Vec<Word>,
-    pub body: TokenTree,
+    pub params: Vec<Param>,
+    pub body: FunctionBody,
+}
+
+/// The code that a function runs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FunctionBody {
+    /// The statements of a tree, like the block of `fun name() { ... }` or a whole file.
+    Block(TokenTree),
+
+    /// The expression at `Span` that an arrow function like `(x) => x * 2` returns.
+    Expr(InputFile, Span),
+}
+
+impl FunctionBody {
+    pub fn input_file(self, db: &dyn crate::Db) -> InputFile {
+        match self {
+            FunctionBody::Block(token_tree) => token_tree.input_file(db),
+            FunctionBody::Expr(input_file, _) => input_file,
+        }
+    }
+
+    /// The span of the block, with its braces, or of the expression.
+    pub fn span(self, db: &dyn crate::Db) -> FileSpan {
+        match self {
+            FunctionBody::Block(token_tree) => {
+                token_tree.span(db).anchor_to(token_tree.input_file(db))
+            }
+            FunctionBody::Expr(input_file, span) => span.anchor_to(input_file),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Param {
+    pub name: Word,
+    pub kind: ParamKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ParamKind {
+    /// `a`, every call passes an argument for it.
+    Required,
+
+    /// `b = 2`, the function evaluates the default value at `Span` when a call does not
+    /// pass an argument for it.
+    Optional(Span),
+
+    /// `...rest`, the last parameter, which gets the positional arguments that are left
+    /// once the other parameters have theirs.
+    Rest,
+}
+
+/// Where a parameter gets its value from in a call, see [`bind_arguments`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Binding {
+    /// The argument at this index in the call.
+    Argument(usize),
+
+    /// The default value of the parameter.
+    Default,
+
+    /// The list of the arguments at these indices in the call, for the rest parameter.
+    Rest(Vec<usize>),
+}
+
+/// Why the arguments of a call do not fit the parameters of a function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgumentError {
+    /// The function only has required parameters, and the call passes `found` arguments by
+    /// position instead of `expected`.
+    WrongCount { expected: usize, found: usize },
+
+    /// There are more positional arguments than the `max` parameters that can take them.
+    TooMany { max: usize, found: usize },
+
+    /// No argument is given for this required parameter.
+    Missing(Word),
+
+    /// No parameter has the name of this named argument.
+    UnknownName(Word),
+
+    /// This parameter is given an argument by position and by name.
+    Duplicate(Word),
+}
+
+/// Formats a number of arguments for an error message, e.g. `1 argument` or `2 arguments`.
+pub fn argument_count(count: usize) -> String {
+    if count == 1 {
+        "1 argument".to_string()
+    } else {
+        format!("{} arguments", count)
+    }
+}
+
+impl ArgumentError {
+    pub fn message(&self, db: &dyn crate::Db) -> String {
+        match self {
+            ArgumentError::WrongCount { expected, found } => {
+                format!("expected {} but got {}", argument_count(*expected), found)
+            }
+            ArgumentError::TooMany { max, found } => {
+                format!(
+                    "expected at most {} but got {}",
+                    argument_count(*max),
+                    found
+                )
+            }
+            ArgumentError::Missing(name) => {
+                format!("missing an argument for `{}`", name.as_str(db))
+            }
+            ArgumentError::UnknownName(name) => {
+                format!("there is no parameter named `{}`", name.as_str(db))
+            }
+            ArgumentError::Duplicate(name) => {
+                format!("`{}` is given more than one argument", name.as_str(db))
+            }
+        }
+    }
+
+    /// The label of the call to `function` that has the error.
+    pub fn label(&self, function: &str) -> String {
+        match self {
+            ArgumentError::WrongCount { .. }
+            | ArgumentError::TooMany { .. }
+            | ArgumentError::Missing(_) => {
+                format!(
+                    "`{}` is called with the wrong number of arguments",
+                    function
+                )
+            }
+            ArgumentError::UnknownName(_) | ArgumentError::Duplicate(_) => {
+                format!("`{}` is called with the wrong named arguments", function)
+            }
+        }
+    }
+}
+
+/// Matches the arguments of a call with `params`: the `positional` arguments come first,
+/// followed by the arguments named by `names`. Returns where each parameter gets its value.
+pub fn bind_arguments(
+    params: &[Param],
+    positional: usize,
+    names: &[Word],
+) -> Result<Vec<Binding>, ArgumentError> {
+    let only_required = params.iter().all(|param| param.kind == ParamKind::Required);
+    if only_required && names.is_empty() && positional != params.len() {
+        return Err(ArgumentError::WrongCount {
+            expected: params.len(),
+            found: positional,
+        });
+    }
+
+    let mut bindings: Vec<Option<Binding>> = vec![None; params.len()];
+    let mut rest = vec![];
+    let max = params
+        .iter()
+        .filter(|param| param.kind != ParamKind::Rest)
+        .count();
+    for index in 0..positional {
+        match params.get(index) {
+            Some(param) if param.kind != ParamKind::Rest => {
+                bindings[index] = Some(Binding::Argument(index))
+            }
+            _ if max < params.len() => rest.push(index),
+            _ => {
+                return Err(ArgumentError::TooMany {
+                    max,
+                    found: positional,
+                })
+            }
+        }
+    }
+    for (offset, &name) in names.iter().enumerate() {
+        // the rest parameter only takes positional arguments
+        let index = params
+            .iter()
+            .position(|param| param.name == name && param.kind != ParamKind::Rest)
+            .ok_or(ArgumentError::UnknownName(name))?;
+        if bindings[index].is_some() {
+            return Err(ArgumentError::Duplicate(name));
+        }
+        bindings[index] = Some(Binding::Argument(positional + offset));
+    }
+    params
+        .iter()
+        .zip(bindings)
+        .map(|(param, binding)| match (binding, param.kind) {
+            (Some(binding), _) => Ok(binding),
+            (None, ParamKind::Optional(_)) => Ok(Binding::Default),
+            (None, ParamKind::Rest) => Ok(Binding::Rest(std::mem::take(&mut rest))),
+            (None, ParamKind::Required) => Err(ArgumentError::Missing(param.name)),
+        })
+        .collect()
 }
 
 impl<'db> DebugWithDb<dyn crate::Db + 'db> for Function {
@@ -22,7 +219,11 @@ impl<'db> DebugWithDb<dyn crate::Db + 'db> for Function {
             self.name(db).as_str(db),
             self.params(db)
                 .iter()
-                .map(|p| p.as_str(db))
+                .map(|param| match param.kind {
+                    ParamKind::Required => param.name.as_str(db).to_string(),
+                    ParamKind::Optional(_) => format!("{} = ..", param.name.as_str(db)),
+                    ParamKind::Rest => format!("...{}", param.name.as_str(db)),
+                })
                 .collect::<Vec<_>>()
                 .join(", ")
         )
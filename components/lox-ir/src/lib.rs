@@ -1,8 +1,11 @@
+pub mod ast;
 pub mod bytecode;
+pub mod cst;
 pub mod diagnostic;
 pub mod function;
 pub mod input_file;
 pub mod kw;
+pub mod line_index;
 pub mod span;
 pub mod syntax;
 pub mod token;
@@ -18,6 +21,7 @@ pub struct Jar(
     kw::Keywords,
     kw::keywords_map,
     function::Function,
+    line_index::line_index,
 );
 
 pub trait Db: salsa::DbWithJar<Jar> {}
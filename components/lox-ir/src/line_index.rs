@@ -0,0 +1,168 @@
+use crate::{input_file::InputFile, span::Offset};
+
+/// The unit in which [`LineIndex`] counts columns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColumnUnit {
+    /// Characters, like offsets and most editors.
+    Char,
+
+    /// Bytes of UTF-8.
+    Utf8,
+
+    /// Code units of UTF-16, like the language server protocol and VS Code.
+    Utf16,
+}
+
+// A character that is longer than one byte in UTF-8, so that it is also longer than one unit in
+// some column unit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct WideChar {
+    offset: Offset,
+    utf8_len: u8,
+    utf16_len: u8,
+}
+
+impl WideChar {
+    // the number of units the character takes in addition to the one it counts as a character
+    fn extra_len(&self, unit: ColumnUnit) -> usize {
+        match unit {
+            ColumnUnit::Char => 0,
+            ColumnUnit::Utf8 => self.utf8_len as usize - 1,
+            ColumnUnit::Utf16 => self.utf16_len as usize - 1,
+        }
+    }
+}
+
+/// The offsets at which the lines of a file start, used to turn offsets into line numbers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineIndex {
+    // the offset of the first character of each line, the first line starts at 0
+    line_starts: Vec<Offset>,
+
+    // the characters that are not ASCII, in order, to convert columns between units
+    wide_chars: Vec<WideChar>,
+}
+
+impl LineIndex {
+    fn new(source_text: &str) -> Self {
+        // offsets count characters, the same way the lexer does
+        let mut line_starts = vec![Offset::from(0u32)];
+        let mut wide_chars = vec![];
+        for (index, ch) in source_text.chars().enumerate() {
+            if ch == '\n' {
+                line_starts.push(Offset::from(index + 1));
+            } else if !ch.is_ascii() {
+                wide_chars.push(WideChar {
+                    offset: Offset::from(index),
+                    utf8_len: ch.len_utf8() as u8,
+                    utf16_len: ch.len_utf16() as u8,
+                });
+            }
+        }
+        Self {
+            line_starts,
+            wide_chars,
+        }
+    }
+
+    /// Returns the 0-based line that contains `offset`.
+    pub fn line(&self, offset: Offset) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        }
+    }
+
+    /// Returns the 0-based line and column of `offset`, the column counts characters.
+    pub fn line_column(&self, offset: Offset) -> (usize, usize) {
+        let line = self.line(offset);
+        let column = usize::from(offset) - usize::from(self.line_starts[line]);
+        (line, column)
+    }
+
+    /// Returns the 0-based line and column of `offset`, the column counts `unit`s.
+    pub fn line_column_in(&self, offset: Offset, unit: ColumnUnit) -> (usize, usize) {
+        let (line, column) = self.line_column(offset);
+        let extra: usize = self
+            .wide_chars_between(self.line_starts[line], offset)
+            .iter()
+            .map(|wide_char| wide_char.extra_len(unit))
+            .sum();
+        (line, column + extra)
+    }
+
+    /// Returns the offset at the 0-based `line` and `column`, which counts `unit`s, if the file
+    /// has that many lines. A column in the middle of a character is rounded to the character.
+    pub fn offset_in(&self, line: usize, column: usize, unit: ColumnUnit) -> Option<Offset> {
+        let line_start = self.line_start(line)?;
+        let line_end = self.line_start(line + 1).unwrap_or(Offset::from(u32::MAX));
+        let mut char_column = column;
+        for wide_char in self.wide_chars_between(line_start, line_end) {
+            let wide_char_column = usize::from(wide_char.offset) - usize::from(line_start);
+            if wide_char_column >= char_column {
+                break;
+            }
+            char_column = char_column.saturating_sub(wide_char.extra_len(unit));
+        }
+        Some(Offset::from(usize::from(line_start) + char_column))
+    }
+
+    fn wide_chars_between(&self, start: Offset, end: Offset) -> &[WideChar] {
+        let start = self
+            .wide_chars
+            .partition_point(|wide_char| wide_char.offset < start);
+        let end = self
+            .wide_chars
+            .partition_point(|wide_char| wide_char.offset < end);
+        &self.wide_chars[start..end]
+    }
+
+    /// Returns the offset at which the 0-based `line` starts, if the file has that many lines.
+    pub fn line_start(&self, line: usize) -> Option<Offset> {
+        self.line_starts.get(line).copied()
+    }
+
+    /// Returns the number of lines in the file.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+#[salsa::tracked(return_ref)]
+pub fn line_index(db: &dyn crate::Db, input_file: InputFile) -> LineIndex {
+    LineIndex::new(input_file.source_text(db))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnUnit, LineIndex};
+    use crate::span::Offset;
+
+    #[test]
+    fn columns_in_units() {
+        // `é` is 2 bytes in UTF-8 and `😀` is 4 bytes in UTF-8 and 2 code units in UTF-16
+        let line_index = LineIndex::new("a\né😀 = 1;\nb");
+        let offset = |offset: usize| Offset::from(offset);
+
+        // the `=` of the second line
+        assert_eq!(line_index.line_column(offset(5)), (1, 3));
+        assert_eq!(
+            line_index.line_column_in(offset(5), ColumnUnit::Utf8),
+            (1, 7)
+        );
+        assert_eq!(
+            line_index.line_column_in(offset(5), ColumnUnit::Utf16),
+            (1, 4)
+        );
+        assert_eq!(
+            line_index.line_column_in(offset(10), ColumnUnit::Utf16),
+            (2, 0)
+        );
+
+        for unit in [ColumnUnit::Char, ColumnUnit::Utf8, ColumnUnit::Utf16] {
+            let (line, column) = line_index.line_column_in(offset(5), unit);
+            assert_eq!(line_index.offset_in(line, column, unit), Some(offset(5)));
+        }
+        assert_eq!(line_index.offset_in(3, 0, ColumnUnit::Char), None);
+    }
+}
@@ -1,10 +1,35 @@
-use crate::word::Word;
+use crate::{span::Span, word::Word};
 
 mod op;
 pub use op::Op;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+
+    // where the expression is in the source text, used to map bytecode back to the source
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for Expr {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        db: &dyn crate::Db,
+        include_all_fields: bool,
+    ) -> std::fmt::Result {
+        salsa::DebugWithDb::fmt(&self.kind, f, db, include_all_fields)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprKind {
     // `22`
     NumberLiteral(Word),
 
@@ -41,14 +66,39 @@ pub enum Expr {
     // logical or
     LogicalOr(Box<Expr>, Box<Expr>),
 
-    // call expression, like `foo(1, 2, 3)`
+    // call expression, like `foo(1, 2, 3)` or `foo(1, c: 3)`, the last `names.len()`
+    // arguments are passed by name
     Call {
         callee: Box<Expr>,
         arguments: Vec<Expr>,
+        names: Vec<Word>,
+    },
+
+    // string interpolation, like `"total: ${a + b}"`, the parts are the pieces of text, as
+    // `StringLiteral`s, and the interpolated expressions
+    Interpolation(Vec<Expr>),
+
+    // property access, like `foo.bar`
+    Get {
+        object: Box<Expr>,
+        name: Word,
+    },
+
+    // property assignment, like `foo.bar = 1`
+    Set {
+        object: Box<Expr>,
+        name: Word,
+        value: Box<Expr>,
     },
+
+    // an anonymous function, like `fun (x) { return x * 2; }` or `(x) => x * 2`
+    Lambda(crate::function::Function),
+
+    // an expression that failed to parse, the error is already reported
+    Error,
 }
 
-impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for Expr {
+impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for ExprKind {
     fn fmt(
         &self,
         f: &mut std::fmt::Formatter<'_>,
@@ -56,55 +106,115 @@ impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for Expr {
         _include_all_fields: bool,
     ) -> std::fmt::Result {
         match self {
-            Expr::NumberLiteral(word) => write!(f, "NumberLiteral({})", word.as_str(db)),
-            Expr::UnaryOp(op, expr) => f
+            ExprKind::NumberLiteral(word) => write!(f, "NumberLiteral({})", word.as_str(db)),
+            ExprKind::UnaryOp(op, expr) => f
                 .debug_struct("UnaryOp")
                 .field("op", op)
                 .field("expr", &expr.debug(db))
                 .finish(),
-            Expr::BinaryOp(left, op, right) => f
+            ExprKind::BinaryOp(left, op, right) => f
                 .debug_struct("BinaryOp")
                 .field("left", &left.debug(db))
                 .field("op", op)
                 .field("right", &right.debug(db))
                 .finish(),
-            Expr::Parenthesized(expr) => f
+            ExprKind::Parenthesized(expr) => f
                 .debug_struct("Parenthesized")
                 .field("expr", &expr.debug(db))
                 .finish(),
-            Expr::BooleanLiteral(value) => write!(f, "BooleanLiteral({})", value),
-            Expr::StringLiteral(word) => write!(f, "StringLiteral({})", word.as_str(db)),
-            Expr::Variable(word) => write!(f, "Variable({})", word.as_str(db)),
-            Expr::Assign { name, value } => f
+            ExprKind::BooleanLiteral(value) => write!(f, "BooleanLiteral({})", value),
+            ExprKind::NilLiteral => write!(f, "NilLiteral"),
+            ExprKind::StringLiteral(word) => write!(f, "StringLiteral({})", word.as_str(db)),
+            ExprKind::Variable(word) => write!(f, "Variable({})", word.as_str(db)),
+            ExprKind::Assign { name, value } => f
                 .debug_struct("Assign")
                 .field("name", &name.as_str(db))
                 .field("value", &value.debug(db))
                 .finish(),
-            Expr::LogicalAnd(left, right) => f
+            ExprKind::LogicalAnd(left, right) => f
                 .debug_struct("LogicalAnd")
                 .field("left", &left.debug(db))
                 .field("right", &right.debug(db))
                 .finish(),
-            Expr::LogicalOr(left, right) => f
+            ExprKind::LogicalOr(left, right) => f
                 .debug_struct("LogicalOr")
                 .field("left", &left.debug(db))
                 .field("right", &right.debug(db))
                 .finish(),
-            Expr::Call { callee, arguments } => {
+            ExprKind::Call {
+                callee,
+                arguments,
+                names,
+            } => {
                 let mut builder = f.debug_struct("Call");
                 builder.field("callee", &callee.debug(db));
-                for arg in arguments {
+                let positional = arguments.len() - names.len();
+                for arg in &arguments[..positional] {
                     builder.field("arg", &arg.debug(db));
                 }
+                for (name, arg) in names.iter().zip(&arguments[positional..]) {
+                    builder.field(name.as_str(db), &arg.debug(db));
+                }
+                builder.finish()
+            }
+            ExprKind::Interpolation(parts) => {
+                let mut builder = f.debug_struct("Interpolation");
+                for part in parts {
+                    builder.field("part", &part.debug(db));
+                }
                 builder.finish()
             }
-            _ => todo!(),
+            ExprKind::Get { object, name } => f
+                .debug_struct("Get")
+                .field("object", &object.debug(db))
+                .field("name", &name.as_str(db))
+                .finish(),
+            ExprKind::Set {
+                object,
+                name,
+                value,
+            } => f
+                .debug_struct("Set")
+                .field("object", &object.debug(db))
+                .field("name", &name.as_str(db))
+                .field("value", &value.debug(db))
+                .finish(),
+            ExprKind::Lambda(function) => f
+                .debug_struct("Lambda")
+                .field("function", &function.debug(db))
+                .finish(),
+            ExprKind::Error => write!(f, "Error"),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Stmt {
+pub struct Stmt {
+    pub kind: StmtKind,
+
+    // where the statement is in the source text, used to map bytecode back to the source
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for Stmt {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        db: &dyn crate::Db,
+        include_all_fields: bool,
+    ) -> std::fmt::Result {
+        salsa::DebugWithDb::fmt(&self.kind, f, db, include_all_fields)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StmtKind {
     // expression statement, like `1 + 2;`
     Expr(Expr),
 
@@ -146,9 +256,12 @@ pub enum Stmt {
 
     // return statement, like `return 1 + 2;`
     Return(Option<Expr>),
+
+    // a statement that failed to parse, the error is already reported
+    Error,
 }
 
-impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for Stmt {
+impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for StmtKind {
     fn fmt(
         &self,
         f: &mut std::fmt::Formatter<'_>,
@@ -156,27 +269,27 @@ impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for Stmt {
         _include_all_fields: bool,
     ) -> std::fmt::Result {
         match self {
-            Stmt::Expr(expr) => f
+            StmtKind::Expr(expr) => f
                 .debug_struct("Expr")
                 .field("expr", &expr.debug(db))
                 .finish(),
-            Stmt::Print(expr) => f
+            StmtKind::Print(expr) => f
                 .debug_struct("Print")
                 .field("expr", &expr.debug(db))
                 .finish(),
-            Stmt::VariableDeclaration { name, initializer } => f
+            StmtKind::VariableDeclaration { name, initializer } => f
                 .debug_struct("Var")
                 .field("name", &name.as_str(db))
                 .field("initializer", &initializer.debug(db))
                 .finish(),
-            Stmt::Block(stmts) => {
+            StmtKind::Block(stmts) => {
                 let mut builder = f.debug_struct("Block");
                 for stmt in stmts {
                     builder.field("stmt", &stmt.debug(db));
                 }
                 builder.finish()
             }
-            Stmt::If {
+            StmtKind::If {
                 condition,
                 then_branch,
                 else_branch,
@@ -189,13 +302,13 @@ impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for Stmt {
                 }
                 builder.finish()
             }
-            Stmt::While { condition, body } => {
+            StmtKind::While { condition, body } => {
                 let mut builder = f.debug_struct("While");
                 builder.field("condition", &condition.debug(db));
                 builder.field("body", &body.debug(db));
                 builder.finish()
             }
-            Stmt::For {
+            StmtKind::For {
                 initializer,
                 condition,
                 increment,
@@ -214,18 +327,19 @@ impl<'db> salsa::DebugWithDb<dyn crate::Db + 'db> for Stmt {
                 builder.field("body", &body.debug(db));
                 builder.finish()
             }
-            Stmt::Return(expr) => {
+            StmtKind::Return(expr) => {
                 let mut builder = f.debug_struct("Return");
                 if let Some(expr) = expr {
                     builder.field("expr", &expr.debug(db));
                 }
                 builder.finish()
             }
-            Stmt::FunctionDeclaration(function) => {
+            StmtKind::FunctionDeclaration(function) => {
                 let mut builder = f.debug_struct("FunctionDeclaration");
                 builder.field("function", &function.debug(db));
                 builder.finish()
             }
+            StmtKind::Error => write!(f, "Error"),
         }
     }
 }
@@ -41,6 +41,7 @@ define_operators! {
     NotEqual => "!=",
     GreaterEqual => ">=",
     LessEqual => "<=",
+    Arrow => "=>",
 
     // 1-character ops
     Plus => "+",
@@ -4,10 +4,10 @@ use crate::{token_tree::TokenTree, word::Word};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Token {
-    // "foo", could be keyword or identifier
+    // "foo" or "größe", could be keyword or identifier, as written in the source text
     Alphabetic(Word),
 
-    // "22_000"
+    // "22_000", "3.14", "1e-9" or "0xff"
     Number(Word),
 
     // A single character from an operator, like "+"
@@ -19,8 +19,8 @@ pub enum Token {
     // Some whitespace (` `, `\n`, etc)
     Whitespace(char),
 
-    /// `# ...`, argument is the length (including `#`).
-    /// Note that the newline that comes after a comment is
+    /// `# ...`, `// ...` or `/* ... */`, argument is the length (including `#`, `//` or
+    /// `/*` and `*/`). Note that the newline that comes after a line comment is
     /// considered a separate whitespace token.
     Comment(u32),
 
@@ -28,8 +28,15 @@ pub enum Token {
     // the closing delimiter are read into a Tree.
     Tree(TokenTree),
 
-    // String
-    String(Word),
+    // A string literal: its value, with the escape sequences replaced, and the length of the
+    // literal in the source text, quotes and raw string prefix included
+    String(Word, u32),
+
+    // A string literal with interpolations, like `"total: ${a + b}!"`. The tree has a
+    // `Token::String` for each piece of text, with the quotes and the `${` and `}` around the
+    // interpolations in their length, and a `Token::Tree` for each interpolated expression:
+    // `String("total: ")`, `Tree(a + b)` and `String("!")`.
+    Interpolation(TokenTree),
 
     // Semicolon
     Semicolon,
@@ -37,6 +44,15 @@ pub enum Token {
     // Comma
     Comma,
 
+    // Dot, as in `object.property`
+    Dot,
+
+    // `:`, as in the named argument `f(name: value)`
+    Colon,
+
+    // `...`, as in the rest parameter `fun f(...rest)`
+    Ellipsis,
+
     // Unkown token
     Unknown(char),
 }
@@ -56,10 +72,17 @@ impl<'db> DebugWithDb<dyn crate::Db + 'db> for Token {
             Token::Whitespace(ch) => write!(f, "Whitespace({:?})", ch),
             Token::Comment(len) => write!(f, "Comment({})", len),
             Token::Tree(tree) => f.debug_tuple("Tree").field(&tree.debug(db)).finish(),
-            Token::String(word) => write!(f, "String({})", word.as_str(db)),
+            Token::Interpolation(tree) => f
+                .debug_tuple("Interpolation")
+                .field(&tree.debug(db))
+                .finish(),
+            Token::String(word, _) => write!(f, "String({})", word.as_str(db)),
             Token::Unknown(ch) => write!(f, "Unknown({})", ch),
             Token::Semicolon => write!(f, "Semicolon"),
             Token::Comma => write!(f, "Comma"),
+            Token::Dot => write!(f, "Dot"),
+            Token::Colon => write!(f, "Colon"),
+            Token::Ellipsis => write!(f, "Ellipsis"),
         }
     }
 }
@@ -67,15 +90,17 @@ impl<'db> DebugWithDb<dyn crate::Db + 'db> for Token {
 impl Token {
     pub fn span_len(&self, db: &dyn crate::Db) -> u32 {
         match self {
-            Token::Alphabetic(word) | Token::Number(word) => word.as_str(db).len() as u32,
-            Token::Op(ch) | Token::Delimiter(ch) | Token::Whitespace(ch) | Token::Unknown(ch) => {
-                ch.len_utf8() as u32
-            }
+            // offsets count characters, not bytes
+            Token::Alphabetic(word) | Token::Number(word) => word.as_str(db).chars().count() as u32,
+            Token::Op(_) | Token::Delimiter(_) | Token::Whitespace(_) | Token::Unknown(_) => 1,
             Token::Comment(s) => *s,
-            Token::Tree(tree) => tree.span(db).len(),
-            Token::String(s) => s.as_str(db).len() as u32 + 2, // plus 2 for the quotes
+            Token::Tree(tree) | Token::Interpolation(tree) => tree.span(db).len(),
+            Token::String(_, len) => *len,
             Token::Semicolon => 1,
             Token::Comma => 1,
+            Token::Dot => 1,
+            Token::Colon => 1,
+            Token::Ellipsis => 3,
         }
     }
 
@@ -1,6 +1,9 @@
 use std::iter::Peekable;
 
-use lox_ir::{input_file::InputFile, span::Span, token::Token, token_tree::TokenTree, word::Word};
+use lox_ir::{
+    diagnostic::DiagnosticBuilder, input_file::InputFile, span::Span, token::Token,
+    token_tree::TokenTree, word::Word,
+};
 
 use crate::Db;
 
@@ -13,7 +16,7 @@ pub fn lex_file(db: &dyn Db, input_file: InputFile) -> TokenTree {
         input_file,
         chars,
     };
-    lexer.lex_tokens(None)
+    lexer.lex_tokens(&[])
 }
 
 pub fn closing_delimiter(ch: char) -> char {
@@ -27,7 +30,7 @@ pub fn closing_delimiter(ch: char) -> char {
 
 struct Lexer<'me, I>
 where
-    I: Iterator<Item = (usize, char)>,
+    I: Iterator<Item = (usize, char)> + Clone,
 {
     db: &'me dyn Db,
     input_file: InputFile,
@@ -36,9 +39,10 @@ where
 
 impl<'me, I> Lexer<'me, I>
 where
-    I: Iterator<Item = (usize, char)>,
+    I: Iterator<Item = (usize, char)> + Clone,
 {
-    fn lex_tokens(&mut self, end_ch: Option<char>) -> TokenTree {
+    // Lexes up to one of the `closing` delimiters, the innermost last, or the end of input.
+    fn lex_tokens(&mut self, closing: &[char]) -> TokenTree {
         let mut tokens = vec![];
         let mut push_token = |t: Token| {
             tracing::debug!("push token: {:?}", t);
@@ -51,7 +55,8 @@ where
             end_pos = end_pos.max(pos);
             start_pos = start_pos.min(pos);
 
-            if Some(ch) == end_ch {
+            // a closing delimiter of an outer tree also ends this one, which is then unclosed
+            if closing.contains(&ch) {
                 break;
             }
 
@@ -60,32 +65,55 @@ where
                 '(' | '[' | '{' => {
                     push_token(Token::Delimiter(ch));
                     let closing_ch = closing_delimiter(ch);
-                    let tree = self.lex_tokens(Some(closing_ch));
+                    let tree = self.lex_tokens(&[closing, &[closing_ch]].concat());
                     push_token(Token::Tree(tree));
 
-                    if let Some((_, next_ch)) = self.chars.peek() {
-                        if *next_ch == closing_ch {
-                            self.chars.next();
-                            push_token(Token::Delimiter(closing_ch));
-                        }
+                    // the tree only ends before a closing delimiter or at the end of input
+                    if self.chars.next_if(|&(_, c)| c == closing_ch).is_some() {
+                        push_token(Token::Delimiter(closing_ch));
+                    } else {
+                        self.unclosed_delimiter(pos, ch);
                     }
                 }
-                'a'..='z' | 'A'..='Z' | '_' => {
-                    let text = self
-                        .accumulate(ch, |c| matches!(c, 'a'..='z' | 'A'..='Z' | '_' | '0'..='9'));
+                ')' | ']' | '}' => {
+                    self.error(pos, pos + 1, format!("unexpected `{}`", ch))
+                        .primary_label("this delimiter has no matching opening delimiter")
+                        .emit(self.db);
+                    push_token(Token::Unknown(ch));
+                }
+                'r' if self.is_raw_string() => {
+                    let (word, len) = self.raw_string_literal(pos);
+                    push_token(Token::String(word, len));
+                }
+                _ if ch == '_' || unicode_ident::is_xid_start(ch) => {
+                    // identifiers are normalized by the parser, so that the token keeps the
+                    // length of the source text
+                    let text = self.accumulate(ch, unicode_ident::is_xid_continue);
 
                     push_token(Token::Alphabetic(text));
                 }
                 '0'..='9' => {
-                    let text = self.accumulate(ch, |c| c.is_ascii_digit());
+                    let text = self.number_literal(ch);
                     push_token(Token::Number(text));
                 }
+                '#' => {
+                    let len = self.line_comment(pos);
+                    push_token(Token::Comment(len));
+                }
+                '/' if self.peek_nth(0) == Some('/') => {
+                    let len = self.line_comment(pos);
+                    push_token(Token::Comment(len));
+                }
+                '/' if self.peek_nth(0) == Some('*') => {
+                    let len = self.block_comment(pos);
+                    push_token(Token::Comment(len));
+                }
                 '+' | '-' | '*' | '/' | '!' | '<' | '>' | '=' => {
                     push_token(Token::Op(ch));
                 }
                 '"' => {
-                    let word = self.string_literal();
-                    push_token(Token::String(word));
+                    let token = self.string_literal(pos);
+                    push_token(token);
                 }
                 ' ' => {
                     push_token(Token::Whitespace(ch));
@@ -96,10 +124,23 @@ where
                 ',' => {
                     push_token(Token::Comma);
                 }
+                '.' if self.peek_nth(0) == Some('.') && self.peek_nth(1) == Some('.') => {
+                    self.chars.next();
+                    self.chars.next();
+                    push_token(Token::Ellipsis);
+                }
+                '.' => {
+                    push_token(Token::Dot);
+                }
+                ':' => {
+                    push_token(Token::Colon);
+                }
                 _ => {
                     if ch.is_whitespace() {
                         push_token(Token::Whitespace(ch))
                     } else {
+                        self.error(pos, pos + 1, format!("unknown character `{}`", ch))
+                            .emit(self.db);
                         push_token(Token::Unknown(ch))
                     }
                 }
@@ -119,6 +160,12 @@ where
     fn accumulate_string(&mut self, ch0: char, matches: impl Fn(char) -> bool) -> String {
         let mut string = String::new();
         string.push(ch0);
+        self.accumulate_into(&mut string, matches);
+        string
+    }
+
+    /// Push the following characters onto `string` while `matches` returns true.
+    fn accumulate_into(&mut self, string: &mut String, matches: impl Fn(char) -> bool) {
         while let Some(&(_, ch1)) = self.chars.peek() {
             if !matches(ch1) {
                 break;
@@ -127,7 +174,56 @@ where
             string.push(ch1);
             self.chars.next();
         }
-        string
+    }
+
+    /// Returns the `n`th character that has not been consumed yet, without consuming it.
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.chars.clone().nth(n).map(|(_, ch)| ch)
+    }
+
+    /// Accumulates a number literal starting with the digit `ch0`: a decimal number with an
+    /// optional fraction and exponent, like `22_000`, `3.14` or `1e-9`, or an integer with a
+    /// `0x`, `0b` or `0o` prefix.
+    ///
+    /// The literal is only validated by the compiler, which reports precise errors: the
+    /// letters and digits that directly follow it, like in `0xfg` or `12px`, are part of it.
+    fn number_literal(&mut self, ch0: char) -> Word {
+        let is_word_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let is_digit = |c: char| c.is_ascii_digit() || c == '_';
+        let mut string = String::from(ch0);
+
+        let has_prefix =
+            ch0 == '0' && matches!(self.peek_nth(0), Some('x' | 'X' | 'b' | 'B' | 'o' | 'O'));
+        if !has_prefix {
+            self.accumulate_into(&mut string, is_digit);
+
+            // `1.` and `1.foo` are not fractions, the `.` is lexed as a `Token::Dot`
+            if self.peek_nth(0) == Some('.') && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit())
+            {
+                string.push('.');
+                self.chars.next();
+                self.accumulate_into(&mut string, is_digit);
+            }
+
+            if let Some(e @ ('e' | 'E')) = self.peek_nth(0) {
+                let sign = match (self.peek_nth(1), self.peek_nth(2)) {
+                    (Some(sign @ ('+' | '-')), Some(c)) if c.is_ascii_digit() => Some(sign),
+                    _ => None,
+                };
+                if sign.is_some() || self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+                    string.push(e);
+                    self.chars.next();
+                    if let Some(sign) = sign {
+                        string.push(sign);
+                        self.chars.next();
+                    }
+                    self.accumulate_into(&mut string, is_digit);
+                }
+            }
+        }
+
+        self.accumulate_into(&mut string, is_word_char);
+        Word::intern(self.db, string)
     }
 
     /// Like [`Self::accumulate_string`], but interns the result.
@@ -136,26 +232,243 @@ where
         Word::intern(self.db, string)
     }
 
-    // Invoke after consuming the opening quote `"`
-    fn string_literal(&mut self) -> Word {
-        // FIXME: handle escape sequences
+    // The offset of the next character, or the length of the source text at its end.
+    fn offset(&mut self) -> usize {
+        match self.chars.peek() {
+            Some(&(pos, _)) => pos,
+            None => self.input_file.source_text(self.db).chars().count(),
+        }
+    }
+
+    fn error(
+        &self,
+        start: usize,
+        end: usize,
+        message: impl std::fmt::Display,
+    ) -> DiagnosticBuilder {
+        lox_ir::error!(
+            Span::from(start, end).anchor_to(self.input_file),
+            "{}",
+            message
+        )
+    }
+
+    fn unterminated_string(&self, start: usize) {
+        self.error(start, start + 1, "unterminated string")
+            .primary_label("this string is never closed")
+            .emit(self.db);
+    }
+
+    // Reports the delimiter `ch` at `start`, whose tree ran to the end of input or to the closing
+    // delimiter of an outer tree.
+    fn unclosed_delimiter(&mut self, start: usize, ch: char) {
+        let (end, before) = match self.chars.peek() {
+            Some(&(pos, closing_ch)) => (pos + 1, format!("this `{}`", closing_ch)),
+            None => (self.offset(), "the end of the file".to_string()),
+        };
+        self.error(start, start + 1, format!("unclosed `{}`", ch))
+            .primary_label(format!("this `{}` is never closed", ch))
+            .secondary_label(
+                Span::from(end - 1, end).anchor_to(self.input_file),
+                format!("expected `{}` before {}", closing_delimiter(ch), before),
+            )
+            .emit(self.db);
+    }
+
+    // Invoke after consuming the first character of a `#` or `//` comment at `start`, returns
+    // the length of the comment. The newline after it is a separate whitespace token.
+    fn line_comment(&mut self, start: usize) -> u32 {
+        while self.chars.next_if(|&(_, c)| c != '\n').is_some() {}
+        (self.offset() - start) as u32
+    }
+
+    // Invoke after consuming the `/` of a `/* */` comment at `start`, returns the length of
+    // the comment. Block comments nest, so that code containing comments can be commented out.
+    fn block_comment(&mut self, start: usize) -> u32 {
+        // the `*` of the opening `/*`
+        self.chars.next();
+        let mut depth = 1;
+        while depth > 0 {
+            let Some((_, ch)) = self.chars.next() else {
+                self.error(start, start + 2, "unterminated block comment")
+                    .primary_label("this comment is never closed")
+                    .emit(self.db);
+                break;
+            };
+            match (ch, self.peek_nth(0)) {
+                ('/', Some('*')) => {
+                    self.chars.next();
+                    depth += 1;
+                }
+                ('*', Some('/')) => {
+                    self.chars.next();
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        (self.offset() - start) as u32
+    }
+
+    // Invoke after consuming the opening quote `"` at `start`. Returns a `Token::String`, or a
+    // `Token::Interpolation` if the string contains `${...}`.
+    fn string_literal(&mut self, start: usize) -> Token {
+        // the pieces of an interpolated string, see `Token::Interpolation`
+        let mut pieces = vec![];
+        let mut piece_start = start;
         let mut string = String::new();
-        while let Some(&(_, ch)) = self.chars.peek() {
-            if ch == '"' {
-                self.chars.next();
+        loop {
+            let Some((pos, ch)) = self.chars.next() else {
+                self.unterminated_string(start);
                 break;
+            };
+            match ch {
+                '"' => break,
+                '\\' => self.escape_sequence(pos, &mut string),
+                '$' if self.chars.next_if(|&(_, c)| c == '{').is_some() => {
+                    let len = self.offset() - piece_start;
+                    let word = Word::intern(self.db, std::mem::take(&mut string));
+                    pieces.push(Token::String(word, len as u32));
+
+                    // the delimiters around the string do not end the interpolation
+                    let tree = self.lex_tokens(&['}']);
+                    pieces.push(Token::Tree(tree));
+
+                    // the end of the file is reported as an unterminated string
+                    self.chars.next_if(|&(_, c)| c == '}');
+                    piece_start = tree.span(self.db).end.into();
+                }
+                _ => string.push(ch),
             }
+        }
+        let end = self.offset();
+        let word = Word::intern(self.db, string);
+        if pieces.is_empty() {
+            return Token::String(word, (end - start) as u32);
+        }
+        pieces.push(Token::String(word, (end - piece_start) as u32));
+        Token::Interpolation(TokenTree::new(
+            self.db,
+            self.input_file,
+            Span::from(start, end),
+            pieces,
+        ))
+    }
 
+    // Invoke after consuming the `\` at `start`, pushes the escaped character onto `string`.
+    fn escape_sequence(&mut self, start: usize, string: &mut String) {
+        // the end of the file is reported as an unterminated string
+        let Some((_, ch)) = self.chars.next() else {
+            return;
+        };
+        match ch {
+            'n' => string.push('\n'),
+            't' => string.push('\t'),
+            'r' => string.push('\r'),
+            '0' => string.push('\0'),
+            '"' | '\\' | '$' => string.push(ch),
+            'u' => self.unicode_escape(start, string),
+
+            // a `\` at the end of a line continues the string on the next line, without the
+            // line break and the indentation
+            '\n' | '\r' => while self.chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {},
+            _ => {
+                let end = self.offset();
+                self.error(start, end, format!("unknown escape sequence `\\{}`", ch))
+                    .child(
+                        lox_ir::help!(
+                            Span::from(start, start + 1).anchor_to(self.input_file),
+                            "use `\\\\` for a literal backslash, or a raw string like `r\"...\"`"
+                        )
+                        .finish(),
+                    )
+                    .emit(self.db);
+                string.push(ch);
+            }
+        }
+    }
+
+    // Invoke after consuming the `\u` at `start`, like `\u{1F600}`.
+    fn unicode_escape(&mut self, start: usize, string: &mut String) {
+        if self.chars.next_if(|&(_, c)| c == '{').is_none() {
+            let end = self.offset();
+            self.error(start, end, "expected `{` after `\\u`")
+                .emit(self.db);
+            return;
+        }
+        let mut digits = String::new();
+        self.accumulate_into(&mut digits, |c| c.is_ascii_hexdigit());
+        if self.chars.next_if(|&(_, c)| c == '}').is_none() {
+            let end = self.offset();
+            self.error(start, end, "expected `}` to close the unicode escape")
+                .emit(self.db);
+            return;
+        }
+
+        let end = self.offset();
+        if digits.is_empty() || digits.len() > 6 {
+            self.error(
+                start,
+                end,
+                "a unicode escape must have 1 to 6 hexadecimal digits",
+            )
+            .emit(self.db);
+            return;
+        }
+        match char::from_u32(u32::from_str_radix(&digits, 16).unwrap()) {
+            Some(ch) => string.push(ch),
+            None => {
+                self.error(
+                    start,
+                    end,
+                    format!("`\\u{{{}}}` is not a unicode character", digits),
+                )
+                .emit(self.db);
+            }
+        }
+    }
+
+    // Whether the `r` that was just consumed starts a raw string, like `r"\d+"`.
+    fn is_raw_string(&self) -> bool {
+        let mut chars = self.chars.clone().map(|(_, c)| c).skip_while(|&c| c == '#');
+        chars.next() == Some('"')
+    }
+
+    // Invoke after consuming the `r` at `start` of a raw string. Nothing is escaped in a raw
+    // string, and it ends at the first `"` followed by as many `#` as there are after the `r`,
+    // so that `r#"say "hi""#` can contain quotes.
+    fn raw_string_literal(&mut self, start: usize) -> (Word, u32) {
+        let mut hashes = 0;
+        while self.chars.next_if(|&(_, c)| c == '#').is_some() {
+            hashes += 1;
+        }
+        // the opening quote
+        self.chars.next();
+
+        let mut string = String::new();
+        loop {
+            let Some((_, ch)) = self.chars.next() else {
+                self.unterminated_string(start);
+                break;
+            };
+            if ch == '"' && (0..hashes).all(|n| self.peek_nth(n) == Some('#')) {
+                for _ in 0..hashes {
+                    self.chars.next();
+                }
+                break;
+            }
             string.push(ch);
-            self.chars.next();
         }
-        Word::intern(self.db, string)
+        let len = self.offset() - start;
+        (Word::intern(self.db, string), len as u32)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use lox_ir::diagnostic::Diagnostics;
     use lox_ir::input_file::InputFile;
+    use lox_ir::token::Token;
     use lox_ir::word::Word;
 
     use crate::Db;
@@ -182,4 +495,236 @@ mod tests {
         let token_tree = lex_file(&db, input_file);
         dbg!(token_tree.tokens(&db));
     }
+
+    #[test]
+    fn number_literals() {
+        let db = Database::default();
+        let source_text = "1_000 3.14 1e9 2.5E-3 0xff 0b10 0o7 1.foo 1e+x 12px";
+        let input_file = InputFile::new(&db, Word::intern(&db, "test"), source_text.to_string());
+        let tokens: Vec<Token> = lex_file(&db, input_file)
+            .tokens(&db)
+            .iter()
+            .copied()
+            .filter(|token| !matches!(token, Token::Whitespace(_)))
+            .collect();
+
+        let number = |text: &str| Token::Number(Word::intern(&db, text));
+        let alphabetic = |text: &str| Token::Alphabetic(Word::intern(&db, text));
+        assert_eq!(
+            tokens,
+            vec![
+                number("1_000"),
+                number("3.14"),
+                number("1e9"),
+                number("2.5E-3"),
+                number("0xff"),
+                number("0b10"),
+                number("0o7"),
+                // a `.` that is not followed by a digit is a property access
+                number("1"),
+                Token::Dot,
+                alphabetic("foo"),
+                // invalid literals are reported by the compiler
+                number("1e"),
+                Token::Op('+'),
+                alphabetic("x"),
+                number("12px"),
+            ]
+        );
+    }
+
+    #[test]
+    fn string_literals() {
+        let db = Database::default();
+        let source_text = r##"
+"a\tb\n" "say \"hi\"" "\u{1F600}" r"\d+" r#"a "quoted" word"# "one \
+    line"
+"##;
+        let input_file = InputFile::new(&db, Word::intern(&db, "test"), source_text.to_string());
+        let strings: Vec<(&str, u32)> = lex_file(&db, input_file)
+            .tokens(&db)
+            .iter()
+            .filter_map(|token| match token {
+                Token::String(word, len) => Some((word.as_str(&db), *len)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            strings,
+            vec![
+                ("a\tb\n", 8),
+                ("say \"hi\"", 12),
+                ("\u{1F600}", 11),
+                ("\\d+", 6),
+                ("a \"quoted\" word", 20),
+                ("one line", 16),
+            ]
+        );
+        assert!(lex_file::accumulated::<Diagnostics>(&db, input_file).is_empty());
+    }
+
+    #[test]
+    fn string_errors() {
+        let db = Database::default();
+        let source_text = r#""\q" "\u{110000}" "\u{}" "open"#;
+        let input_file = InputFile::new(&db, Word::intern(&db, "test"), source_text.to_string());
+        lex_file(&db, input_file);
+        let diagnostics: Vec<(String, u32, u32)> =
+            lex_file::accumulated::<Diagnostics>(&db, input_file)
+                .into_iter()
+                .map(|diagnostic| {
+                    let span = diagnostic.span;
+                    (diagnostic.message, span.start.into(), span.end.into())
+                })
+                .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                ("unknown escape sequence `\\q`".to_string(), 1, 3),
+                (
+                    "`\\u{110000}` is not a unicode character".to_string(),
+                    6,
+                    16
+                ),
+                (
+                    "a unicode escape must have 1 to 6 hexadecimal digits".to_string(),
+                    19,
+                    23
+                ),
+                ("unterminated string".to_string(), 25, 26),
+            ]
+        );
+    }
+
+    #[test]
+    fn interpolation() {
+        let db = Database::default();
+        let source_text = "\"a${x + 1}b${}\"";
+        let input_file = InputFile::new(&db, Word::intern(&db, "test"), source_text.to_string());
+        let tokens = lex_file(&db, input_file).tokens(&db);
+        let [Token::Interpolation(tree)] = tokens[..] else {
+            panic!("expected an interpolation, got {:?}", tokens);
+        };
+        assert_eq!(tree.span(&db).len() as usize, source_text.len());
+
+        // the lengths of the pieces add up to the length of the literal
+        let pieces: Vec<(String, u32)> = tree
+            .tokens(&db)
+            .iter()
+            .map(|token| match token {
+                Token::String(word, len) => (format!("{:?}", word.as_str(&db)), *len),
+                Token::Tree(tree) => ("tree".to_string(), tree.span(&db).len()),
+                _ => panic!("unexpected token {:?}", token),
+            })
+            .collect();
+        assert_eq!(
+            pieces,
+            vec![
+                ("\"a\"".to_string(), 4),
+                ("tree".to_string(), 5),
+                ("\"b\"".to_string(), 4),
+                ("tree".to_string(), 0),
+                ("\"\"".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let db = Database::default();
+        let source_text = "# hash\n1 // slashes\n/* a /* nested */ comment */ 2 / 3";
+        let input_file = InputFile::new(&db, Word::intern(&db, "test"), source_text.to_string());
+        let tokens: Vec<Token> = lex_file(&db, input_file)
+            .tokens(&db)
+            .iter()
+            .copied()
+            .filter(|token| !matches!(token, Token::Whitespace(_)))
+            .collect();
+        let number = |text: &str| Token::Number(Word::intern(&db, text));
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Comment(6),
+                number("1"),
+                Token::Comment(10),
+                Token::Comment(28),
+                number("2"),
+                Token::Op('/'),
+                number("3"),
+            ]
+        );
+        assert!(lex_file::accumulated::<Diagnostics>(&db, input_file).is_empty());
+    }
+
+    #[test]
+    fn lexer_errors() {
+        let db = Database::default();
+        let source_text = "a @ b;\n}\nf(1, [2);\n/* open";
+        let input_file = InputFile::new(&db, Word::intern(&db, "test"), source_text.to_string());
+        lex_file(&db, input_file);
+        let diagnostics: Vec<(String, Vec<(String, u32, u32)>)> =
+            lex_file::accumulated::<Diagnostics>(&db, input_file)
+                .into_iter()
+                .map(|diagnostic| {
+                    let labels = diagnostic
+                        .labels
+                        .into_iter()
+                        .map(|label| {
+                            (
+                                label.message,
+                                label.span.start.into(),
+                                label.span.end.into(),
+                            )
+                        })
+                        .collect();
+                    (diagnostic.message, labels)
+                })
+                .collect();
+        let label = |message: &str, start, end| (message.to_string(), start, end);
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    "unknown character `@`".to_string(),
+                    vec![label("here", 2, 3)]
+                ),
+                (
+                    "unexpected `}`".to_string(),
+                    vec![label(
+                        "this delimiter has no matching opening delimiter",
+                        7,
+                        8
+                    )]
+                ),
+                // the `)` closes the `(`, and leaves the `[` unclosed
+                (
+                    "unclosed `[`".to_string(),
+                    vec![
+                        label("this `[` is never closed", 14, 15),
+                        label("expected `]` before this `)`", 16, 17),
+                    ]
+                ),
+                (
+                    "unterminated block comment".to_string(),
+                    vec![label("this comment is never closed", 19, 21)]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        let db = Database::default();
+        let source_text = "größe = 変数_1 + _x;";
+        let input_file = InputFile::new(&db, Word::intern(&db, "test"), source_text.to_string());
+        let tokens = lex_file(&db, input_file).tokens(&db);
+        let alphabetic = |text: &str| Token::Alphabetic(Word::intern(&db, text));
+        assert_eq!(tokens[0], alphabetic("größe"));
+        assert_eq!(tokens[4], alphabetic("変数_1"));
+        assert_eq!(tokens[8], alphabetic("_x"));
+
+        // spans count characters, not bytes
+        assert_eq!(tokens[0].span_len(&db), 5);
+        assert_eq!(tokens[4].span_len(&db), 4);
+    }
 }
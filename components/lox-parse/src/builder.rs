@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use lox_ir::{
+    cst::{GreenElement, GreenNode, GreenToken, SyntaxKind},
+    token::Token,
+    token_tree::TokenTree,
+};
+
+use crate::tokens::Tokens;
+
+/// Builds the green tree of a `TokenTree` as the parser consumes its tokens.
+///
+/// The parser only sees the tokens it does not skip, so the builder compares its position
+/// with the position of the parser to record every token, trivia included. Nodes are created
+/// once they are complete, from a checkpoint taken where they start, so that the kind of a
+/// node can depend on what follows its start, like for binary expressions and for
+/// statements that fail to parse.
+pub(crate) struct Builder<'me> {
+    db: &'me dyn crate::Db,
+
+    /// Tokens that the parser consumed or skipped but that are not recorded yet,
+    /// followed by the tokens that the parser did not reach yet.
+    pending: &'me [Token],
+
+    /// The nodes and tokens recorded so far, the last ones are the children of the nodes
+    /// that are not complete yet.
+    children: Vec<GreenElement>,
+
+    /// The trees of the nested `TokenTree`s that were parsed, by sub-parsers.
+    subtrees: Vec<(TokenTree, Arc<GreenNode>)>,
+}
+
+/// Where a node starts, see [`Builder::checkpoint`].
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Checkpoint(usize);
+
+impl<'me> Builder<'me> {
+    pub(crate) fn new(db: &'me dyn crate::Db, token_tree: TokenTree) -> Self {
+        Self {
+            db,
+            pending: token_tree.tokens(db),
+            children: vec![],
+            subtrees: vec![],
+        }
+    }
+
+    /// Marks the start of a node at the next token of `tokens`. The trivia before
+    /// the node is left out of it.
+    pub(crate) fn checkpoint(&mut self, tokens: &Tokens<'_>) -> Checkpoint {
+        let consumed = self.pending.len() - tokens.tokens.len();
+        self.record(consumed);
+        Checkpoint(self.children.len())
+    }
+
+    /// Creates a node of kind `kind` with everything consumed since `checkpoint`. The trivia
+    /// after the last token consumed is left out of it.
+    pub(crate) fn finish_node_at(
+        &mut self,
+        checkpoint: Checkpoint,
+        kind: SyntaxKind,
+        tokens: &Tokens<'_>,
+    ) {
+        let consumed = self.pending.len() - tokens.tokens.len();
+        let end = self.pending[..consumed]
+            .iter()
+            .rposition(|token| !is_trivia(*token))
+            .map_or(0, |index| index + 1);
+        self.record(end);
+        let children = self.children.split_off(checkpoint.0);
+        self.children
+            .push(GreenElement::Node(Arc::new(GreenNode::new(kind, children))));
+    }
+
+    /// Records the tree that a sub-parser built for `token_tree`, to use in place
+    /// of the tokens of the tree.
+    pub(crate) fn add_subtree(&mut self, token_tree: TokenTree, green: Arc<GreenNode>) {
+        self.subtrees.push((token_tree, green));
+    }
+
+    /// Creates the node for the contents of `token_tree`, including the tokens that the
+    /// parser did not reach.
+    pub(crate) fn finish(mut self, token_tree: TokenTree) -> Arc<GreenNode> {
+        self.record(self.pending.len());
+        Arc::new(GreenNode::token_tree(token_tree, self.children))
+    }
+
+    fn record(&mut self, count: usize) {
+        let pending: &'me [Token] = self.pending;
+        let (recorded, pending) = pending.split_at(count);
+        self.pending = pending;
+        for &token in recorded {
+            let element = self.element(token);
+            self.children.push(element);
+        }
+    }
+
+    fn element(&mut self, token: Token) -> GreenElement {
+        match token {
+            Token::Tree(token_tree) | Token::Interpolation(token_tree) => {
+                let green = match self
+                    .subtrees
+                    .iter()
+                    .position(|(tree, _)| *tree == token_tree)
+                {
+                    Some(index) => self.subtrees.swap_remove(index).1,
+                    None => raw_tree(self.db, token_tree),
+                };
+                GreenElement::Node(green)
+            }
+            _ => GreenElement::Token(GreenToken {
+                token,
+                len: token.span_len(self.db),
+            }),
+        }
+    }
+}
+
+fn is_trivia(token: Token) -> bool {
+    matches!(token, Token::Whitespace(_) | Token::Comment(_))
+}
+
+// the tree of a `TokenTree` that was not parsed, like the tokens skipped after a syntax error
+fn raw_tree(db: &dyn crate::Db, token_tree: TokenTree) -> Arc<GreenNode> {
+    Builder::new(db, token_tree).finish(token_tree)
+}
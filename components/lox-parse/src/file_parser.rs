@@ -1,10 +1,225 @@
-use crate::parser::Parser;
+use std::collections::HashMap;
 
-use lox_ir::{input_file::InputFile, syntax::Stmt};
+use crate::{
+    lower::{delimited_span, Lower},
+    parser::Parser,
+};
 
+use lox_ir::{
+    ast,
+    cst::{SyntaxKind, SyntaxNode},
+    function::{Function, FunctionBody, ParamKind},
+    input_file::InputFile,
+    span::{FileSpan, Offset, Span},
+    syntax::{Expr, Stmt, StmtKind},
+    token::Token,
+    token_tree::TokenTree,
+};
+
+/// The concrete syntax tree of `input_file`, including the bodies of its functions.
 #[salsa::tracked(return_ref)]
-pub fn parse_file(db: &dyn crate::Db, input_file: InputFile) -> Vec<Stmt> {
+pub fn parse_file_cst(db: &dyn crate::Db, input_file: InputFile) -> SyntaxNode {
     let token_tree = lox_lex::lex_file(db, input_file);
     let mut parser = Parser::new(db, token_tree);
-    parser.parse()
+    parser.parse();
+    SyntaxNode::new_root(parser.finish(), Offset::from(0u32))
+}
+
+#[salsa::tracked(return_ref)]
+pub fn parse_file(db: &dyn crate::Db, input_file: InputFile) -> Vec<Stmt> {
+    let tree = ast::Tree::cast(parse_file_cst(db, input_file).clone()).unwrap();
+    Lower::new(db, input_file).stmts(tree.stmts())
+}
+
+/// The nodes that the functions of a file are lowered from, so that each function finds its
+/// own without walking the whole tree.
+#[salsa::tracked(return_ref)]
+pub fn function_nodes(db: &dyn crate::Db, input_file: InputFile) -> FunctionNodes {
+    let mut nodes = FunctionNodes::default();
+    for node in parse_file_cst(db, input_file).descendants() {
+        if let Some(token_tree) = node.token_tree() {
+            nodes
+                .trees
+                .entry(token_tree)
+                .or_insert_with(|| node.clone());
+        }
+        // the expression of an arrow function, or the default value of a parameter
+        if let Some(parent @ (SyntaxKind::Lambda | SyntaxKind::Param)) =
+            node.parent().map(|parent| parent.kind())
+        {
+            nodes.exprs.entry((node.span(), parent)).or_insert(node);
+        }
+    }
+    nodes
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FunctionNodes {
+    // the node of each tree, like the block of a function, by the tree it was built from
+    trees: HashMap<TokenTree, SyntaxNode>,
+
+    // the expressions whose parent is a `Lambda` or a `Param`, by their span and parent kind
+    exprs: HashMap<(Span, SyntaxKind), SyntaxNode>,
+}
+
+impl FunctionNodes {
+    fn tree(&self, token_tree: TokenTree) -> Option<&SyntaxNode> {
+        self.trees.get(&token_tree)
+    }
+
+    // the expression at `span` whose parent is of kind `parent`
+    fn expr(&self, span: Span, parent: SyntaxKind) -> Option<ast::Expr> {
+        self.exprs
+            .get(&(span, parent))
+            .cloned()
+            .and_then(ast::Expr::cast)
+    }
+}
+
+// The body of a function is lowered on its own, so that the functions it declares are only
+// created when the function is needed. Its syntax errors are reported with the ones of the file.
+#[salsa::tracked(return_ref)]
+pub fn parse_function(db: &dyn crate::Db, function: Function) -> Vec<Stmt> {
+    let body = function.body(db);
+    let input_file = body.input_file(db);
+    let nodes = function_nodes(db, input_file);
+    let lower = Lower::new(db, input_file);
+    match body {
+        FunctionBody::Block(token_tree) => {
+            let tree = nodes
+                .tree(token_tree)
+                .cloned()
+                .and_then(ast::Tree::cast)
+                .expect("the body of a function is in the syntax tree of its file");
+            lower.stmts(tree.stmts())
+        }
+        FunctionBody::Expr(_, span) => {
+            // the expression of an arrow function is returned
+            let expr = nodes
+                .expr(span, SyntaxKind::Lambda)
+                .expect("the body of a function is in the syntax tree of its file");
+            vec![Stmt::new(StmtKind::Return(Some(lower.expr(expr))), span)]
+        }
+    }
+}
+
+/// The default values of the parameters of `function`, `None` for the parameters without one.
+#[salsa::tracked(return_ref)]
+pub fn parse_defaults(db: &dyn crate::Db, function: Function) -> Vec<Option<Expr>> {
+    let input_file = function.body(db).input_file(db);
+    let nodes = function_nodes(db, input_file);
+    let lower = Lower::new(db, input_file);
+    function
+        .params(db)
+        .iter()
+        .map(|param| match param.kind {
+            ParamKind::Optional(span) => {
+                let expr = nodes
+                    .expr(span, SyntaxKind::Param)
+                    .expect("the default value of a parameter is in the syntax tree of its file");
+                Some(lower.expr(expr))
+            }
+            ParamKind::Required | ParamKind::Rest => None,
+        })
+        .collect()
+}
+
+/// The span of the parameter list of `function`, parentheses included, if it parsed without
+/// errors. `None` for the main function of a file.
+pub fn parameters_span(db: &dyn crate::Db, function: Function) -> Option<FileSpan> {
+    let body = function.body(db);
+    let input_file = body.input_file(db);
+    let nodes = function_nodes(db, input_file);
+    let node = match body {
+        // the tree is in the block of a `FunDecl` or a `Lambda`
+        FunctionBody::Block(token_tree) => nodes.tree(token_tree)?.parent()?.parent()?.clone(),
+        FunctionBody::Expr(_, span) => nodes
+            .expr(span, SyntaxKind::Lambda)?
+            .syntax()
+            .parent()?
+            .clone(),
+    };
+    if !matches!(node.kind(), SyntaxKind::FunDecl | SyntaxKind::Lambda) {
+        return None;
+    }
+    let params = node.children().find_map(ast::Tree::cast)?;
+
+    // the parameters that failed to parse are left as tokens in the list
+    let complete = params
+        .syntax()
+        .tokens()
+        .all(|token| token.token == Token::Comma)
+        && params
+            .syntax()
+            .children()
+            .all(|node| node.kind() == SyntaxKind::Param);
+    complete.then(|| delimited_span(&params).anchor_to(input_file))
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_ir::{
+        cst::SyntaxKind,
+        span::Offset,
+        syntax::{ExprKind, StmtKind},
+    };
+
+    use crate::test_db::Database;
+
+    use super::{parse_file, parse_file_cst};
+
+    #[test]
+    fn lossless_tree() {
+        let db = &mut Database::default();
+        let source_text =
+            "var a = 1; // one\nfun f(x) {\n  return x;\n}\nprint (a + f(2)) * 3;\nprint ;\n";
+        let input_file = db.new_input_file("test.lox", source_text.to_string());
+        let root = parse_file_cst(db, input_file);
+
+        // the tokens cover the text without gaps, trivia and errors included
+        let mut offset = Offset::from(0u32);
+        for token in root.descendant_tokens() {
+            assert_eq!(token.span.start, offset);
+            offset = token.span.end;
+        }
+        assert_eq!(offset, Offset::from(source_text.chars().count()));
+        assert_eq!(root.text(source_text), source_text);
+
+        let kinds: Vec<SyntaxKind> = root.children().map(|node| node.kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                SyntaxKind::VarDecl,
+                SyntaxKind::FunDecl,
+                SyntaxKind::PrintStmt,
+                SyntaxKind::ErrorStmt,
+            ]
+        );
+
+        // nodes leave out the trivia around them
+        let texts: Vec<String> = root
+            .descendants()
+            .iter()
+            .filter(|node| {
+                matches!(
+                    node.kind(),
+                    SyntaxKind::VarDecl | SyntaxKind::ParenExpr | SyntaxKind::ReturnStmt
+                )
+            })
+            .map(|node| node.text(source_text))
+            .collect();
+        assert_eq!(texts, ["var a = 1;", "return x;", "(a + f(2))"]);
+
+        // the tree is lowered to the same statements as before
+        let stmts = parse_file(db, input_file);
+        assert_eq!(stmts.len(), 4);
+        let StmtKind::Print(print) = &stmts[2].kind else {
+            panic!("expected a print statement");
+        };
+        let ExprKind::BinaryOp(left, _, _) = &print.kind else {
+            panic!("expected a binary operation");
+        };
+        assert!(matches!(left.kind, ExprKind::Parenthesized(_)));
+        assert_eq!(stmts[3].kind, StmtKind::Error);
+    }
 }
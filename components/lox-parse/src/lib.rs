@@ -1,14 +1,24 @@
 #![feature(trait_upcasting)]
 
+mod builder;
 pub mod file_parser;
+mod lower;
 mod parser;
 pub mod prelude;
+#[cfg(test)]
+mod test_db;
 mod token_test;
 mod tokens;
-pub use file_parser::parse_file;
+pub use file_parser::{parse_file, parse_file_cst};
 
 #[salsa::jar(db = Db)]
-pub struct Jar(parse_file);
+pub struct Jar(
+    parse_file,
+    parse_file_cst,
+    file_parser::function_nodes,
+    file_parser::parse_function,
+    file_parser::parse_defaults,
+);
 
 pub trait Db: salsa::DbWithJar<Jar> + lox_lex::Db + lox_ir::Db {}
 impl<T> Db for T where T: salsa::DbWithJar<Jar> + lox_lex::Db + lox_ir::Db {}
@@ -0,0 +1,243 @@
+use lox_ir::{
+    ast::{self, BinaryOp},
+    cst::{SyntaxElement, SyntaxToken},
+    function::{Function, FunctionBody, Param, ParamKind},
+    input_file::InputFile,
+    kw::Keyword,
+    span::Span,
+    syntax::{Expr, ExprKind, Stmt, StmtKind},
+    token::Token,
+    word::Word,
+};
+
+use crate::token_test::{Identifier, TokenTest};
+
+/// Turns the typed view of the concrete syntax tree into the `syntax` tree that the compiler
+/// uses. The parser already reported the syntax errors, the parts of the code that failed to
+/// parse become `StmtKind::Error` and `ExprKind::Error`.
+pub(crate) struct Lower<'me> {
+    db: &'me dyn crate::Db,
+    input_file: InputFile,
+}
+
+impl<'me> Lower<'me> {
+    pub(crate) fn new(db: &'me dyn crate::Db, input_file: InputFile) -> Self {
+        Self { db, input_file }
+    }
+
+    pub(crate) fn stmts(&self, stmts: Vec<ast::Stmt>) -> Vec<Stmt> {
+        stmts.into_iter().map(|stmt| self.stmt(stmt)).collect()
+    }
+
+    fn stmt(&self, stmt: ast::Stmt) -> Stmt {
+        let span = stmt.syntax().span();
+        let kind = match stmt {
+            ast::Stmt::Var(stmt) => StmtKind::VariableDeclaration {
+                name: self.identifier(stmt.name().unwrap()),
+                initializer: stmt.initializer().map(|expr| self.expr(expr)),
+            },
+            ast::Stmt::Fun(stmt) => {
+                let name = self.identifier(stmt.name().unwrap());
+                let params = self.params(stmt.params());
+                let body = FunctionBody::Block(stmt.body().unwrap().tree().unwrap().token_tree());
+                StmtKind::FunctionDeclaration(Function::new(self.db, name, params, body))
+            }
+            ast::Stmt::Expr(stmt) => StmtKind::Expr(self.expr(stmt.expr().unwrap())),
+            ast::Stmt::Print(stmt) => StmtKind::Print(self.expr(stmt.expr().unwrap())),
+            ast::Stmt::Block(stmt) => StmtKind::Block(self.stmts(stmt.stmts())),
+            ast::Stmt::If(stmt) => StmtKind::If {
+                condition: self.tree_expr(stmt.condition()),
+                then_branch: Box::new(self.stmt(stmt.then_branch().unwrap())),
+                else_branch: stmt
+                    .else_branch()
+                    .map(|else_branch| Box::new(self.stmt(else_branch))),
+            },
+            ast::Stmt::While(stmt) => StmtKind::While {
+                condition: self.tree_expr(stmt.condition()),
+                body: Box::new(self.stmt(stmt.body().unwrap())),
+            },
+            ast::Stmt::For(stmt) => StmtKind::For {
+                initializer: stmt
+                    .initializer()
+                    .map(|initializer| Box::new(self.stmt(initializer))),
+                condition: stmt.condition().map(|expr| self.expr(expr)),
+                increment: stmt.increment().map(|expr| self.expr(expr)),
+                body: Box::new(self.stmt(stmt.body().unwrap())),
+            },
+            ast::Stmt::Return(stmt) => StmtKind::Return(stmt.expr().map(|expr| self.expr(expr))),
+            ast::Stmt::Error(_) => StmtKind::Error,
+        };
+        Stmt::new(kind, span)
+    }
+
+    pub(crate) fn expr(&self, expr: ast::Expr) -> Expr {
+        let span = expr.syntax().span();
+        let kind = match expr {
+            ast::Expr::Literal(expr) => match expr.token().token {
+                Token::Number(word) => ExprKind::NumberLiteral(word),
+                Token::String(word, _) => ExprKind::StringLiteral(word),
+                token => match token.alphabetic_str(self.db) {
+                    Some(str) if str == Keyword::True.str() => ExprKind::BooleanLiteral(true),
+                    Some(str) if str == Keyword::False.str() => ExprKind::BooleanLiteral(false),
+                    _ => ExprKind::NilLiteral,
+                },
+            },
+            ast::Expr::NameRef(expr) => ExprKind::Variable(self.identifier(expr.name())),
+            ast::Expr::InterpolatedString(expr) => {
+                ExprKind::Interpolation(self.interpolation(span, expr))
+            }
+            ast::Expr::Paren(expr) => {
+                // errors inside the parentheses stand for the whole parenthesized expression
+                let inner = match expr.tree().and_then(|tree| tree.expr()) {
+                    Some(ast::Expr::Error(_)) | None => Expr::new(ExprKind::Error, span),
+                    Some(inner) => self.expr(inner),
+                };
+                ExprKind::Parenthesized(Box::new(inner))
+            }
+            ast::Expr::Unary(expr) => ExprKind::UnaryOp(
+                expr.op().unwrap(),
+                Box::new(self.expr(expr.operand().unwrap())),
+            ),
+            ast::Expr::Binary(expr) => {
+                let left = Box::new(self.expr(expr.lhs().unwrap()));
+                let right = Box::new(self.expr(expr.rhs().unwrap()));
+                match expr.op(self.db).unwrap() {
+                    BinaryOp::Op(op) => ExprKind::BinaryOp(left, op, right),
+                    BinaryOp::And => ExprKind::LogicalAnd(left, right),
+                    BinaryOp::Or => ExprKind::LogicalOr(left, right),
+                }
+            }
+            ast::Expr::Assign(expr) => {
+                let target = expr.target().unwrap();
+                let value = Box::new(self.expr(expr.value().unwrap()));
+                match target {
+                    ast::Expr::NameRef(target) => ExprKind::Assign {
+                        name: self.identifier(target.name()),
+                        value,
+                    },
+                    ast::Expr::Field(target) => ExprKind::Set {
+                        object: Box::new(self.expr(target.object().unwrap())),
+                        name: self.identifier(target.name().unwrap()),
+                        value,
+                    },
+                    // the invalid target is reported, only the target is kept
+                    target => return self.expr(target),
+                }
+            }
+            ast::Expr::Call(expr) => {
+                let mut arguments: Vec<Expr> = expr
+                    .arguments()
+                    .into_iter()
+                    .map(|argument| self.expr(argument))
+                    .collect();
+                let mut names = vec![];
+                for argument in expr.named_arguments() {
+                    names.push(self.identifier(argument.name()));
+                    arguments.push(self.expr(argument.value().unwrap()));
+                }
+                ExprKind::Call {
+                    callee: Box::new(self.expr(expr.callee().unwrap())),
+                    arguments,
+                    names,
+                }
+            }
+            ast::Expr::Field(expr) => ExprKind::Get {
+                object: Box::new(self.expr(expr.object().unwrap())),
+                name: self.identifier(expr.name().unwrap()),
+            },
+            ast::Expr::Lambda(expr) => {
+                let name = Word::intern(self.db, "anonymous");
+                let params = self.params(expr.params());
+                let body = match expr.body_block() {
+                    Some(block) => FunctionBody::Block(block.tree().unwrap().token_tree()),
+                    None => {
+                        let body = expr.body_expr().unwrap();
+                        FunctionBody::Expr(self.input_file, body.syntax().span())
+                    }
+                };
+                ExprKind::Lambda(Function::new(self.db, name, params, body))
+            }
+            ast::Expr::Error(_) => ExprKind::Error,
+        };
+        Expr::new(kind, span)
+    }
+
+    fn params(&self, params: Vec<ast::Param>) -> Vec<Param> {
+        params
+            .into_iter()
+            .map(|param| {
+                let kind = if param.is_rest() {
+                    ParamKind::Rest
+                } else if let Some(default_value) = param.default_value() {
+                    ParamKind::Optional(default_value.syntax().span())
+                } else {
+                    ParamKind::Required
+                };
+                Param {
+                    name: self.identifier(param.name()),
+                    kind,
+                }
+            })
+            .collect()
+    }
+
+    // The condition in the parentheses `tree`, an error in the parentheses stands for all of
+    // them.
+    fn tree_expr(&self, tree: Option<ast::Tree>) -> Expr {
+        let tree = tree.unwrap();
+        match tree.expr() {
+            Some(ast::Expr::Error(_)) | None => Expr::new(ExprKind::Error, delimited_span(&tree)),
+            Some(expr) => self.expr(expr),
+        }
+    }
+
+    // The pieces of text and the interpolated expressions of the string literal at `span`.
+    // Empty pieces of text are left out, errors in an interpolated expression stand for the
+    // whole string.
+    fn interpolation(&self, span: Span, expr: ast::InterpolatedString) -> Vec<Expr> {
+        let mut parts = vec![];
+        for child in expr.tree().syntax().children_with_tokens() {
+            match child {
+                SyntaxElement::Token(token) => {
+                    if let Token::String(word, _) = token.token {
+                        if !word.as_str(self.db).is_empty() {
+                            parts.push(Expr::new(ExprKind::StringLiteral(word), token.span));
+                        }
+                    }
+                }
+                SyntaxElement::Node(node) => {
+                    let expr = ast::Tree::cast(node).and_then(|tree| tree.expr());
+                    parts.push(match expr {
+                        Some(ast::Expr::Error(_)) | None => Expr::new(ExprKind::Error, span),
+                        Some(expr) => self.expr(expr),
+                    });
+                }
+            }
+        }
+        parts
+    }
+
+    fn identifier(&self, token: SyntaxToken) -> Word {
+        let span = token.span.anchor_to(self.input_file);
+        Identifier.test(self.db, token.token, span).unwrap()
+    }
+}
+
+// The span of `tree` along with its delimiters, the closing delimiter is missing when the
+// tree ends the file.
+pub(crate) fn delimited_span(tree: &ast::Tree) -> Span {
+    let span = tree.syntax().span();
+    let parent = tree.syntax().parent().unwrap();
+    let mut start = span.start;
+    let mut end = span.end;
+    for token in parent.tokens() {
+        if let Token::Delimiter(_) = token.token {
+            if token.span.end == span.start {
+                start = token.span.start;
+            } else if token.span.start == span.end {
+                end = token.span.end;
+            }
+        }
+    }
+    Span::from(start, end)
+}
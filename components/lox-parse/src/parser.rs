@@ -1,22 +1,32 @@
+use std::sync::Arc;
+
 use lox_ir::{
+    cst::{GreenNode, SyntaxKind},
     diagnostic::DiagnosticBuilder,
     input_file::InputFile,
     kw::Keyword,
     span::Span,
-    syntax::{Expr, Op, Stmt},
+    syntax::Op,
     token::Token,
     token_tree::TokenTree,
 };
 
 use crate::{
-    token_test::{AnyTree, Identifier, Number, StringLiteral, TokenTest},
+    builder::{Builder, Checkpoint},
+    token_test::{AnyTree, Identifier, InterpolatedString, Number, StringLiteral, TokenTest},
     tokens::Tokens,
 };
 
+/// Parses a `TokenTree` into the green tree of `lox_ir::cst`, reporting the syntax errors.
+///
+/// The contents of the nested token trees, like blocks and parenthesized expressions, are
+/// parsed by sub-parsers, whose trees end up in the tree of the parser.
 pub(crate) struct Parser<'me> {
     db: &'me dyn crate::Db,
     input_file: InputFile,
+    token_tree: TokenTree,
     tokens: Tokens<'me>,
+    builder: Builder<'me>,
 }
 
 impl<'me> Parser<'me> {
@@ -25,197 +35,301 @@ impl<'me> Parser<'me> {
         Self {
             db,
             input_file: token_tree.input_file(db),
+            token_tree,
             tokens,
+            builder: Builder::new(db, token_tree),
         }
     }
 
-    pub(crate) fn parse(&mut self) -> Vec<Stmt> {
+    /// Returns the tree of everything parsed so far, along with the tokens that were not.
+    pub(crate) fn finish(self) -> Arc<GreenNode> {
+        self.builder.finish(self.token_tree)
+    }
+
+    pub(crate) fn parse(&mut self) {
         tracing::debug!("parsing {:?}", self.tokens.tokens);
-        let mut stmts = vec![];
-        while let Some(stmt) = self.declaration() {
-            stmts.push(stmt);
+        while self.tokens.peek().is_some() {
+            let checkpoint = self.checkpoint();
+            let remaining = self.tokens.tokens.len();
+            if self.declaration().is_none() {
+                // the error is reported, skip the rest of the statement so that it does not
+                // cause more errors, and keep parsing the statements after it
+                self.synchronize(remaining);
+                self.finish_node(checkpoint, SyntaxKind::ErrorStmt);
+            }
         }
-        if self.tokens.peek().is_some() {
-            let span = self.tokens.peek_span();
-            self.error(span, "extra tokens after statement")
-                .emit(self.db);
+    }
+
+    /// Skips the tokens of a statement that failed to parse: up to and including the next `;`,
+    /// or up to the next keyword that starts a statement. Blocks are a single token tree, so
+    /// this never skips past the end of a block. At least one token is skipped when the
+    /// statement consumed none, so that parsing always makes progress.
+    fn synchronize(&mut self, remaining: usize) {
+        let mut skip_first = self.tokens.tokens.len() == remaining;
+        while let Some(token) = self.tokens.peek() {
+            if !skip_first && self.starts_stmt() {
+                break;
+            }
+            skip_first = false;
+            self.tokens.consume();
+            if token == Token::Semicolon {
+                break;
+            }
         }
-        stmts
+    }
+
+    // true if the next token is a keyword that starts a statement
+    fn starts_stmt(&mut self) -> bool {
+        [
+            Keyword::Var,
+            Keyword::Fun,
+            Keyword::Print,
+            Keyword::If,
+            Keyword::While,
+            Keyword::For,
+            Keyword::Return,
+        ]
+        .into_iter()
+        .any(|keyword| self.peek(keyword).is_some())
     }
 
     #[tracing::instrument(skip(self))]
-    fn declaration(&mut self) -> Option<Stmt> {
+    fn declaration(&mut self) -> Option<()> {
+        let checkpoint = self.checkpoint();
         if self.eat(Keyword::Var).is_some() {
-            self.var_declaration()
+            self.var_declaration(checkpoint)
         } else if self.eat(Keyword::Fun).is_some() {
-            self.func_declaration()
+            self.func_declaration(checkpoint)
         } else {
             self.stmt()
         }
     }
 
     #[tracing::instrument(skip(self))]
-    fn func_declaration(&mut self) -> Option<Stmt> {
-        let name = self.eat(Identifier)?.1;
-        let parameters_tree = self.delimited('(')?.1;
-        let mut sub_parser = Parser::new(self.db, parameters_tree);
-        let mut parameters = vec![];
-        while sub_parser.tokens.peek().is_some() {
-            let (_, id) = sub_parser.eat(Identifier)?;
-            parameters.push(id);
-            if sub_parser.eat(Token::Comma).is_none() {
+    fn func_declaration(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        self.eat(Identifier)
+            .or_report_error(self, || "expected a function name after `fun`")?;
+        let (_, parameters_tree) = self
+            .delimited('(')
+            .or_report_error(self, || "expected `(` after the function name")?;
+        self.parse_tree(parameters_tree, |parser| parser.parameters());
+        self.function_body()?;
+        self.finish_node(checkpoint, SyntaxKind::FunDecl);
+        Some(())
+    }
+
+    // block          -> "{" declaration* "}" ;
+    fn function_body(&mut self) -> Option<()> {
+        let checkpoint = self.checkpoint();
+        let (_, body_tree) = self
+            .delimited('{')
+            .or_report_error(self, || "expected `{` before the function body")?;
+        self.parse_tree(body_tree, |parser| parser.parse());
+        self.finish_node(checkpoint, SyntaxKind::Block);
+        Some(())
+    }
+
+    // lambda         -> "fun" "(" parameters? ")" block
+    //                 | "(" parameters? ")" "=>" ( block | expression ) ;
+    //
+    // The `fun` of the first form is already consumed.
+    fn lambda(&mut self, arrow: bool) -> Option<()> {
+        let (_, parameters_tree) = self
+            .delimited('(')
+            .or_report_error(self, || "expected `(` after `fun`")?;
+        self.parse_tree(parameters_tree, |parser| parser.parameters());
+        if arrow {
+            // checked by `arrow_ahead`
+            self.eat_op(Op::Arrow).unwrap();
+            if self.peek(Token::Delimiter('{')).is_none() {
+                return self.parse_expr().map(|_| ());
+            }
+        }
+        self.function_body()
+    }
+
+    // Whether the next tokens start an arrow function, like `(a, b) =>`.
+    fn arrow_ahead(&self) -> bool {
+        let mut tokens = self.tokens;
+        if tokens.consume() != Some(Token::Delimiter('(')) {
+            return false;
+        }
+        // the lexer always produces a token tree after a delimiter
+        tokens.consume();
+        if tokens.consume() != Some(Token::Delimiter(')')) {
+            return false;
+        }
+        Self::test_op_in(tokens, Op::Arrow).is_some()
+    }
+
+    // parameters     -> parameter ( "," parameter )* ;
+    // parameter      -> IDENTIFIER ( "=" expression )? | "..." IDENTIFIER ;
+    fn parameters(&mut self) {
+        let mut rest_span = None;
+        while self.tokens.peek().is_some() {
+            if let Some(span) = rest_span.take() {
+                self.error(span, "the rest parameter must be the last parameter")
+                    .emit(self.db);
+            }
+
+            // an invalid parameter list is reported but the function is still declared, so that
+            // its calls are not reported as well
+            let checkpoint = self.checkpoint();
+            let ellipsis = self.eat(Token::Ellipsis);
+            let Some((name_span, _)) = self
+                .eat(Identifier)
+                .or_report_error(self, || "expected a parameter name")
+            else {
+                break;
+            };
+            if let Some((ellipsis_span, _)) = ellipsis {
+                rest_span = Some(ellipsis_span.to(name_span));
+            } else if self.eat_op(Op::Equal).is_some() && self.parse_expr().is_none() {
+                break;
+            }
+            self.finish_node(checkpoint, SyntaxKind::Param);
+            if self.tokens.peek().is_some()
+                && self
+                    .eat(Token::Comma)
+                    .or_report_error(self, || "expected `,` between parameters")
+                    .is_none()
+            {
                 break;
             }
         }
-        let body_tree = self.delimited('{')?.1;
-        Some(Stmt::FunctionDeclaration(lox_ir::function::Function::new(
-            self.db, name, parameters, body_tree,
-        )))
     }
 
     // "var" IDENTIFIER ( "=" expression )? ";" ;
     #[tracing::instrument(skip(self))]
-    fn var_declaration(&mut self) -> Option<Stmt> {
-        if let Some((_, id)) = self.eat(Identifier) {
-            let initializer = if self.eat_op(Op::Equal).is_some() {
-                let expr = self.parse_expr()?;
-                Some(expr)
-            } else {
-                None
-            };
-            self.eat(Token::Semicolon)
-                .or_report_error(self, || "expected `;`");
-            Some(Stmt::VariableDeclaration {
-                name: id,
-                initializer,
-            })
-        } else {
-            None
+    fn var_declaration(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        self.eat(Identifier)
+            .or_report_error(self, || "expected a variable name after `var`")?;
+        if self.eat_op(Op::Equal).is_some() {
+            self.parse_expr()?;
         }
+        self.eat(Token::Semicolon)
+            .or_report_error(self, || "expected `;`");
+        self.finish_node(checkpoint, SyntaxKind::VarDecl);
+        Some(())
     }
 
     #[tracing::instrument(skip(self))]
-    fn stmt(&mut self) -> Option<Stmt> {
+    fn stmt(&mut self) -> Option<()> {
+        let checkpoint = self.checkpoint();
         if self.eat(Keyword::Print).is_some() {
-            return self.print_stmt();
+            return self.print_stmt(checkpoint);
         } else if let Some((_, token_tree)) = self.delimited('{') {
             // parse a block
-            let mut parser = Parser::new(self.db, token_tree);
-            let stmts = parser.parse();
-            return Some(Stmt::Block(stmts));
+            self.parse_tree(token_tree, |parser| parser.parse());
+            self.finish_node(checkpoint, SyntaxKind::Block);
+            return Some(());
         } else if self.eat(Keyword::If).is_some() {
-            return self.if_stmt();
+            return self.if_stmt(checkpoint);
         } else if self.eat(Keyword::While).is_some() {
-            return self.while_stmt();
+            return self.while_stmt(checkpoint);
         } else if self.eat(Keyword::For).is_some() {
-            return self.for_stmt();
+            return self.for_stmt(checkpoint);
         } else if self.eat(Keyword::Return).is_some() {
-            return self.return_stmt();
+            return self.return_stmt(checkpoint);
         }
         self.expr_stmt()
     }
 
     #[tracing::instrument(skip(self))]
-    fn return_stmt(&mut self) -> Option<Stmt> {
-        if self.eat(Token::Semicolon).is_some() {
-            return Some(Stmt::Return(None));
+    fn return_stmt(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        if self.eat(Token::Semicolon).is_none() {
+            self.parse_expr()?;
+            self.eat(Token::Semicolon)
+                .or_report_error(self, || "expected `;`");
         }
-        let expr = self.parse_expr()?;
-        self.eat(Token::Semicolon)
-            .or_report_error(self, || "expected `;`");
-        Some(Stmt::Return(Some(expr)))
+        self.finish_node(checkpoint, SyntaxKind::ReturnStmt);
+        Some(())
     }
 
     // forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
     //              expression? ";"
     //              expression? ")" statement ;
     #[tracing::instrument(skip(self))]
-    fn for_stmt(&mut self) -> Option<Stmt> {
-        let (_, token_tree) = self.delimited('(')?;
-        let mut sub_parser = Parser::new(self.db, token_tree);
-        let initializer = if sub_parser.eat(Keyword::Var).is_some() {
-            sub_parser.var_declaration()
-        } else if sub_parser.eat(Token::Semicolon).is_some() {
-            None
-        } else {
-            sub_parser.expr_stmt()
-        };
-
-        let condition = if sub_parser.peek(Token::Semicolon).is_some() {
-            None
-        } else {
-            let expr = sub_parser.parse_expr()?;
-            Some(expr)
-        };
-
-        sub_parser
-            .eat(Token::Semicolon)
-            .or_report_error(&mut sub_parser, || "expected `;`");
+    fn for_stmt(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        let (_, token_tree) = self
+            .delimited('(')
+            .or_report_error(self, || "expected `(` after `for`")?;
+        self.parse_tree(token_tree, |parser| parser.for_clauses())?;
+        self.stmt()?;
+        self.finish_node(checkpoint, SyntaxKind::ForStmt);
+        Some(())
+    }
+
+    // the clauses between the parentheses of a `for`
+    fn for_clauses(&mut self) -> Option<()> {
+        let initializer_checkpoint = self.checkpoint();
+        if self.eat(Keyword::Var).is_some() {
+            self.var_declaration(initializer_checkpoint)?;
+        } else if self.eat(Token::Semicolon).is_none() {
+            self.expr_stmt()?;
+        }
 
-        let increment = if sub_parser.tokens.peek().is_some() {
-            let expr = sub_parser.parse_expr()?;
-            Some(expr)
-        } else {
-            None
-        };
+        if self.peek(Token::Semicolon).is_none() {
+            self.parse_expr()?;
+        }
 
-        let body = self.stmt()?;
+        self.eat(Token::Semicolon)
+            .or_report_error(self, || "expected `;`");
 
-        Some(Stmt::For {
-            initializer: initializer.map(Box::new),
-            condition,
-            increment,
-            body: Box::new(body),
-        })
+        if self.tokens.peek().is_some() {
+            self.parse_expr()?;
+            self.expect_end();
+        }
+        Some(())
     }
 
     #[tracing::instrument(skip(self))]
-    fn while_stmt(&mut self) -> Option<Stmt> {
-        let (_, token_tree) = self.delimited('(')?;
-        let condition = Parser::new(self.db, token_tree).parse_expr()?;
-        let body = self.stmt()?;
-        Some(Stmt::While {
-            condition,
-            body: Box::new(body),
-        })
+    fn while_stmt(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        let (span, token_tree) = self
+            .delimited('(')
+            .or_report_error(self, || "expected `(` after `while`")?;
+        self.tree_expr(span, token_tree);
+        self.stmt()?;
+        self.finish_node(checkpoint, SyntaxKind::WhileStmt);
+        Some(())
     }
 
     #[tracing::instrument(skip(self))]
-    fn if_stmt(&mut self) -> Option<Stmt> {
-        let (_, token_tree) = self.delimited('(')?;
-        let condition = Parser::new(self.db, token_tree).parse_expr()?;
-        let then_branch = self.stmt()?;
-        let else_branch = if self.eat(Keyword::Else).is_some() {
-            self.stmt().map(Box::new)
-        } else {
-            None
-        };
-        Some(Stmt::If {
-            condition,
-            then_branch: Box::new(then_branch),
-            else_branch,
-        })
+    fn if_stmt(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        let (span, token_tree) = self
+            .delimited('(')
+            .or_report_error(self, || "expected `(` after `if`")?;
+        self.tree_expr(span, token_tree);
+        self.stmt()?;
+        if self.eat(Keyword::Else).is_some() {
+            self.stmt()?;
+        }
+        self.finish_node(checkpoint, SyntaxKind::IfStmt);
+        Some(())
     }
 
     //  "print" expression ";" ;
     #[tracing::instrument(skip(self))]
-    fn print_stmt(&mut self) -> Option<Stmt> {
-        let expr = self.parse_expr()?;
+    fn print_stmt(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        self.parse_expr()?;
         self.eat(Token::Semicolon)
             .or_report_error(self, || "expected `;`");
-        Some(Stmt::Print(expr))
+        self.finish_node(checkpoint, SyntaxKind::PrintStmt);
+        Some(())
     }
 
     #[tracing::instrument(skip(self))]
-    fn expr_stmt(&mut self) -> Option<Stmt> {
-        let expr = self.parse_expr()?;
+    fn expr_stmt(&mut self) -> Option<()> {
+        let checkpoint = self.checkpoint();
+        self.parse_expr()?;
         self.eat(Token::Semicolon)
             .or_report_error(self, || "expected `;`");
-        Some(Stmt::Expr(expr))
+        self.finish_node(checkpoint, SyntaxKind::ExprStmt);
+        Some(())
     }
 
     // expression     -> assignment ;
-    // assignment     -> IDENTIFIER "=" assignment | equality ;
+    // assignment     -> ( call "." )? IDENTIFIER "=" assignment | equality ;
     // equality       -> comparison ( ( "!=" | "==" ) comparison )* ;
     // comparison     -> term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
     // term           -> factor ( ( "-" | "+" ) factor )* ;
@@ -223,208 +337,282 @@ impl<'me> Parser<'me> {
     // unary          -> ( "!" | "-" ) unary
     //             | primary ;
     // primary        → NUMBER | STRING | "true" | "false" | "nil"
-    //             | "(" expression ")" ;
-    fn parse_expr(&mut self) -> Option<Expr> {
+    //             | lambda | "(" expression ")" ;
+    //
+    // The expression functions return the kind of the node they parsed.
+    fn parse_expr(&mut self) -> Option<SyntaxKind> {
         self.assignment()
     }
 
-    // assignment     -> IDENTIFIER "=" assignment | logic_or ;
+    // assignment     -> ( call "." )? IDENTIFIER "=" assignment | logic_or ;
     // assignment is not a statement, it is an expression
     #[tracing::instrument(skip(self))]
-    fn assignment(&mut self) -> Option<Expr> {
-        let expr = self.logic_or()?;
+    fn assignment(&mut self) -> Option<SyntaxKind> {
+        let checkpoint = self.checkpoint();
+        let start = self.tokens.peek_span();
+        let target = self.logic_or()?;
+        let target_span = Span::from(start.start, self.tokens.last_span().end);
         if self.eat_op(Op::Equal).is_some() {
-            let value = self.assignment()?;
-            if let Expr::Variable(name) = expr {
-                return Some(Expr::Assign {
-                    name,
-                    value: Box::new(value),
-                });
-            } else {
-                // FIXME: we should use the span of the `expr` here
-                let span = self.tokens.peek_span();
-                self.error(span, "invalid assignment target").emit(self.db);
+            self.assignment()?;
+            if !matches!(target, SyntaxKind::NameRef | SyntaxKind::FieldExpr) {
+                self.error(target_span, "invalid assignment target")
+                    .emit(self.db);
             }
+            self.finish_node(checkpoint, SyntaxKind::AssignExpr);
+            return Some(SyntaxKind::AssignExpr);
         }
-        Some(expr)
+        Some(target)
     }
 
     // logic_or       -> logic_and ( "or" logic_and )* ;
     #[tracing::instrument(skip(self))]
-    fn logic_or(&mut self) -> Option<Expr> {
-        let mut left = self.logic_and()?;
-
-        loop {
-            if self.eat(Keyword::Or).is_some() {
-                let right = self.logic_and()?;
-                left = Expr::LogicalOr(Box::new(left), Box::new(right));
-                continue;
-            }
-            break;
+    fn logic_or(&mut self) -> Option<SyntaxKind> {
+        let checkpoint = self.checkpoint();
+        let mut kind = self.logic_and()?;
+        while self.eat(Keyword::Or).is_some() {
+            self.logic_and()?;
+            self.finish_node(checkpoint, SyntaxKind::BinaryExpr);
+            kind = SyntaxKind::BinaryExpr;
         }
-        Some(left)
+        Some(kind)
     }
 
     // logic_and       -> equality ( "and" equality )* ;
     #[tracing::instrument(skip(self))]
-    fn logic_and(&mut self) -> Option<Expr> {
-        let mut left = self.equality()?;
-
-        loop {
-            if self.eat(Keyword::And).is_some() {
-                let right = self.equality()?;
-                left = Expr::LogicalAnd(Box::new(left), Box::new(right));
-                continue;
-            }
-            break;
+    fn logic_and(&mut self) -> Option<SyntaxKind> {
+        let checkpoint = self.checkpoint();
+        let mut kind = self.equality()?;
+        while self.eat(Keyword::And).is_some() {
+            self.equality()?;
+            self.finish_node(checkpoint, SyntaxKind::BinaryExpr);
+            kind = SyntaxKind::BinaryExpr;
         }
-        Some(left)
+        Some(kind)
     }
 
     #[tracing::instrument(skip(self))]
-    fn equality(&mut self) -> Option<Expr> {
-        let mut left = self.comparison()?;
-
-        loop {
-            if let Some(right) =
-                self.parse_binary(left.clone(), &[Op::NotEqual, Op::EqualEqual], |p| {
-                    p.comparison()
-                })
-            {
-                left = right;
-                continue;
-            }
-            break;
-        }
-        Some(left)
+    fn equality(&mut self) -> Option<SyntaxKind> {
+        self.parse_binary(&[Op::NotEqual, Op::EqualEqual], |p| p.comparison())
     }
 
     #[tracing::instrument(skip(self))]
-    fn comparison(&mut self) -> Option<Expr> {
-        let mut left = self.term()?;
-
-        loop {
-            if let Some(right) = self.parse_binary(
-                left.clone(),
-                &[Op::Greater, Op::GreaterEqual, Op::Less, Op::LessEqual],
-                |p| p.term(),
-            ) {
-                left = right;
-                continue;
-            }
-            break;
-        }
-        Some(left)
+    fn comparison(&mut self) -> Option<SyntaxKind> {
+        self.parse_binary(
+            &[Op::Greater, Op::GreaterEqual, Op::Less, Op::LessEqual],
+            |p| p.term(),
+        )
     }
 
     #[tracing::instrument(skip(self))]
-    fn term(&mut self) -> Option<Expr> {
-        let mut left = self.factor()?;
-
-        loop {
-            if let Some(right) =
-                self.parse_binary(left.clone(), &[Op::Minus, Op::Plus], |p| p.factor())
-            {
-                left = right;
-                continue;
-            }
-            break;
-        }
-        Some(left)
+    fn term(&mut self) -> Option<SyntaxKind> {
+        self.parse_binary(&[Op::Minus, Op::Plus], |p| p.factor())
     }
 
     #[tracing::instrument(skip(self))]
-    fn factor(&mut self) -> Option<Expr> {
-        let mut left = self.unary()?;
-
-        loop {
-            if let Some(right) =
-                self.parse_binary(left.clone(), &[Op::Star, Op::Slash], |p| p.unary())
-            {
-                left = right;
-                continue;
-            }
-            break;
-        }
-        Some(left)
+    fn factor(&mut self) -> Option<SyntaxKind> {
+        self.parse_binary(&[Op::Star, Op::Slash], |p| p.unary())
     }
 
+    // operand ( op operand )* for the operators `ops`, which have the same precedence
     fn parse_binary(
         &mut self,
-        left: Expr,
         ops: &[Op],
-        parse_rhs: impl Fn(&mut Self) -> Option<Expr>,
-    ) -> Option<Expr> {
-        for op in ops {
-            if self.eat_op(*op).is_some() {
-                let right = parse_rhs(self)?;
-                let left = Expr::BinaryOp(Box::new(left), *op, Box::new(right));
-                return Some(left);
-            }
+        parse_operand: impl Fn(&mut Self) -> Option<SyntaxKind>,
+    ) -> Option<SyntaxKind> {
+        let checkpoint = self.checkpoint();
+        let mut kind = parse_operand(self)?;
+        while ops.iter().any(|op| self.eat_op(*op).is_some()) {
+            parse_operand(self)?;
+            self.finish_node(checkpoint, SyntaxKind::BinaryExpr);
+            kind = SyntaxKind::BinaryExpr;
         }
-        None
+        Some(kind)
     }
 
     #[tracing::instrument(skip(self))]
-    fn unary(&mut self) -> Option<Expr> {
+    fn unary(&mut self) -> Option<SyntaxKind> {
+        let checkpoint = self.checkpoint();
         for op in &[Op::Minus, Op::Bang] {
             if self.eat_op(*op).is_some() {
-                let expr = self.unary()?;
-                return Some(Expr::UnaryOp(*op, Box::new(expr)));
+                self.unary()?;
+                self.finish_node(checkpoint, SyntaxKind::UnaryExpr);
+                return Some(SyntaxKind::UnaryExpr);
             }
         }
         self.call()
     }
 
+    // call           -> primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
     #[tracing::instrument(skip(self))]
-    fn call(&mut self) -> Option<Expr> {
-        let mut expr = self.primary()?;
+    fn call(&mut self) -> Option<SyntaxKind> {
+        let checkpoint = self.checkpoint();
+        let mut kind = self.primary()?;
         loop {
             if let Some((_, token_tree)) = self.delimited('(') {
-                let mut parser = Parser::new(self.db, token_tree);
-                let mut args = vec![];
-                while parser.tokens.peek().is_some() {
-                    let arg = parser.parse_expr()?;
-                    args.push(arg);
-                    if parser.eat(Token::Comma).is_none() {
-                        break;
-                    }
+                self.parse_tree(token_tree, |parser| parser.arguments());
+                kind = SyntaxKind::CallExpr;
+            } else if let Some((dot_span, _)) = self.eat(Token::Dot) {
+                if self.eat(Identifier).is_none() {
+                    self.error(dot_span, "expected a property name after `.`")
+                        .emit(self.db);
+                    return None;
                 }
-                parser.eat(Token::Delimiter(')'));
-                expr = Expr::Call {
-                    callee: Box::new(expr),
-                    arguments: args,
-                };
-                continue;
+                kind = SyntaxKind::FieldExpr;
+            } else {
+                break;
+            }
+            self.finish_node(checkpoint, kind);
+        }
+        Some(kind)
+    }
+
+    // arguments      -> argument ( "," argument )* ;
+    // argument       -> ( IDENTIFIER ":" )? expression ;
+    fn arguments(&mut self) {
+        let mut named = false;
+        while self.tokens.peek().is_some() {
+            let checkpoint = self.checkpoint();
+            let start = self.tokens.peek_span();
+            let named_argument = self.named_argument_ahead();
+            if named_argument {
+                self.eat(Identifier);
+                self.eat(Token::Colon);
+            }
+            if self.parse_expr().is_none() {
+                // the error is reported, skip to the next argument
+                while self.peek(Token::Comma).is_none() && self.tokens.consume().is_some() {}
+                self.finish_node(checkpoint, SyntaxKind::ErrorExpr);
+            } else if named_argument {
+                named = true;
+                self.finish_node(checkpoint, SyntaxKind::NamedArg);
+            } else if named {
+                let span = start.to(self.tokens.last_span());
+                self.error(
+                    span,
+                    "positional arguments must come before named arguments",
+                )
+                .emit(self.db);
+            }
+            if self.tokens.peek().is_some()
+                && self
+                    .eat(Token::Comma)
+                    .or_report_error(self, || "expected `,` between arguments")
+                    .is_none()
+            {
+                break;
             }
-            break;
         }
-        Some(expr)
+    }
+
+    // Whether the next tokens start a named argument, like `name: value`.
+    fn named_argument_ahead(&mut self) -> bool {
+        if self.peek(Identifier).is_none() {
+            return false;
+        }
+        let mut tokens = self.tokens;
+        tokens.consume();
+        tokens.peek() == Some(Token::Colon)
     }
 
     #[tracing::instrument(skip(self))]
-    fn primary(&mut self) -> Option<Expr> {
-        if self.eat(Keyword::True).is_some() {
-            Some(Expr::BooleanLiteral(true))
-        } else if self.eat(Keyword::False).is_some() {
-            Some(Expr::BooleanLiteral(false))
-        } else if self.eat(Keyword::Nil).is_some() {
-            Some(Expr::NilLiteral)
-        } else if let Some((_, word)) = self.eat(Identifier) {
-            Some(Expr::Variable(word))
-        } else if let Some((_, word)) = self.eat(Number) {
-            Some(Expr::NumberLiteral(word))
-        } else if let Some((_, word)) = self.eat(StringLiteral) {
-            Some(Expr::StringLiteral(word))
-        } else if let Some((_, token_tree)) = self.delimited('(') {
-            let expr = Parser::new(self.db, token_tree).parse_expr()?;
-            self.eat(Token::Delimiter(')'));
-            Some(Expr::Parenthesized(Box::new(expr)))
+    fn primary(&mut self) -> Option<SyntaxKind> {
+        let checkpoint = self.checkpoint();
+        let kind = if self.eat(Keyword::True).is_some()
+            || self.eat(Keyword::False).is_some()
+            || self.eat(Keyword::Nil).is_some()
+            || self.eat(Number).is_some()
+            || self.eat(StringLiteral).is_some()
+        {
+            SyntaxKind::Literal
+        } else if self.eat(Identifier).is_some() {
+            SyntaxKind::NameRef
+        } else if let Some((span, token_tree)) = self.eat(InterpolatedString) {
+            self.parse_tree(token_tree, |parser| parser.interpolation(span));
+            SyntaxKind::InterpolatedString
+        } else if self.eat(Keyword::Fun).is_some() {
+            self.lambda(false)?;
+            SyntaxKind::Lambda
+        } else if self.arrow_ahead() {
+            self.lambda(true)?;
+            SyntaxKind::Lambda
+        } else if let Some((span, token_tree)) = self.delimited('(') {
+            self.tree_expr(span, token_tree);
+            SyntaxKind::ParenExpr
         } else {
-            None
+            // the lexer already reported the characters it does not know
+            if !matches!(self.tokens.peek(), Some(Token::Unknown(_))) {
+                let span = self.tokens.peek_span();
+                self.error(span, "expected an expression").emit(self.db);
+            }
+            return None;
+        };
+        self.finish_node(checkpoint, kind);
+        Some(kind)
+    }
+
+    // Parses the expression that fills `token_tree`, like the condition of an `if` or the
+    // contents of `(...)`. The errors are contained in the tree: an expression that fails to
+    // parse becomes an error node, which stands for the code at `span`, so that the enclosing
+    // statement still parses.
+    fn tree_expr(&mut self, span: Span, token_tree: TokenTree) {
+        self.parse_tree(token_tree, |parser| {
+            if parser.tokens.peek().is_none() {
+                parser.error(span, "expected an expression").emit(parser.db);
+                return;
+            }
+            let checkpoint = parser.checkpoint();
+            if parser.parse_expr().is_none() {
+                parser.finish_node(checkpoint, SyntaxKind::ErrorExpr);
+                return;
+            }
+            parser.expect_end();
+        })
+    }
+
+    // Reports the tokens left in a token tree once its contents are parsed.
+    fn expect_end(&mut self) {
+        if self.tokens.peek().is_some() {
+            let span = self.tokens.peek_span();
+            self.error(span, "unexpected token after the expression")
+                .emit(self.db);
         }
     }
 
+    // Parses the pieces of text and the interpolated expressions of the string literal at
+    // `span`, see `Token::Interpolation`.
+    fn interpolation(&mut self, span: Span) {
+        loop {
+            if let Some((_, expr_tree)) = self.eat(AnyTree) {
+                self.tree_expr(span, expr_tree);
+            } else if self.eat(StringLiteral).is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Parses the contents of `token_tree` with a new parser, and keeps the tree it builds
+    /// for when the parser records `token_tree`.
+    fn parse_tree<R>(
+        &mut self,
+        token_tree: TokenTree,
+        parse: impl FnOnce(&mut Parser<'me>) -> R,
+    ) -> R {
+        let mut parser = Parser::new(self.db, token_tree);
+        let result = parse(&mut parser);
+        self.builder.add_subtree(token_tree, parser.finish());
+        result
+    }
+
+    /// Marks the start of a node at the next pending token, see `Builder::checkpoint`.
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.builder.checkpoint(&self.tokens)
+    }
+
+    /// Creates a node of kind `kind` from `checkpoint` to the last token consumed.
+    fn finish_node(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        self.builder.finish_node_at(checkpoint, kind, &self.tokens);
+    }
+
     /// Returns `Some` if the next pending token matches `is`, along
     /// with the narrowed view of the next token.
     fn peek<TT: TokenTest>(&mut self, test: TT) -> Option<TT::Narrow> {
@@ -447,7 +635,11 @@ impl<'me> Parser<'me> {
     /// Peek ahead to see if `op` matches the next set of tokens;
     /// if so, return the span and the tokens after skipping the operator.
     fn test_op(&self, op: Op) -> Option<(Span, Tokens<'me>)> {
-        let mut tokens = self.tokens;
+        Self::test_op_in(self.tokens, op)
+    }
+
+    /// Like `test_op`, but looks at `tokens` in place of the pending tokens.
+    fn test_op_in(mut tokens: Tokens<'me>, op: Op) -> Option<(Span, Tokens<'me>)> {
         let span0 = tokens.peek_span();
 
         let mut chars = op.str().chars();
@@ -502,7 +694,7 @@ impl<'me> Parser<'me> {
     /// If the next token is an opening delimiter, like `(` or `{`,
     /// then consumes it, the token-tree that follows, and the closing delimiter (if present).
     /// Returns the token tree + the span including delimiters.
-    /// Reports an error if there is no closing delimiter.
+    /// A missing closing delimiter is reported by the lexer.
     fn delimited(&mut self, delimiter: char) -> Option<(Span, TokenTree)> {
         let (open_span, _) = self.eat(Token::Delimiter(delimiter))?;
 
@@ -511,12 +703,7 @@ impl<'me> Parser<'me> {
 
         // Consume closing delimiter (if present)
         let closing_delimiter = lox_lex::closing_delimiter(delimiter);
-        self.eat(Token::Delimiter(closing_delimiter)).or_else(|| {
-            let span = self.tokens.peek_span();
-            let message = format!("expected `{}`", closing_delimiter);
-            self.error(span, message).emit(self.db);
-            None
-        });
+        self.eat(Token::Delimiter(closing_delimiter));
 
         let span = open_span.to(self.tokens.last_span());
         Some((span, token_tree))
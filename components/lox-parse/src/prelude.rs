@@ -1,14 +1,29 @@
-use lox_ir::syntax::Stmt;
-
-use crate::parser::Parser;
+use lox_ir::{
+    span::FileSpan,
+    syntax::{Expr, Stmt},
+};
 
 pub trait FunctionParseExt {
-    fn parse(&self, db: &dyn crate::Db) -> Vec<Stmt>;
+    fn parse<'db>(&self, db: &'db dyn crate::Db) -> &'db Vec<Stmt>;
+
+    /// The default values of the parameters, `None` for the parameters without one.
+    fn defaults<'db>(&self, db: &'db dyn crate::Db) -> &'db Vec<Option<Expr>>;
+
+    /// The span of the parameter list, parentheses included, if it parsed without errors.
+    /// `None` for the main function of a file.
+    fn params_span(&self, db: &dyn crate::Db) -> Option<FileSpan>;
 }
 
 impl FunctionParseExt for lox_ir::function::Function {
-    fn parse(&self, db: &dyn crate::Db) -> Vec<Stmt> {
-        let mut parser = Parser::new(db, self.body(db));
-        parser.parse()
+    fn parse<'db>(&self, db: &'db dyn crate::Db) -> &'db Vec<Stmt> {
+        crate::file_parser::parse_function(db, *self)
+    }
+
+    fn defaults<'db>(&self, db: &'db dyn crate::Db) -> &'db Vec<Option<Expr>> {
+        crate::file_parser::parse_defaults(db, *self)
+    }
+
+    fn params_span(&self, db: &dyn crate::Db) -> Option<FileSpan> {
+        crate::file_parser::parameters_span(db, *self)
     }
 }
@@ -0,0 +1,21 @@
+use lox_ir::{input_file::InputFile, word::Word};
+
+// The database of the unit tests, with the jars the parser needs.
+#[salsa::db(crate::Jar, lox_ir::Jar, lox_lex::Jar)]
+#[derive(Default)]
+pub(crate) struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+impl lox_ir::Db for Database {}
+
+impl lox_lex::Db for Database {}
+
+impl Database {
+    pub(crate) fn new_input_file(&self, name: impl ToString, source_text: String) -> InputFile {
+        let name = Word::intern(self, name);
+        InputFile::new(self, name, source_text)
+    }
+}
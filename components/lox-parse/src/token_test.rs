@@ -1,5 +1,6 @@
 use lox_ir::{kw::Keyword, span::FileSpan, token::Token, token_tree::TokenTree, word::Word};
 use std::fmt::Debug;
+use unicode_normalization::UnicodeNormalization;
 
 /// Represents some kind of "condition test" that can be applied to a single token
 /// (e.g., is an identifier or is a keyword).
@@ -65,7 +66,21 @@ impl TokenTest for StringLiteral {
 
     fn test(self, _db: &dyn crate::Db, token: Token, _span: FileSpan) -> Option<Word> {
         match token {
-            Token::String(w) => Some(w),
+            Token::String(w, _) => Some(w),
+            _ => None,
+        }
+    }
+}
+
+/// A string literal with interpolations, see [`Token::Interpolation`]
+#[derive(Debug)]
+pub(crate) struct InterpolatedString;
+impl TokenTest for InterpolatedString {
+    type Narrow = TokenTree;
+
+    fn test(self, _db: &dyn crate::Db, token: Token, _span: FileSpan) -> Option<TokenTree> {
+        match token {
+            Token::Interpolation(tree) => Some(tree),
             _ => None,
         }
     }
@@ -82,7 +97,8 @@ impl TokenTest for AnyTree {
     }
 }
 
-/// An `Alphabetic` that is not a keyword
+/// An `Alphabetic` that is not a keyword, in Unicode normalization form C, so that `é`
+/// written as one or as two code points is the same identifier
 #[derive(Debug)]
 pub(crate) struct Identifier;
 impl TokenTest for Identifier {
@@ -91,9 +107,13 @@ impl TokenTest for Identifier {
     fn test(self, db: &dyn crate::Db, token: Token, _span: FileSpan) -> Option<Word> {
         let word = token.alphabetic()?;
         if lox_ir::kw::keywords(db).contains_key(&word) {
-            None
-        } else {
+            return None;
+        }
+        let text = word.as_str(db);
+        if unicode_normalization::is_nfc(text) {
             Some(word)
+        } else {
+            Some(Word::intern(db, text.nfc().collect::<String>()))
         }
     }
 }
@@ -102,19 +122,10 @@ impl TokenTest for Identifier {
 mod tests {
     use lox_ir::{input_file::InputFile, kw::Keyword, span::FileSpan, token::Token, word::Word};
 
-    use crate::token_test::TokenTest;
-
-    #[salsa::db(crate::Jar, lox_ir::Jar, lox_lex::Jar)]
-    #[derive(Default)]
-    struct Database {
-        storage: salsa::Storage<Self>,
-    }
-
-    impl salsa::Database for Database {}
-
-    impl lox_ir::Db for Database {}
-
-    impl lox_lex::Db for Database {}
+    use crate::{
+        test_db::Database,
+        token_test::{Identifier, TokenTest},
+    };
 
     fn fake_file_span(db: &dyn crate::Db) -> FileSpan {
         let fake_file = InputFile::new(db, Word::intern(db, "foo"), "foo".to_string());
@@ -141,4 +152,20 @@ mod tests {
         let token = Token::Alphabetic(Word::intern(db, "foo"));
         assert_eq!(Keyword::True.test(db, token, file_span), None);
     }
+
+    #[test]
+    fn token_test_for_identifier() {
+        let db = &mut Database::default();
+        let file_span = fake_file_span(db);
+
+        // `e` followed by a combining acute accent is normalized to `é`
+        let token = Token::Alphabetic(Word::intern(db, "caf\u{65}\u{301}"));
+        assert_eq!(
+            Identifier.test(db, token, file_span),
+            Some(Word::intern(db, "caf\u{e9}"))
+        );
+
+        let token = Token::Alphabetic(Word::intern(db, "nil"));
+        assert_eq!(Identifier.test(db, token, file_span), None);
+    }
 }
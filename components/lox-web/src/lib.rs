@@ -1,8 +1,27 @@
-use lox_execute::kernel::BufferKernel;
+use lox_execute::{
+    kernel::{BufferKernel, Capabilities, Capability},
+    ExecutionLimits,
+};
 use lox_ir::{diagnostic::Diagnostics, input_file::InputFile};
 use salsa::DebugWithDb;
 use wasm_bindgen::prelude::*;
 
+// The code runs on the main thread of the page, so it must not be able to run forever.
+// There is no clock in wasm, the running time is bounded by the number of instructions instead.
+const EXECUTION_LIMITS: ExecutionLimits = ExecutionLimits {
+    max_instructions: Some(100_000_000),
+    max_call_depth: Some(1_000),
+    max_heap_objects: Some(1_000_000),
+    timeout: None,
+};
+
+// The page has no stdin, files or environment, and `eprint` output is shown with the rest.
+fn capabilities() -> Capabilities {
+    Capabilities::none()
+        .with(Capability::WriteStderr)
+        .with(Capability::Exit)
+}
+
 #[wasm_bindgen]
 pub struct Compiler {
     db: lox_db::Database,
@@ -38,15 +57,17 @@ impl Compiler {
         if !diagnostics.is_empty() {
             lox_error_format::format_diagnostics(&self.db, &diagnostics).unwrap()
         } else {
-            let mut kernel = BufferKernel::new();
-            lox_execute::execute_file(
+            let mut kernel = BufferKernel::new().with_capabilities(capabilities());
+            lox_execute::execute_file_with_limits(
                 &self.db,
                 self.input_file,
                 &mut kernel,
                 true,
-                None::<fn(_, &lox_execute::VM)>,
+                EXECUTION_LIMITS,
+                &mut (),
             );
-            kernel.take_buffer()
+            let stderr = kernel.stderr().to_string();
+            kernel.take_buffer() + &stderr
         }
     }
 
@@ -0,0 +1,207 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use lox_db::Database;
+use lox_execute::kernel::BufferKernel;
+use lox_ir::{diagnostic::Diagnostics, input_file::InputFile, word::Word};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+/// The results of a benchmark run, keyed by the path of the benchmark program
+/// relative to the benchmark directory. This is also the format of baseline files.
+#[derive(Serialize, Deserialize, Default)]
+pub struct BenchResults {
+    benchmarks: BTreeMap<String, BenchResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct BenchResult {
+    mean_ms: f64,
+    median_ms: f64,
+    instructions: u64,
+}
+
+/// Runs every `.lox` file under `path` (or `path` itself if it is a file) `iterations` times,
+/// and reports the mean and median wall time of the runs along with the number of
+/// instructions executed by the VM. The compile errors of a benchmark are printed, and the other
+/// benchmarks still run.
+pub fn run(db: &Database, path: &Path, iterations: usize) -> Result<BenchResults, String> {
+    let mut results = BenchResults::default();
+    println!(
+        "{:<30} {:>12} {:>12} {:>14}",
+        "benchmark", "mean", "median", "instructions"
+    );
+    for lox in list(path)? {
+        let name = lox
+            .strip_prefix(path)
+            .ok()
+            .filter(|name| !name.as_os_str().is_empty())
+            .unwrap_or(lox.as_path())
+            .display()
+            .to_string();
+        let Some(result) = bench_file(db, &lox, iterations)? else {
+            println!("{:<30} failed to compile", name);
+            continue;
+        };
+        println!(
+            "{:<30} {:>10.3}ms {:>10.3}ms {:>14}",
+            name, result.mean_ms, result.median_ms, result.instructions
+        );
+        results.benchmarks.insert(name, result);
+    }
+    Ok(results)
+}
+
+fn list(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut res = vec![];
+    for entry in WalkDir::new(path) {
+        let entry = entry.map_err(|e| format!("failed to list {}: {}", path.display(), e))?;
+        if entry.path().extension().unwrap_or_default() == "lox" {
+            res.push(entry.into_path());
+        }
+    }
+    res.sort();
+    Ok(res)
+}
+
+// Returns `None` if the benchmark does not compile, after printing its diagnostics.
+fn bench_file(db: &Database, lox: &Path, iterations: usize) -> Result<Option<BenchResult>, String> {
+    let source_text =
+        fs::read_to_string(lox).map_err(|e| format!("failed to read {}: {}", lox.display(), e))?;
+    let input_file = InputFile::new(db, Word::intern(db, lox.to_string_lossy()), source_text);
+
+    // compile ahead of time, so that only the execution is measured
+    lox_compile::compile_file(db, input_file);
+    let diagnostics = lox_compile::compile_file::accumulated::<Diagnostics>(db, input_file);
+    if !diagnostics.is_empty() {
+        for diagnostic in &diagnostics {
+            lox_error_format::print_diagnostic(db, diagnostic).unwrap();
+        }
+        return Ok(None);
+    }
+
+    let mut durations = Vec::with_capacity(iterations);
+    let mut instructions = 0;
+    for _ in 0..iterations.max(1) {
+        // the output is discarded, printing it would only measure the terminal
+        let mut kernel = BufferKernel::new();
+        let start = Instant::now();
+        let vm = lox_execute::execute_file(db, input_file, &mut kernel, false, &mut ());
+        durations.push(start.elapsed());
+        instructions = vm.instruction_count();
+    }
+
+    durations.sort();
+    let total: Duration = durations.iter().sum();
+    let mean = total / durations.len() as u32;
+    let median = durations[durations.len() / 2];
+    Ok(Some(BenchResult {
+        mean_ms: mean.as_secs_f64() * 1000.0,
+        median_ms: median.as_secs_f64() * 1000.0,
+        instructions,
+    }))
+}
+
+impl BenchResults {
+    pub fn load(path: &Path) -> Result<BenchResults, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("failed to parse {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).unwrap();
+        fs::write(path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+
+    /// Compares the results against `baseline` and prints the changes.
+    /// A benchmark regresses if its median time grows by more than `threshold` percent.
+    /// Returns the number of regressions.
+    pub fn compare(&self, baseline: &BenchResults, threshold: f64) -> usize {
+        println!();
+        println!(
+            "{:<30} {:>12} {:>12} {:>14}",
+            "benchmark", "baseline", "median", "change"
+        );
+        let mut regressions = 0;
+        for (name, result) in &self.benchmarks {
+            let Some(base) = baseline.benchmarks.get(name) else {
+                println!("{:<30} not in baseline", name);
+                continue;
+            };
+            let change = (result.median_ms - base.median_ms) / base.median_ms * 100.0;
+            let regressed = change > threshold;
+            if regressed {
+                regressions += 1;
+            }
+            println!(
+                "{:<30} {:>10.3}ms {:>10.3}ms {:>+13.1}%{}",
+                name,
+                base.median_ms,
+                result.median_ms,
+                change,
+                if regressed { "  REGRESSION" } else { "" }
+            );
+            if result.instructions != base.instructions {
+                println!(
+                    "{:<30} instructions changed from {} to {}",
+                    "", base.instructions, result.instructions
+                );
+            }
+        }
+        regressions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BenchResult, BenchResults};
+
+    fn results(medians: &[(&str, f64)]) -> BenchResults {
+        let mut results = BenchResults::default();
+        for &(name, median_ms) in medians {
+            let result = BenchResult {
+                mean_ms: median_ms,
+                median_ms,
+                instructions: 100,
+            };
+            results.benchmarks.insert(name.to_string(), result);
+        }
+        results
+    }
+
+    #[test]
+    fn compare_against_baseline() {
+        let baseline = results(&[("fib.lox", 10.0), ("loop.lox", 10.0), ("sort.lox", 10.0)]);
+
+        // `fib.lox` is 5% slower, under the threshold, `loop.lox` is 50% slower, and `new.lox`
+        // has no baseline to compare against
+        let current = results(&[("fib.lox", 10.5), ("loop.lox", 15.0), ("new.lox", 1.0)]);
+        assert_eq!(current.compare(&baseline, 10.0), 1);
+        assert_eq!(current.compare(&baseline, 60.0), 0);
+        assert_eq!(baseline.compare(&current, 10.0), 0);
+    }
+
+    #[test]
+    fn baseline_files() {
+        let path = std::env::temp_dir().join("lox_bench_baseline.json");
+        let saved = results(&[("fib.lox", 10.0)]);
+        saved.save(&path).unwrap();
+        let loaded = BenchResults::load(&path).unwrap();
+        assert_eq!(loaded.compare(&saved, 0.0), 0);
+        assert_eq!(loaded.benchmarks["fib.lox"].median_ms, 10.0);
+
+        std::fs::write(&path, "not json").unwrap();
+        let error = BenchResults::load(&path).err().unwrap();
+        assert!(error.starts_with("failed to parse"), "{}", error);
+
+        let missing = std::env::temp_dir().join("lox_bench_missing.json");
+        let error = BenchResults::load(&missing).err().unwrap();
+        assert!(error.starts_with("failed to read"), "{}", error);
+    }
+}
@@ -0,0 +1,239 @@
+use std::path::{Path, PathBuf};
+
+use lox_db::Database;
+use lox_ir::{
+    bytecode::Code,
+    diagnostic::{Diagnostic, Diagnostics},
+    function::Function,
+    input_file::InputFile,
+};
+use walkdir::WalkDir;
+
+/// How `lox check` prints the diagnostics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// rendered for humans, with the source code they point to
+    Text,
+
+    /// a JSON array of diagnostics
+    Json,
+
+    /// a SARIF 2.1.0 log, for code scanning dashboards
+    Sarif,
+}
+
+/// Lists the lox files at `path`, which is either a lox file or a directory of them.
+pub fn lox_files(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_owned()];
+    }
+    let mut files: Vec<PathBuf> = WalkDir::new(path)
+        .into_iter()
+        .map(|entry| entry.unwrap().into_path())
+        .filter(|path| path.extension().unwrap_or_default() == "lox")
+        .collect();
+    files.sort();
+    files
+}
+
+/// Returns the compile errors of `input_file`, including the ones in the bodies of its
+/// functions, which are only compiled when they are called otherwise.
+pub fn check_file(db: &Database, input_file: InputFile) -> Vec<Diagnostic> {
+    let main = lox_compile::compile_file(db, input_file);
+    let mut diagnostics = lox_compile::compile_file::accumulated::<Diagnostics>(db, input_file);
+    for ip in 0..main.chunk.len() {
+        if let Code::Function(function) = main.chunk.read_byte(ip) {
+            check_function(db, function, &mut diagnostics);
+        }
+    }
+    diagnostics
+}
+
+fn check_function(db: &Database, function: Function, diagnostics: &mut Vec<Diagnostic>) {
+    let compiled_function = lox_compile::compile_fn(db, function);
+
+    // the bodies of functions are parsed when resolving the global variables of the file,
+    // so their syntax errors are also accumulated by the compilation of the file
+    for diagnostic in lox_compile::compile_fn::accumulated::<Diagnostics>(db, function) {
+        if !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    for ip in 0..compiled_function.chunk.len() {
+        if let Code::Function(function) = compiled_function.chunk.read_byte(ip) {
+            check_function(db, function, diagnostics);
+        }
+    }
+}
+
+pub fn format_diagnostics(db: &Database, diagnostics: &[Diagnostic], format: Format) -> String {
+    match format {
+        Format::Text => lox_error_format::format_diagnostics(db, diagnostics).unwrap(),
+        Format::Json => lox_error_format::format_diagnostics_json(db, diagnostics) + "\n",
+        Format::Sarif => lox_error_format::format_diagnostics_sarif(db, diagnostics) + "\n",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_db::Database;
+    use serde_json::{json, Value};
+
+    use super::{check_file, format_diagnostics, Format};
+
+    #[test]
+    fn json_and_sarif() {
+        let db = Database::default();
+        let input_file = db.new_input_file(
+            "test.lox",
+            "print 1;\nfun f() {\n    1 = 2;\n}\n".to_string(),
+        );
+
+        // the error is in the body of a function that is never called
+        let diagnostics = check_file(&db, input_file);
+        assert_eq!(diagnostics.len(), 1);
+
+        let json: Value =
+            serde_json::from_str(&format_diagnostics(&db, &diagnostics, Format::Json)).unwrap();
+        let range = json!({
+            "start": { "line": 3, "column": 5, "utf16Column": 5 },
+            "end": { "line": 3, "column": 6, "utf16Column": 6 },
+        });
+        assert_eq!(
+            json,
+            json!([{
+                "severity": "error",
+                "message": "invalid assignment target",
+                "file": "test.lox",
+                "range": range,
+                "labels": [{ "message": "here", "file": "test.lox", "range": range }],
+                "children": [],
+            }])
+        );
+
+        let sarif: Value =
+            serde_json::from_str(&format_diagnostics(&db, &diagnostics, Format::Sarif)).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["level"], "error");
+        assert_eq!(result["message"]["text"], "invalid assignment target");
+        assert_eq!(
+            result["locations"][0]["physicalLocation"],
+            json!({
+                "artifactLocation": { "uri": "test.lox" },
+                "region": { "startLine": 3, "startColumn": 5, "endLine": 3, "endColumn": 6 },
+            })
+        );
+    }
+
+    #[test]
+    fn reports_every_syntax_error() {
+        let db = Database::default();
+        let input_file = db.new_input_file(
+            "test.lox",
+            "var = 1;\nprint 1 +;\nif (1 2) print 3;\nfun f(a b) {}\nf(1 2);\nprint 4\nprint 5;\n"
+                .to_string(),
+        );
+
+        let diagnostics = check_file(&db, input_file);
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "expected a variable name after `var`",
+                "expected an expression",
+                "unexpected token after the expression",
+                "expected `,` between parameters",
+                "expected `,` between arguments",
+                "expected `;`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_calls_that_would_fail() {
+        let db = Database::default();
+        let input_file = db.new_input_file(
+            "test.lox",
+            "\
+fun hello() {}
+fun add(a, b = 1) { return a + b; }
+var f = add;
+hello(1, 2);
+add();
+add(1, c: 2);
+\"str\"();
+print fun (x) { return x; }(1, 2);
+f(1, 2, 3);
+fun g() {}
+g = add;
+g(1);
+"
+            .to_string(),
+        );
+
+        // `f` and `g` may hold any value, their calls are checked when they run
+        let diagnostics = check_file(&db, input_file);
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "expected 0 arguments but got 2",
+                "missing an argument for `a`",
+                "there is no parameter named `c`",
+                "can only call functions",
+                "expected 1 argument but got 2",
+            ]
+        );
+
+        let json: Value =
+            serde_json::from_str(&format_diagnostics(&db, &diagnostics[..1], Format::Json))
+                .unwrap();
+        assert_eq!(
+            json[0]["labels"],
+            json!([
+                {
+                    "message": "`hello` is called with the wrong number of arguments",
+                    "file": "test.lox",
+                    "range": {
+                        "start": { "line": 4, "column": 1, "utf16Column": 1 },
+                        "end": { "line": 4, "column": 12, "utf16Column": 12 },
+                    },
+                },
+                {
+                    "message": "the parameters of `hello`",
+                    "file": "test.lox",
+                    "range": {
+                        "start": { "line": 1, "column": 10, "utf16Column": 10 },
+                        "end": { "line": 1, "column": 12, "utf16Column": 12 },
+                    },
+                },
+            ])
+        );
+    }
+
+    // every call looks up the parameters of `add`, which must not walk the whole file each time
+    #[test]
+    fn checks_many_calls_to_one_function() {
+        let db = Database::default();
+        let mut source_text = "fun add(a, b = 1) { return a + b; }\n".to_string();
+        for _ in 0..20_000 {
+            source_text.push_str("add(1);\n");
+        }
+        source_text.push_str("add();");
+        let input_file = db.new_input_file("test.lox", source_text);
+
+        let diagnostics = check_file(&db, input_file);
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(messages, ["missing an argument for `a`"]);
+    }
+}
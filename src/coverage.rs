@@ -0,0 +1,287 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use lox_db::Database;
+use lox_execute::{kernel::Kernel, ExecutionLimits, Observer, Value, VM};
+use lox_ir::{
+    bytecode::{Code, CompiledFunction},
+    input_file::InputFile,
+    line_index::{line_index, LineIndex},
+    span::{FileSpan, Offset},
+};
+
+/// The lines and branches executed by lox programs, collected over any number of runs.
+#[derive(Default)]
+pub struct Coverage {
+    // keyed by the name of the input file
+    files: BTreeMap<String, FileCoverage>,
+}
+
+#[derive(Default)]
+struct FileCoverage {
+    // how many times the execution entered each 1-based line that has instructions
+    lines: BTreeMap<usize, u64>,
+
+    // every `JumpIfFalse` instruction of the file
+    branches: BTreeMap<BranchKey, Branch>,
+}
+
+// The span of a `JumpIfFalse` instruction, which orders the branches as in the source, and the
+// name of its function and its offset in the chunk, as the same span can be compiled into
+// several functions.
+type BranchKey = (Offset, Offset, String, usize);
+
+struct Branch {
+    // 1-based line of the instruction
+    line: usize,
+
+    // how many times the condition was true, and how many times it was false and jumped
+    outcomes: [u64; 2],
+}
+
+impl Coverage {
+    /// Runs `input_file` like [`lox_execute::execute_file_with_limits`], and records which lines
+    /// and branches of the file are executed.
+    pub fn run_file(
+        &mut self,
+        db: &Database,
+        input_file: InputFile,
+        kernel: &mut impl Kernel,
+        diagnostic_with_color: bool,
+        limits: ExecutionLimits,
+    ) {
+        let line_index = line_index(db, input_file);
+        let file = self
+            .files
+            .entry(input_file.name(db).as_str(db).to_string())
+            .or_default();
+
+        // every line and branch of the file is listed, even if it never runs
+        file.register(db, line_index, &lox_compile::compile_file(db, input_file));
+
+        let mut observer = CoverageObserver {
+            file,
+            line_index,
+            previous: None,
+            depth: 0,
+            span: None,
+            ip: None,
+            condition_is_false: false,
+        };
+        lox_execute::execute_file_with_limits(
+            db,
+            input_file,
+            kernel,
+            diagnostic_with_color,
+            limits,
+            &mut observer,
+        );
+    }
+
+    /// Renders the coverage in the lcov tracefile format.
+    pub fn lcov(&self) -> String {
+        let mut lcov = String::new();
+        for (name, file) in &self.files {
+            writeln!(lcov, "TN:\nSF:{}", name).unwrap();
+
+            // the branches of a line are numbered in the order they appear in the source
+            let mut block = 0;
+            let mut previous_line = 0;
+            for branch in file.branches.values() {
+                if branch.line != previous_line {
+                    block = 0;
+                    previous_line = branch.line;
+                }
+                for (index, outcome) in branch.outcomes.iter().enumerate() {
+                    // `-` means the branch was never reached
+                    let taken = if branch.outcomes == [0, 0] {
+                        "-".to_string()
+                    } else {
+                        outcome.to_string()
+                    };
+                    writeln!(lcov, "BRDA:{},{},{},{}", branch.line, block, index, taken).unwrap();
+                }
+                block += 1;
+            }
+            let (branches, branches_hit) = file.branch_counts();
+            writeln!(lcov, "BRF:{}\nBRH:{}", branches, branches_hit).unwrap();
+
+            for (line, hits) in &file.lines {
+                writeln!(lcov, "DA:{},{}", line, hits).unwrap();
+            }
+            let (lines, lines_hit) = file.line_counts();
+            writeln!(lcov, "LF:{}\nLH:{}", lines, lines_hit).unwrap();
+            writeln!(lcov, "end_of_record").unwrap();
+        }
+        lcov
+    }
+
+    /// Renders how many lines and branches of each file were executed.
+    pub fn summary(&self) -> String {
+        let percent = |hit: usize, total: usize| {
+            if total == 0 {
+                100.0
+            } else {
+                hit as f64 * 100.0 / total as f64
+            }
+        };
+        let mut summary = String::new();
+        writeln!(summary, "{:<40} {:>16} {:>16}", "file", "lines", "branches").unwrap();
+        for (name, file) in &self.files {
+            let (lines, lines_hit) = file.line_counts();
+            let (branches, branches_hit) = file.branch_counts();
+            writeln!(
+                summary,
+                "{:<40} {:>6.1}% {:>3}/{:<3} {:>6.1}% {:>3}/{:<3}",
+                name,
+                percent(lines_hit, lines),
+                lines_hit,
+                lines,
+                percent(branches_hit, branches),
+                branches_hit,
+                branches
+            )
+            .unwrap();
+        }
+        summary
+    }
+}
+
+struct CoverageObserver<'a> {
+    file: &'a mut FileCoverage,
+    line_index: &'a LineIndex,
+
+    // the call depth and line of the last instruction executed
+    previous: Option<(usize, usize)>,
+
+    // the call depth, span and offset in its function of the instruction being executed
+    depth: usize,
+    span: Option<FileSpan>,
+    ip: Option<usize>,
+
+    // `JumpIfFalse` jumps if the value on top of the stack is `false`
+    condition_is_false: bool,
+}
+
+impl<K> Observer<K> for CoverageObserver<'_> {
+    fn before_step(&mut self, vm: &VM) {
+        self.depth = vm.call_depth();
+        self.span = vm.current_span();
+        self.ip = vm.frames().next().and_then(|frame| frame.ip());
+        self.condition_is_false = matches!(vm.stack_values().last(), Some(Value::Boolean(false)));
+    }
+
+    fn after_step(&mut self, vm: &VM, _: &K, instruction: Option<&Code>) -> bool {
+        let (Some(instruction), Some(span)) = (instruction, self.span) else {
+            return true;
+        };
+        let line = self.line_index.line(span.start) + 1;
+        if self.previous != Some((self.depth, line)) {
+            *self.file.lines.entry(line).or_default() += 1;
+            self.previous = Some((self.depth, line));
+        }
+        if let (Code::JumpIfFalse(_), Some(ip)) = (instruction, self.ip) {
+            // a jump stays in the function it was executed in
+            let function = vm.frames().next().unwrap().function_name().to_string();
+            let key = (span.start, span.end, function, ip);
+            if let Some(branch) = self.file.branches.get_mut(&key) {
+                branch.outcomes[self.condition_is_false as usize] += 1;
+            }
+        }
+        true
+    }
+}
+
+impl FileCoverage {
+    fn register(&mut self, db: &Database, line_index: &LineIndex, function: &CompiledFunction) {
+        let chunk = &function.chunk;
+        for ip in 0..chunk.len() {
+            let span = chunk.span(ip);
+            let line = line_index.line(span.start) + 1;
+            self.lines.entry(line).or_default();
+            match chunk.read_byte(ip) {
+                Code::JumpIfFalse(_) => {
+                    self.branches
+                        .entry((span.start, span.end, function.name.clone(), ip))
+                        .or_insert(Branch {
+                            line,
+                            outcomes: [0, 0],
+                        });
+                }
+                Code::Function(function) => {
+                    self.register(db, line_index, &lox_compile::compile_fn(db, function))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Returns the number of lines, and how many of them were executed.
+    fn line_counts(&self) -> (usize, usize) {
+        let hit = self.lines.values().filter(|hits| **hits > 0).count();
+        (self.lines.len(), hit)
+    }
+
+    // Returns the number of branch outcomes, and how many of them happened.
+    fn branch_counts(&self) -> (usize, usize) {
+        let hit = self
+            .branches
+            .values()
+            .flat_map(|branch| branch.outcomes)
+            .filter(|taken| *taken > 0)
+            .count();
+        (self.branches.len() * 2, hit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_db::Database;
+    use lox_execute::{kernel::BufferKernel, ExecutionLimits};
+
+    use super::Coverage;
+
+    #[test]
+    fn lines_and_branches() {
+        let db = Database::default();
+        let input_file = db.new_input_file(
+            "test.lox",
+            "\
+fun unused() {
+    print 0;
+}
+var a = 1;
+if (a > 0) {
+    print a;
+}
+while (a < 3) {
+    a = a + 1;
+}
+"
+            .to_string(),
+        );
+        let mut coverage = Coverage::default();
+        let mut kernel = BufferKernel::new();
+        coverage.run_file(
+            &db,
+            input_file,
+            &mut kernel,
+            false,
+            ExecutionLimits::default(),
+        );
+        assert_eq!(kernel.take_buffer(), "1\n");
+
+        let lcov = coverage.lcov();
+        assert!(lcov.starts_with("TN:\nSF:test.lox\n"), "{}", lcov);
+
+        // the body of `unused` never runs
+        assert!(lcov.contains("DA:2,0\n"), "{}", lcov);
+        assert!(lcov.contains("DA:6,1\n"), "{}", lcov);
+
+        // the condition of the `if` is only ever true, the one of the `while` is true twice
+        // and false once
+        assert!(lcov.contains("BRDA:5,0,0,1\nBRDA:5,0,1,0\n"), "{}", lcov);
+        assert!(lcov.contains("BRDA:8,0,0,2\nBRDA:8,0,1,1\n"), "{}", lcov);
+        assert!(lcov.contains("BRF:4\nBRH:3\n"), "{}", lcov);
+        assert!(lcov.ends_with("end_of_record\n"), "{}", lcov);
+    }
+}
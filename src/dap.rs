@@ -0,0 +1,835 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use lox_db::Database;
+use lox_error_format::FormatOptions;
+use lox_execute::kernel::BufferKernel;
+use lox_ir::{
+    diagnostic::Diagnostics,
+    line_index::{line_index, ColumnUnit},
+};
+use salsa::DebugWithDb;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::debug::{Breakpoint, Debugger, Resume, Stop};
+
+// the VM runs the program on a single thread
+const THREAD_ID: i64 = 1;
+
+// the variables reference of the global scope, the locals of the frame `n` use `n + 2`
+const GLOBALS_REFERENCE: i64 = 1;
+
+// the exit codes reported when the program finishes or stops because of an error
+const EXIT_SUCCESS: i64 = 0;
+const EXIT_FAILURE: i64 = 1;
+
+/// A server speaking the Debug Adapter Protocol, which lets editors debug lox programs.
+///
+/// Messages are read from and written to streams, usually stdin and stdout. The program is
+/// run synchronously between requests, so it can only stop at breakpoints or after steps,
+/// `pause` is not supported.
+pub struct DapServer<'db, W: Write> {
+    db: &'db Database,
+    output: W,
+
+    // the sequence number of the last message sent
+    seq: i64,
+
+    // the debugger of the launched program
+    debugger: Option<Debugger<'db>>,
+
+    // the program prints into this kernel, its output is forwarded as `output` events
+    kernel: BufferKernel,
+
+    // whether to stop before the first line, instead of running to the first breakpoint
+    stop_on_entry: bool,
+
+    // whether the client has sent all breakpoints, the program only starts after that
+    configuration_done: bool,
+
+    // the breakpoints are kept here with their ids and handed to the debugger whenever they
+    // change, the client may set them before the program is launched
+    line_breakpoints: Vec<LineBreakpoint>,
+    function_breakpoints: Vec<(usize, String)>,
+
+    // the id of the last breakpoint set by the client, ids are never reused so that the ones
+    // reported earlier keep pointing to the same breakpoint
+    last_breakpoint_id: usize,
+}
+
+// a breakpoint on a line of a source, only the ones in the launched program can be hit
+struct LineBreakpoint {
+    id: usize,
+    path: String,
+    line: usize,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    seq: i64,
+    command: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+impl<'db, W: Write> DapServer<'db, W> {
+    pub fn new(db: &'db Database, output: W) -> Self {
+        Self {
+            db,
+            output,
+            seq: 0,
+            debugger: None,
+            kernel: BufferKernel::new(),
+            stop_on_entry: false,
+            configuration_done: false,
+            line_breakpoints: vec![],
+            function_breakpoints: vec![],
+            last_breakpoint_id: 0,
+        }
+    }
+
+    /// Handles requests read from `input` until it ends or the client disconnects.
+    pub fn run(&mut self, mut input: impl BufRead) -> io::Result<()> {
+        while let Some(body) = read_message(&mut input)? {
+            let request: Request = match serde_json::from_slice(&body) {
+                Ok(request) => request,
+                Err(e) => {
+                    let output = format!("invalid request: {}\n", e);
+                    self.send_event("output", json!({ "category": "stderr", "output": output }))?;
+                    continue;
+                }
+            };
+            if request.command == "disconnect" {
+                return self.send_response(&request, Ok(Value::Null));
+            }
+            self.handle(request)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, request: Request) -> io::Result<()> {
+        let arguments = &request.arguments;
+        match request.command.as_str() {
+            "initialize" => {
+                self.send_response(
+                    &request,
+                    Ok(json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsFunctionBreakpoints": true,
+                        "supportsTerminateRequest": true,
+                    })),
+                )?;
+                // the client sends its breakpoints after this, and ends with `configurationDone`
+                self.send_event("initialized", Value::Null)
+            }
+            "launch" => {
+                let result = self.launch(arguments);
+                let launched = result.is_ok();
+                self.send_response(&request, result)?;
+                if launched {
+                    // the breakpoints set before are only checked against the program now
+                    let breakpoints: Vec<_> = self
+                        .line_breakpoints
+                        .iter()
+                        .map(|breakpoint| self.line_breakpoint_json(breakpoint))
+                        .collect();
+                    for breakpoint in breakpoints {
+                        let body = json!({ "reason": "changed", "breakpoint": breakpoint });
+                        self.send_event("breakpoint", body)?;
+                    }
+                    self.start()?;
+                }
+                Ok(())
+            }
+            "setBreakpoints" => {
+                let result = self.set_breakpoints(arguments);
+                self.send_response(&request, result)
+            }
+            "setFunctionBreakpoints" => {
+                let names: Vec<String> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["name"].as_str())
+                    .map(|name| name.to_string())
+                    .collect();
+                self.function_breakpoints = names
+                    .into_iter()
+                    .map(|name| (self.new_breakpoint_id(), name))
+                    .collect();
+                self.update_breakpoints();
+                let breakpoints: Vec<_> = self
+                    .function_breakpoints
+                    .iter()
+                    .map(|(id, _)| json!({ "id": id, "verified": true }))
+                    .collect();
+                self.send_response(&request, Ok(json!({ "breakpoints": breakpoints })))
+            }
+            "configurationDone" => {
+                self.configuration_done = true;
+                self.send_response(&request, Ok(Value::Null))?;
+                self.start()
+            }
+            "threads" => self.send_response(
+                &request,
+                Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            ),
+            "stackTrace" => {
+                let result = self.stack_trace();
+                self.send_response(&request, result)
+            }
+            "scopes" => {
+                let result = self.scopes(arguments);
+                self.send_response(&request, result)
+            }
+            "variables" => {
+                let result = self.variables(arguments);
+                self.send_response(&request, result)
+            }
+            "continue" => {
+                self.send_response(&request, Ok(json!({ "allThreadsContinued": true })))?;
+                self.resume(Resume::Continue)
+            }
+            "next" => {
+                self.send_response(&request, Ok(Value::Null))?;
+                self.resume(Resume::Next)
+            }
+            "stepIn" => {
+                self.send_response(&request, Ok(Value::Null))?;
+                self.resume(Resume::Step)
+            }
+            "stepOut" => {
+                self.send_response(&request, Ok(Value::Null))?;
+                self.resume(Resume::Finish)
+            }
+            "terminate" => {
+                self.debugger = None;
+                self.send_response(&request, Ok(Value::Null))?;
+                self.send_event("terminated", Value::Null)
+            }
+            command => {
+                let message = format!("unsupported command `{}`", command);
+                self.send_response(&request, Err(message))
+            }
+        }
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let Some(program) = arguments["program"].as_str() else {
+            return Err("expected the path of the program to debug".to_string());
+        };
+        let source_text = fs::read_to_string(program)
+            .map_err(|e| format!("failed to read {}: {}", program, e))?;
+        let input_file = self.db.new_input_file(program, source_text);
+
+        lox_compile::compile_file(self.db, input_file);
+        let diagnostics =
+            lox_compile::compile_file::accumulated::<Diagnostics>(self.db, input_file);
+        if !diagnostics.is_empty() {
+            let text = lox_error_format::format_diagnostics_with_options(
+                self.db,
+                &diagnostics,
+                FormatOptions::no_color(),
+            )
+            .unwrap();
+            return Err(text);
+        }
+
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+        self.debugger = Some(Debugger::new(self.db, input_file));
+        self.update_breakpoints();
+        Ok(Value::Null)
+    }
+
+    // Replaces the line breakpoints of a source. Only the launched program has breakpoints, and
+    // only on the lines that have instructions, the others are reported as unverified. The ones
+    // set before the launch are checked then, see the `launch` request.
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let Some(path) = arguments["source"]["path"].as_str() else {
+            return Err("expected the path of the source".to_string());
+        };
+        let lines: Vec<usize> = arguments["breakpoints"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|breakpoint| breakpoint["line"].as_u64())
+            .map(|line| line as usize)
+            .collect();
+
+        self.line_breakpoints
+            .retain(|breakpoint| !same_path(&breakpoint.path, path));
+        let first = self.line_breakpoints.len();
+        for line in lines {
+            let id = self.new_breakpoint_id();
+            let path = path.to_string();
+            self.line_breakpoints
+                .push(LineBreakpoint { id, path, line });
+        }
+        self.update_breakpoints();
+
+        let breakpoints: Vec<_> = self.line_breakpoints[first..]
+            .iter()
+            .map(|breakpoint| self.line_breakpoint_json(breakpoint))
+            .collect();
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    // the breakpoint as reported to the client, which tells whether it can be hit
+    fn line_breakpoint_json(&self, breakpoint: &LineBreakpoint) -> Value {
+        let message = match &self.debugger {
+            None => Some("the program is not launched yet"),
+            Some(_) if !self.is_program(&breakpoint.path) => {
+                Some("only the launched program can have breakpoints")
+            }
+            Some(debugger) if !debugger.has_code(breakpoint.line) => {
+                Some("there is no code on this line")
+            }
+            Some(_) => None,
+        };
+        let mut json = json!({
+            "id": breakpoint.id,
+            "verified": message.is_none(),
+            "line": breakpoint.line,
+        });
+        if let Some(message) = message {
+            json["message"] = json!(message);
+        }
+        json
+    }
+
+    // whether `path` is the launched program
+    fn is_program(&self, path: &str) -> bool {
+        self.debugger.as_ref().is_some_and(|debugger| {
+            same_path(path, debugger.input_file().name(self.db).as_str(self.db))
+        })
+    }
+
+    // the line breakpoints of the launched program, in the order the debugger numbers them
+    fn program_breakpoints(&self) -> impl Iterator<Item = &LineBreakpoint> {
+        self.line_breakpoints
+            .iter()
+            .filter(|breakpoint| self.is_program(&breakpoint.path))
+    }
+
+    fn update_breakpoints(&mut self) {
+        // the debugger numbers the breakpoints in this order, see `breakpoint_id`
+        let lines: Vec<_> = self
+            .program_breakpoints()
+            .map(|breakpoint| Breakpoint::Line(breakpoint.line))
+            .collect();
+        let functions = self
+            .function_breakpoints
+            .iter()
+            .map(|(_, name)| Breakpoint::Function(name.clone()));
+        let Some(debugger) = &mut self.debugger else {
+            return;
+        };
+        debugger.set_breakpoints(lines.into_iter().chain(functions));
+    }
+
+    fn new_breakpoint_id(&mut self) -> usize {
+        self.last_breakpoint_id += 1;
+        self.last_breakpoint_id
+    }
+
+    // the id reported to the client of the breakpoint that the debugger numbers `number`
+    fn breakpoint_id(&self, number: usize) -> usize {
+        let line_ids = self.program_breakpoints().map(|breakpoint| breakpoint.id);
+        let function_ids = self.function_breakpoints.iter().map(|&(id, _)| id);
+        line_ids.chain(function_ids).nth(number - 1).unwrap()
+    }
+
+    // Starts the program once it is launched and the client has sent its breakpoints.
+    fn start(&mut self) -> io::Result<()> {
+        let Some(debugger) = &self.debugger else {
+            return Ok(());
+        };
+        if !self.configuration_done || debugger.vm().instruction_count() > 0 {
+            return Ok(());
+        }
+        if self.stop_on_entry {
+            self.send_event(
+                "stopped",
+                json!({ "reason": "entry", "threadId": THREAD_ID }),
+            )
+        } else {
+            self.resume(Resume::Continue)
+        }
+    }
+
+    fn resume(&mut self, resume: Resume) -> io::Result<()> {
+        let Some(debugger) = &mut self.debugger else {
+            return Ok(());
+        };
+        if !debugger.is_running() {
+            return Ok(());
+        }
+        let stop = debugger.resume(resume, &mut self.kernel);
+
+        let output = std::mem::take(&mut self.kernel).take_buffer();
+        if !output.is_empty() {
+            self.send_event("output", json!({ "category": "stdout", "output": output }))?;
+        }
+
+        match stop {
+            Stop::Step => self.send_event(
+                "stopped",
+                json!({ "reason": "step", "threadId": THREAD_ID }),
+            ),
+            Stop::Breakpoint(number) => {
+                let id = self.breakpoint_id(number);
+                self.send_event(
+                    "stopped",
+                    json!({
+                        "reason": "breakpoint",
+                        "threadId": THREAD_ID,
+                        "hitBreakpointIds": [id],
+                    }),
+                )
+            }
+            Stop::Finished => self.exit(EXIT_SUCCESS),
+            Stop::Error(diagnostic) => {
+                let text = lox_error_format::format_diagnostics_with_options(
+                    self.db,
+                    &[diagnostic],
+                    FormatOptions::no_color(),
+                )
+                .unwrap();
+                self.send_event("output", json!({ "category": "stderr", "output": text }))?;
+                self.exit(EXIT_FAILURE)
+            }
+        }
+    }
+
+    fn exit(&mut self, exit_code: i64) -> io::Result<()> {
+        self.send_event("exited", json!({ "exitCode": exit_code }))?;
+        self.send_event("terminated", Value::Null)
+    }
+
+    fn running_debugger(&self) -> Result<&Debugger<'db>, String> {
+        match &self.debugger {
+            Some(debugger) if debugger.is_running() => Ok(debugger),
+            _ => Err("the program is not running".to_string()),
+        }
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let debugger = self.running_debugger()?;
+        let frames: Vec<_> = debugger
+            .vm()
+            .frames()
+            .enumerate()
+            .map(|(id, frame)| match frame.span() {
+                Some(span) => {
+                    // editors count the columns of the protocol in UTF-16 code units
+                    let (line, column) = line_index(self.db, span.input_file)
+                        .line_column_in(span.start, ColumnUnit::Utf16);
+                    let path = span.input_file.name(self.db).as_str(self.db);
+                    json!({
+                        "id": id,
+                        "name": frame.function_name(),
+                        "line": line + 1,
+                        "column": column + 1,
+                        "source": { "name": source_name(path), "path": path },
+                    })
+                }
+                None => json!({
+                    "id": id,
+                    "name": frame.function_name(),
+                    "line": 0,
+                    "column": 0,
+                }),
+            })
+            .collect();
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn scopes(&self, arguments: &Value) -> Result<Value, String> {
+        let debugger = self.running_debugger()?;
+        let frame_id = arguments["frameId"].as_i64().unwrap_or(0);
+        if frame_id < 0 || frame_id as usize >= debugger.vm().call_depth() {
+            return Err(format!("no frame with id {}", frame_id));
+        }
+        Ok(json!({
+            "scopes": [
+                {
+                    "name": "Locals",
+                    "presentationHint": "locals",
+                    "variablesReference": frame_id + 2,
+                    "expensive": false,
+                },
+                {
+                    "name": "Globals",
+                    "variablesReference": GLOBALS_REFERENCE,
+                    "expensive": false,
+                },
+            ]
+        }))
+    }
+
+    fn variables(&self, arguments: &Value) -> Result<Value, String> {
+        let debugger = self.running_debugger()?;
+        let reference = arguments["variablesReference"].as_i64().unwrap_or(0);
+        let variable = |name: &str, value: &lox_execute::Value| {
+            json!({
+                "name": name,
+                "value": format!("{:?}", value.debug(self.db)),
+                "variablesReference": 0,
+            })
+        };
+        let variables: Vec<_> = if reference == GLOBALS_REFERENCE {
+            debugger
+                .vm()
+                .globals()
+                .map(|(name, value)| variable(name.as_str(self.db), value))
+                .collect()
+        } else {
+            let Some(frame) = usize::try_from(reference - 2)
+                .ok()
+                .and_then(|id| debugger.vm().frames().nth(id))
+            else {
+                return Err(format!("no variables with reference {}", reference));
+            };
+            frame
+                .locals()
+                .into_iter()
+                .map(|(name, value)| variable(name, value))
+                .collect()
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn send_response(
+        &mut self,
+        request: &Request,
+        result: Result<Value, String>,
+    ) -> io::Result<()> {
+        self.seq += 1;
+        let mut response = json!({
+            "seq": self.seq,
+            "type": "response",
+            "request_seq": request.seq,
+            "command": request.command,
+        });
+        match result {
+            Ok(body) => {
+                response["success"] = json!(true);
+                if !body.is_null() {
+                    response["body"] = body;
+                }
+            }
+            Err(message) => {
+                response["success"] = json!(false);
+                response["message"] = json!(message);
+            }
+        }
+        write_message(&mut self.output, &response)
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.seq += 1;
+        let mut message = json!({ "seq": self.seq, "type": "event", "event": event });
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        write_message(&mut self.output, &message)
+    }
+}
+
+// Returns whether both paths name the same file, even if one of them is relative.
+fn same_path(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => Path::new(a) == Path::new(b),
+    }
+}
+
+fn source_name(path: &str) -> &str {
+    std::path::Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
+
+// Reads the body of the next message, returns `None` at the end of the input.
+// Every message starts with a `Content-Length` header, followed by an empty line.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(length) = line.strip_prefix("Content-Length:") {
+            let length = length.trim().parse::<usize>().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("invalid header: {}", e))
+            })?;
+            content_length = Some(length);
+        }
+    }
+
+    let mut body = vec![0; content_length.unwrap()];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_db::Database;
+    use serde_json::{json, Value};
+
+    use super::{read_message, DapServer};
+
+    const PROGRAM: &str = "\
+fun add(a, b) {
+    var sum = a + b;
+    return sum;
+}
+
+var x = add(1, 2);
+print x;
+";
+
+    // Runs the server on the scripted `requests`, and returns the messages it sent.
+    fn run(name: &str, requests: impl Fn(&str) -> Vec<Value>) -> Vec<Value> {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, PROGRAM).unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut input = vec![];
+        for (seq, mut request) in requests(path).into_iter().enumerate() {
+            request["seq"] = json!(seq + 1);
+            request["type"] = json!("request");
+            let body = request.to_string();
+            input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+        }
+
+        let db = Database::default();
+        let mut output = vec![];
+        DapServer::new(&db, &mut output)
+            .run(input.as_slice())
+            .unwrap();
+
+        let mut messages = vec![];
+        let mut output = output.as_slice();
+        while let Some(body) = read_message(&mut output).unwrap() {
+            messages.push(serde_json::from_slice(&body).unwrap());
+        }
+        messages
+    }
+
+    fn response<'a>(messages: &'a [Value], command: &str, nth: usize) -> &'a Value {
+        let response = messages
+            .iter()
+            .filter(|message| message["type"] == "response" && message["command"] == command)
+            .nth(nth)
+            .unwrap_or_else(|| panic!("no response to {} in {:#?}", command, messages));
+        assert_eq!(response["success"], true, "{:#?}", response);
+        &response["body"]
+    }
+
+    fn events<'a>(messages: &'a [Value], event: &str) -> Vec<&'a Value> {
+        messages
+            .iter()
+            .filter(|message| message["type"] == "event" && message["event"] == event)
+            .map(|message| &message["body"])
+            .collect()
+    }
+
+    #[test]
+    fn breakpoints_and_stepping() {
+        let messages = run("lox_dap_breakpoints.lox", |path| {
+            vec![
+                json!({ "command": "initialize", "arguments": { "adapterID": "lox" } }),
+                json!({
+                    "command": "setBreakpoints",
+                    "arguments": {
+                        "source": { "path": path },
+                        "breakpoints": [{ "line": 2 }, { "line": 5 }],
+                    },
+                }),
+                // only the launched program has breakpoints, this does not clear its own
+                json!({
+                    "command": "setBreakpoints",
+                    "arguments": {
+                        "source": { "path": "other.lox" },
+                        "breakpoints": [{ "line": 1 }],
+                    },
+                }),
+                json!({ "command": "launch", "arguments": { "program": path } }),
+                json!({ "command": "configurationDone" }),
+                json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+                json!({ "command": "scopes", "arguments": { "frameId": 0 } }),
+                json!({ "command": "variables", "arguments": { "variablesReference": 2 } }),
+                json!({ "command": "next", "arguments": { "threadId": 1 } }),
+                json!({ "command": "variables", "arguments": { "variablesReference": 2 } }),
+                json!({ "command": "stepOut", "arguments": { "threadId": 1 } }),
+                json!({ "command": "variables", "arguments": { "variablesReference": 1 } }),
+                json!({ "command": "continue", "arguments": { "threadId": 1 } }),
+                json!({ "command": "disconnect" }),
+            ]
+        });
+
+        // the client waits for `initialized` before it sends the breakpoints
+        assert_eq!(messages[0]["command"], "initialize");
+        assert_eq!(messages[1]["event"], "initialized");
+        assert_eq!(events(&messages, "initialized").len(), 1);
+
+        let not_launched = |id, line| {
+            json!({
+                "id": id,
+                "verified": false,
+                "line": line,
+                "message": "the program is not launched yet",
+            })
+        };
+        assert_eq!(
+            response(&messages, "setBreakpoints", 0)["breakpoints"],
+            json!([not_launched(1, 2), not_launched(2, 5)])
+        );
+        assert_eq!(
+            response(&messages, "setBreakpoints", 1)["breakpoints"],
+            json!([not_launched(3, 1)])
+        );
+        // the launch checks them against the program, line 5 is empty
+        let changed: Vec<_> = events(&messages, "breakpoint")
+            .into_iter()
+            .map(|body| {
+                assert_eq!(body["reason"], "changed");
+                &body["breakpoint"]
+            })
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                &json!({ "id": 1, "verified": true, "line": 2 }),
+                &json!({
+                    "id": 2,
+                    "verified": false,
+                    "line": 5,
+                    "message": "there is no code on this line",
+                }),
+                &json!({
+                    "id": 3,
+                    "verified": false,
+                    "line": 1,
+                    "message": "only the launched program can have breakpoints",
+                }),
+            ]
+        );
+
+        let stopped = events(&messages, "stopped");
+        assert_eq!(stopped[0]["reason"], "breakpoint");
+        assert_eq!(stopped[0]["hitBreakpointIds"], json!([1]));
+        assert_eq!(stopped[1]["reason"], "step");
+        assert_eq!(stopped[2]["reason"], "step");
+
+        let frames = &response(&messages, "stackTrace", 0)["stackFrames"];
+        assert_eq!(frames[0]["name"], "add");
+        assert_eq!(frames[0]["line"], 2);
+        assert_eq!(frames[0]["source"]["name"], "lox_dap_breakpoints.lox");
+        assert_eq!(frames[1]["name"], "main");
+        assert_eq!(frames[1]["line"], 6);
+
+        let scopes = &response(&messages, "scopes", 0)["scopes"];
+        assert_eq!(scopes[0]["variablesReference"], 2);
+        assert_eq!(scopes[1]["variablesReference"], 1);
+
+        let names_and_values = |nth| {
+            response(&messages, "variables", nth)["variables"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|variable| {
+                    format!(
+                        "{} = {}",
+                        variable["name"].as_str().unwrap(),
+                        variable["value"].as_str().unwrap()
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names_and_values(0), ["a = 1", "b = 2"]);
+        assert_eq!(names_and_values(1), ["a = 1", "b = 2", "sum = 3"]);
+        // the function has returned, but `x` is not assigned yet
+        assert_eq!(names_and_values(2), ["add = <fn add>"]);
+
+        assert_eq!(events(&messages, "output")[0]["output"], "3\n");
+        assert_eq!(events(&messages, "exited")[0]["exitCode"], 0);
+        assert_eq!(events(&messages, "terminated").len(), 1);
+    }
+
+    #[test]
+    fn function_breakpoint_and_stop_on_entry() {
+        let messages = run("lox_dap_entry.lox", |path| {
+            vec![
+                json!({ "command": "initialize", "arguments": { "adapterID": "lox" } }),
+                json!({
+                    "command": "launch",
+                    "arguments": { "program": path, "stopOnEntry": true },
+                }),
+                json!({
+                    "command": "setFunctionBreakpoints",
+                    "arguments": { "breakpoints": [{ "name": "add" }] },
+                }),
+                // setting line breakpoints later does not change the id of the function one
+                json!({
+                    "command": "setBreakpoints",
+                    "arguments": {
+                        "source": { "path": path },
+                        "breakpoints": [{ "line": 5 }],
+                    },
+                }),
+                json!({ "command": "configurationDone" }),
+                json!({ "command": "continue", "arguments": { "threadId": 1 } }),
+                json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+                json!({ "command": "pause", "arguments": { "threadId": 1 } }),
+                json!({ "command": "continue", "arguments": { "threadId": 1 } }),
+                json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+            ]
+        });
+
+        assert_eq!(
+            response(&messages, "setFunctionBreakpoints", 0)["breakpoints"],
+            json!([{ "id": 1, "verified": true }])
+        );
+        assert_eq!(
+            response(&messages, "setBreakpoints", 0)["breakpoints"][0]["id"],
+            2
+        );
+
+        let stopped = events(&messages, "stopped");
+        assert_eq!(stopped[0]["reason"], "entry");
+        assert_eq!(stopped[1]["reason"], "breakpoint");
+        assert_eq!(stopped[1]["hitBreakpointIds"], json!([1]));
+        assert_eq!(
+            response(&messages, "stackTrace", 0)["stackFrames"][0]["name"],
+            "add"
+        );
+
+        let failed: Vec<_> = messages
+            .iter()
+            .filter(|message| message["success"] == false)
+            .map(|message| message["command"].as_str().unwrap())
+            .collect();
+        assert_eq!(failed, ["pause", "stackTrace"]);
+        assert_eq!(events(&messages, "exited")[0]["exitCode"], 0);
+    }
+}
@@ -0,0 +1,536 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+};
+
+use lox_db::Database;
+use lox_error_format::FormatOptions;
+use lox_execute::{kernel::Kernel, ControlFlow, Observer, VM};
+use lox_ir::{
+    bytecode::{Code, CompiledFunction},
+    diagnostic::Diagnostic,
+    input_file::InputFile,
+    line_index::{line_index, LineIndex},
+    span::FileSpan,
+};
+use salsa::DebugWithDb;
+
+const HELP: &str = "\
+commands:
+  break <line>|<function>  stop when the line is reached or the function is called (b)
+  delete <breakpoint>      delete a breakpoint (d)
+  continue                 run until a breakpoint is hit (c)
+  step                     run until the next line, entering function calls (s)
+  next                     run until the next line, stepping over function calls (n)
+  finish                   run until the current function returns
+  locals                   print the local variables of the current function
+  globals                  print the global variables
+  stack                    print the values on the stack
+  backtrace                print the call stack (bt)
+  help                     print this message (h)
+  quit                     stop debugging (q)";
+
+/// An interactive debugger, which reads commands from its input and steps the VM accordingly.
+pub struct Debugger<'db> {
+    db: &'db Database,
+    input_file: InputFile,
+    vm: VM,
+
+    // the 1-based lines that have instructions, line breakpoints elsewhere are never hit
+    code_lines: BTreeSet<usize>,
+
+    // deleted breakpoints are kept as `None`, so that the numbers of the others do not change
+    breakpoints: Vec<Option<Breakpoint>>,
+
+    // whether the program has finished running
+    done: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops when the 1-based line is reached.
+    Line(usize),
+
+    /// Stops when the function is called.
+    Function(String),
+}
+
+// where the VM is stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Location {
+    depth: usize,
+
+    // 1-based line number of the next instruction, if the current frame has one
+    line: Option<usize>,
+}
+
+/// How far the VM runs when it is resumed.
+#[derive(Clone, Copy, Debug)]
+pub enum Resume {
+    /// Runs until a breakpoint is hit.
+    Continue,
+
+    /// Runs until the next line, entering function calls.
+    Step,
+
+    /// Runs until the next line, stepping over function calls.
+    Next,
+
+    /// Runs until the current function returns.
+    Finish,
+}
+
+/// Why the VM stopped after it was resumed.
+#[derive(Debug)]
+pub enum Stop {
+    /// The step requested by [`Resume`] is complete.
+    Step,
+
+    /// The breakpoint with this number was hit.
+    Breakpoint(usize),
+
+    /// The program has finished running.
+    Finished,
+
+    /// The program stopped because of an error.
+    Error(Diagnostic),
+}
+
+impl<'db> Debugger<'db> {
+    pub fn new(db: &'db Database, input_file: InputFile) -> Self {
+        let main = lox_execute::execute::main_function(db, input_file);
+        let mut code_lines = BTreeSet::new();
+        add_code_lines(
+            db,
+            line_index(db, input_file),
+            &lox_compile::compile_file(db, input_file),
+            &mut code_lines,
+        );
+        Self {
+            db,
+            input_file,
+            vm: VM::new(db, main, false),
+            code_lines,
+            breakpoints: vec![],
+            done: false,
+        }
+    }
+
+    /// Runs the debugger until the input ends or the `quit` command is read.
+    /// The program prints through `kernel`, the debugger itself prints to `output`.
+    pub fn run(
+        &mut self,
+        input: impl BufRead,
+        output: &mut impl Write,
+        kernel: &mut impl Kernel,
+    ) -> io::Result<()> {
+        self.print_location(output)?;
+        let mut lines = input.lines();
+        loop {
+            write!(output, "(lox) ")?;
+            output.flush()?;
+            let Some(line) = lines.next() else {
+                writeln!(output)?;
+                return Ok(());
+            };
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or_default();
+            let argument = words.next();
+            match command {
+                "" => {}
+                "break" | "b" => self.break_command(argument, output)?,
+                "delete" | "d" => self.delete_command(argument, output)?,
+                "continue" | "c" => self.resume_command(Resume::Continue, output, kernel)?,
+                "step" | "s" => self.resume_command(Resume::Step, output, kernel)?,
+                "next" | "n" => self.resume_command(Resume::Next, output, kernel)?,
+                "finish" => self.resume_command(Resume::Finish, output, kernel)?,
+                "locals" => self.print_locals(output)?,
+                "globals" => self.print_globals(output)?,
+                "stack" => self.print_stack(output)?,
+                "backtrace" | "bt" => self.print_backtrace(output)?,
+                "help" | "h" => writeln!(output, "{}", HELP)?,
+                "quit" | "q" => return Ok(()),
+                _ => writeln!(
+                    output,
+                    "unknown command `{}`, type `help` for a list of commands",
+                    command
+                )?,
+            }
+        }
+    }
+
+    fn break_command(&mut self, argument: Option<&str>, output: &mut impl Write) -> io::Result<()> {
+        let breakpoint = match argument {
+            None => return writeln!(output, "expected a line number or a function name"),
+            Some(argument) => match argument.parse::<usize>() {
+                Ok(line) => Breakpoint::Line(line),
+                Err(_) => Breakpoint::Function(argument.to_string()),
+            },
+        };
+        let number = self.add_breakpoint(breakpoint.clone());
+        match breakpoint {
+            Breakpoint::Line(line) => writeln!(output, "breakpoint {} at line {}", number, line),
+            Breakpoint::Function(name) => {
+                writeln!(output, "breakpoint {} at function `{}`", number, name)
+            }
+        }
+    }
+
+    /// Adds a breakpoint and returns its number.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(Some(breakpoint));
+        self.breakpoints.len()
+    }
+
+    /// Replaces all breakpoints, they are numbered from 1 in the order of `breakpoints`.
+    pub fn set_breakpoints(&mut self, breakpoints: impl IntoIterator<Item = Breakpoint>) {
+        self.breakpoints = breakpoints.into_iter().map(Some).collect();
+    }
+
+    fn delete_command(
+        &mut self,
+        argument: Option<&str>,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        let breakpoint = argument
+            .and_then(|argument| argument.parse::<usize>().ok())
+            .and_then(|number| self.breakpoints.get_mut(number.checked_sub(1)?))
+            .and_then(|breakpoint| breakpoint.take());
+        match breakpoint {
+            Some(_) => writeln!(output, "breakpoint {} deleted", argument.unwrap()),
+            None => writeln!(output, "no such breakpoint"),
+        }
+    }
+
+    /// Returns the file of the program being debugged.
+    pub fn input_file(&self) -> InputFile {
+        self.input_file
+    }
+
+    /// Returns whether the 1-based `line` has instructions, so that a breakpoint on it can be hit.
+    pub fn has_code(&self, line: usize) -> bool {
+        self.code_lines.contains(&line)
+    }
+
+    /// Returns the VM being debugged.
+    pub fn vm(&self) -> &VM {
+        &self.vm
+    }
+
+    /// Returns whether the program can still be resumed.
+    pub fn is_running(&self) -> bool {
+        !self.done
+    }
+
+    /// Returns the 1-based line and column of the start of `span`.
+    pub fn line_column(&self, span: FileSpan) -> (usize, usize) {
+        let (line, column) = line_index(self.db, span.input_file).line_column(span.start);
+        (line + 1, column + 1)
+    }
+
+    fn resume_command(
+        &mut self,
+        resume: Resume,
+        output: &mut impl Write,
+        kernel: &mut impl Kernel,
+    ) -> io::Result<()> {
+        if self.done {
+            return writeln!(output, "the program is not running");
+        }
+
+        match self.resume(resume, kernel) {
+            Stop::Step => self.print_location(output),
+            Stop::Breakpoint(number) => {
+                writeln!(output, "hit breakpoint {}", number)?;
+                self.print_location(output)
+            }
+            Stop::Finished => writeln!(output, "the program has finished"),
+            Stop::Error(diagnostic) => {
+                let text = lox_error_format::format_diagnostics_with_options(
+                    self.db,
+                    &[diagnostic],
+                    FormatOptions::no_color(),
+                )
+                .unwrap();
+                write!(output, "{}", text)
+            }
+        }
+    }
+
+    /// Runs the program until the step requested by `resume` is complete or a breakpoint is hit.
+    /// Must not be called after the program has finished or stopped because of an error.
+    pub fn resume(&mut self, resume: Resume, kernel: &mut impl Kernel) -> Stop {
+        assert!(!self.done, "the program is not running");
+
+        let start = location(self.db, &self.vm);
+        let mut stepper = Stepper {
+            db: self.db,
+            breakpoints: &self.breakpoints,
+            resume,
+            start,
+            previous: start,
+            stop: None,
+        };
+        match self.vm.run_with(self.db, kernel, &mut stepper) {
+            Ok(ControlFlow::Next) => stepper.stop.unwrap_or(Stop::Step),
+            Ok(ControlFlow::Done) => {
+                self.done = true;
+                Stop::Finished
+            }
+            Err(diagnostic) => {
+                self.done = true;
+                Stop::Error(diagnostic)
+            }
+        }
+    }
+
+    fn print_location(&self, output: &mut impl Write) -> io::Result<()> {
+        let Some(frame) = self.vm.frames().next() else {
+            return Ok(());
+        };
+        let Some(span) = frame.span() else {
+            return Ok(());
+        };
+        let (line, column) = self.line_column(span);
+        let source_text = span.input_file.source_text(self.db);
+        writeln!(
+            output,
+            "{} at {}:{}:{}",
+            frame.function_name(),
+            span.input_file.name(self.db).as_str(self.db),
+            line,
+            column
+        )?;
+        writeln!(
+            output,
+            "{:>4} | {}",
+            line,
+            source_text.lines().nth(line - 1).unwrap_or_default()
+        )
+    }
+
+    fn print_locals(&self, output: &mut impl Write) -> io::Result<()> {
+        let locals = match self.vm.frames().next() {
+            Some(frame) => frame.locals(),
+            None => vec![],
+        };
+        if locals.is_empty() {
+            return writeln!(output, "no local variables");
+        }
+        for (name, value) in locals {
+            writeln!(output, "  {} = {:?}", name, value.debug(self.db))?;
+        }
+        Ok(())
+    }
+
+    fn print_globals(&self, output: &mut impl Write) -> io::Result<()> {
+        let mut any = false;
+        for (name, value) in self.vm.globals() {
+            any = true;
+            writeln!(
+                output,
+                "  {} = {:?}",
+                name.as_str(self.db),
+                value.debug(self.db)
+            )?;
+        }
+        if !any {
+            writeln!(output, "no global variables")?;
+        }
+        Ok(())
+    }
+
+    fn print_stack(&self, output: &mut impl Write) -> io::Result<()> {
+        for (index, value) in self.vm.stack_values().iter().enumerate() {
+            writeln!(output, "  [{}] {:?}", index, value.debug(self.db))?;
+        }
+        Ok(())
+    }
+
+    fn print_backtrace(&self, output: &mut impl Write) -> io::Result<()> {
+        for (index, frame) in self.vm.frames().enumerate() {
+            match frame.span() {
+                Some(span) => {
+                    let (line, column) = self.line_column(span);
+                    writeln!(
+                        output,
+                        "#{} {} at {}:{}:{}",
+                        index,
+                        frame.function_name(),
+                        span.input_file.name(self.db).as_str(self.db),
+                        line,
+                        column
+                    )?;
+                }
+                None => writeln!(output, "#{} {}", index, frame.function_name())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Returns where `vm` is stopped.
+fn location(db: &Database, vm: &VM) -> Location {
+    let line = vm
+        .current_span()
+        .map(|span| line_index(db, span.input_file).line(span.start) + 1);
+    Location {
+        depth: vm.call_depth(),
+        line,
+    }
+}
+
+// Runs the VM until the step requested by `resume` is complete or a breakpoint is hit.
+struct Stepper<'a> {
+    db: &'a Database,
+    breakpoints: &'a [Option<Breakpoint>],
+    resume: Resume,
+
+    // where the VM was stopped when it was resumed, and after the last step
+    start: Location,
+    previous: Location,
+
+    // why the VM stopped, `None` while it keeps running
+    stop: Option<Stop>,
+}
+
+impl Stepper<'_> {
+    // Returns the number of the breakpoint that makes the VM stop at `location`, if any.
+    // Breakpoints are only hit when a line is entered, not when a call on the line returns.
+    fn breakpoint_hit(&self, vm: &VM, location: Location) -> Option<usize> {
+        let previous = self.previous;
+        let entered_frame = location.depth > previous.depth;
+        let entered_line =
+            entered_frame || (location.depth == previous.depth && location.line != previous.line);
+        let function = vm.frames().next()?.function_name();
+        let index = self
+            .breakpoints
+            .iter()
+            .position(|breakpoint| match breakpoint {
+                Some(Breakpoint::Line(line)) => entered_line && location.line == Some(*line),
+                Some(Breakpoint::Function(name)) => entered_frame && function == name,
+                None => false,
+            })?;
+        Some(index + 1)
+    }
+
+    // Returns whether the step requested by `resume` is complete once the VM is at `location`.
+    fn step_complete(&self, location: Location) -> bool {
+        let start = self.start;
+        match self.resume {
+            Resume::Continue => false,
+            Resume::Step => location.depth != start.depth || location.line != start.line,
+            Resume::Next => {
+                location.depth < start.depth
+                    || (location.depth == start.depth && location.line != start.line)
+            }
+            Resume::Finish => location.depth < start.depth,
+        }
+    }
+}
+
+impl<K> Observer<K> for Stepper<'_> {
+    fn after_step(&mut self, vm: &VM, _: &K, _: Option<&Code>) -> bool {
+        let location = location(self.db, vm);
+        if location.line.is_none() {
+            // the frame is about to return, there is no line to stop at
+            return true;
+        }
+
+        if let Some(number) = self.breakpoint_hit(vm, location) {
+            self.stop = Some(Stop::Breakpoint(number));
+        } else if self.step_complete(location) {
+            self.stop = Some(Stop::Step);
+        }
+        self.previous = location;
+        self.stop.is_none()
+    }
+}
+
+// Adds the 1-based lines of the instructions of `function`, and of the functions it declares.
+fn add_code_lines(
+    db: &Database,
+    line_index: &LineIndex,
+    function: &CompiledFunction,
+    lines: &mut BTreeSet<usize>,
+) {
+    let chunk = &function.chunk;
+    for ip in 0..chunk.len() {
+        lines.insert(line_index.line(chunk.span(ip).start) + 1);
+        if let Code::Function(function) = chunk.read_byte(ip) {
+            add_code_lines(
+                db,
+                line_index,
+                &lox_compile::compile_fn(db, function),
+                lines,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_db::Database;
+    use lox_execute::kernel::BufferKernel;
+
+    use super::Debugger;
+
+    const PROGRAM: &str = "\
+fun add(a, b) {
+    var sum = a + b;
+    return sum;
+}
+
+var x = add(1, 2);
+print x;
+";
+
+    fn debug(commands: &str) -> (String, String) {
+        let db = Database::default();
+        let input_file = db.new_input_file("test.lox", PROGRAM.to_string());
+        let mut output = vec![];
+        let mut kernel = BufferKernel::new();
+        Debugger::new(&db, input_file)
+            .run(commands.as_bytes(), &mut output, &mut kernel)
+            .unwrap();
+        (String::from_utf8(output).unwrap(), kernel.take_buffer())
+    }
+
+    #[test]
+    fn function_breakpoint_and_stepping() {
+        let (output, program_output) =
+            debug("break add\ncontinue\nlocals\nbacktrace\nnext\nlocals\nfinish\ncontinue\n");
+        let expected = [
+            "main at test.lox:1:1",
+            "breakpoint 1 at function `add`",
+            "hit breakpoint 1\nadd at test.lox:2:15\n   2 |     var sum = a + b;",
+            "  a = 1\n  b = 2\n(lox) ",
+            "#0 add at test.lox:2:15\n#1 main at test.lox:6:9",
+            "add at test.lox:3:12",
+            "  a = 1\n  b = 2\n  sum = 3",
+            "main at test.lox:6:1",
+            "the program has finished",
+        ];
+        let mut rest = output.as_str();
+        for expected in expected {
+            let Some(index) = rest.find(expected) else {
+                panic!("expected {:?} in:\n{}", expected, output);
+            };
+            rest = &rest[index + expected.len()..];
+        }
+        assert_eq!(program_output, "3\n");
+    }
+
+    #[test]
+    fn line_breakpoint() {
+        let (output, _) = debug("b 7\nc\nglobals\nc\nc\n");
+        assert!(
+            output.contains("hit breakpoint 1\nmain at test.lox:7:7"),
+            "{}",
+            output
+        );
+        assert!(output.contains("  add = <fn add>\n  x = 3"), "{}", output);
+        assert!(output.contains("the program is not running"), "{}", output);
+    }
+}
@@ -1,17 +1,35 @@
 use std::{
     fs,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use clap::{Parser, Subcommand};
+use coverage::Coverage;
 use expect_test::expect_file;
 use lox_db::Database;
-use lox_execute::kernel::{BufferKernel, StdoutKernel};
-use lox_ir::{diagnostic::Diagnostics, input_file::InputFile, word::Word};
+use lox_execute::{
+    kernel::{BufferKernel, StdoutKernel},
+    limits::DEFAULT_MAX_CALL_DEPTH,
+    ExecutionLimits,
+};
+use lox_ir::{
+    diagnostic::{Diagnostics, Severity},
+    input_file::InputFile,
+    word::Word,
+};
 use salsa::DebugWithDb;
 use tracing_subscriber::{filter::LevelFilter, fmt, prelude::*, EnvFilter};
 use walkdir::WalkDir;
 
+mod bench;
+mod check;
+mod coverage;
+mod dap;
+mod debug;
+mod profile;
+mod trace;
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
 struct TestCase {
     lox: PathBuf,
@@ -87,7 +105,7 @@ impl TestCase {
         path.strip_prefix(crate_root_dir).unwrap().to_owned()
     }
 
-    fn test(self, db: &Database) {
+    fn test(self, db: &Database, trace: bool, coverage: Option<&mut Coverage>) {
         print!("test {} ... ", self.lox.display());
         let relative_path = TestCase::relative_path(&self.lox);
         let input_file = InputFile::new(
@@ -97,11 +115,7 @@ impl TestCase {
         );
 
         // check if we should ignore this test
-        let ignore = input_file
-            .source_text(db)
-            .lines()
-            .any(|line| line.trim_start().starts_with("# ignore"));
-        if ignore {
+        if has_directive(input_file.source_text(db), "ignore") {
             println!("ignored");
             return;
         }
@@ -123,33 +137,52 @@ impl TestCase {
         let compiled_function = lox_compile::compile_file(db, input_file);
         expect_file![self.bytecode].assert_eq(&format!("{:#?}", compiled_function.debug(db)));
 
-        // test execute
-        // let buf = Arc::new(Mutex::new(String::new()));
-        // let step_inspect = |code: Option<bytecode::Code>, vm: &lox_execute::VM| {
-        //     let mut buf = buf.lock().unwrap();
-        //     buf.push_str(&format!("execute: {:#?}\n", code));
-        //     buf.push_str(&format!("stack: {:?}\n", &vm.stack_values()));
-        //     buf.push_str(&format!("stdout: {:#?}\n", vm.output));
-        //     buf.push('\n');
-        // };
-        // let output = lox_execute::execute_file(db, input_file, Some(step_inspect));
-        // expect_file![self.execute].assert_eq(&buf.lock().unwrap());
+        // test execute, only for the tests that ask for a trace, since it is long
+        if trace || has_directive(input_file.source_text(db), "trace") {
+            let execute = trace::trace_file(db, input_file);
+            expect_file![self.execute].assert_eq(&execute);
+        }
 
         // test stdout
         let mut kernel = BufferKernel::new();
-        lox_execute::execute_file(
-            db,
-            input_file,
-            &mut kernel,
-            false,
-            None::<fn(_, &lox_execute::VM)>,
-        );
+        match coverage {
+            Some(coverage) => coverage.run_file(
+                db,
+                input_file,
+                &mut kernel,
+                false,
+                ExecutionLimits::default(),
+            ),
+            None => {
+                lox_execute::execute_file(db, input_file, &mut kernel, false, &mut ());
+            }
+        }
         expect_file![self.stdout].assert_eq(kernel.buffer());
 
         println!("ok");
     }
 }
 
+// Returns whether a line of `source_text` is the directive `# {name}`, and nothing else, so that
+// comments like `# trace the loop` are not mistaken for directives.
+fn has_directive(source_text: &str, name: &str) -> bool {
+    source_text.lines().any(|line| {
+        line.trim()
+            .strip_prefix('#')
+            .is_some_and(|directive| directive.trim_start() == name)
+    })
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(1)
+}
+
+fn write_lcov(coverage: &Coverage, path: &Path) {
+    fs::write(path, coverage.lcov())
+        .unwrap_or_else(|e| panic!("failed to write {}: {}", path.display(), e));
+}
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
@@ -168,11 +201,102 @@ enum Commands {
         /// instead of validating the output, generate or update it
         #[arg(long)]
         bless: bool,
+
+        /// also check the `execute` snapshot, a trace of every instruction executed,
+        /// which is otherwise only checked for the files with a `# trace` directive
+        #[arg(long)]
+        trace: bool,
+
+        /// record the lines and branches executed by the tests, print a summary
+        /// and write an lcov file
+        #[arg(long)]
+        coverage: bool,
+
+        /// where `--coverage` writes the lcov file
+        #[arg(long, default_value = "lcov.info")]
+        lcov_file: PathBuf,
     },
 
     Run {
         /// path to lox file
         path: PathBuf,
+
+        /// stop the program after executing this many instructions
+        #[arg(long)]
+        max_instructions: Option<u64>,
+
+        /// report a stack overflow when the program nests more than this many function calls
+        #[arg(long, default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+        max_call_depth: usize,
+
+        /// stop the program when it keeps more than this many objects alive on the heap
+        #[arg(long)]
+        max_heap_objects: Option<usize>,
+
+        /// stop the program after running for this many milliseconds
+        #[arg(long)]
+        timeout_ms: Option<u64>,
+
+        /// count the instructions executed per opcode, function and line, measure the time
+        /// spent in each function, and print a report to stderr
+        #[arg(long, conflicts_with = "coverage")]
+        profile: bool,
+
+        /// where `--profile` writes the call stacks in the folded format of flame graph tools
+        #[arg(long, default_value = "profile.folded")]
+        folded_stacks: PathBuf,
+
+        /// record the lines and branches executed by the program, print a summary to stderr
+        /// and write an lcov file
+        #[arg(long)]
+        coverage: bool,
+
+        /// where `--coverage` writes the lcov file
+        #[arg(long, default_value = "lcov.info")]
+        lcov_file: PathBuf,
+    },
+
+    /// reports the compile errors of lox files without running them
+    Check {
+        /// paths to lox files or directories of them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// how the diagnostics are printed
+        #[arg(long, value_enum, default_value_t = check::Format::Text)]
+        format: check::Format,
+    },
+
+    /// runs a lox file under an interactive debugger
+    Debug {
+        /// path to lox file
+        path: PathBuf,
+    },
+
+    /// runs a Debug Adapter Protocol server over stdin and stdout, for debugging from editors
+    Dap,
+
+    /// runs benchmark programs and reports how long they take
+    Bench {
+        /// path to benchmark file or directory
+        #[arg(default_value = "lox_benches")]
+        path: PathBuf,
+
+        /// how many times each benchmark is run
+        #[arg(long, default_value_t = 5)]
+        iterations: usize,
+
+        /// save the results as a baseline file
+        #[arg(long)]
+        save_baseline: Option<PathBuf>,
+
+        /// compare the results against a baseline file
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+
+        /// percentage by which the median time may grow before it is flagged as a regression
+        #[arg(long, default_value_t = 10.0)]
+        threshold: f64,
     },
 }
 
@@ -191,22 +315,44 @@ fn main() {
     let db = Database::default();
 
     match cli.command {
-        Commands::Test { path, bless } => {
+        Commands::Test {
+            path,
+            bless,
+            trace,
+            coverage,
+            lcov_file,
+        } => {
             if bless {
                 // add `UPDATE_EXPECT` to the environment to update the expected output
                 std::env::set_var("UPDATE_EXPECT", "1");
             }
+            let mut coverage = coverage.then(Coverage::default);
             if path.is_dir() {
                 let test_cases = TestCase::list(&path);
                 for test_case in test_cases {
-                    test_case.test(&db);
+                    test_case.test(&db, trace, coverage.as_mut());
                 }
             } else {
                 let test_case = TestCase::new(&path);
-                test_case.test(&db);
+                test_case.test(&db, trace, coverage.as_mut());
+            }
+            if let Some(coverage) = coverage {
+                println!();
+                print!("{}", coverage.summary());
+                write_lcov(&coverage, &lcov_file);
             }
         }
-        Commands::Run { path } => {
+        Commands::Run {
+            path,
+            max_instructions,
+            max_call_depth,
+            max_heap_objects,
+            timeout_ms,
+            profile,
+            folded_stacks,
+            coverage,
+            lcov_file,
+        } => {
             let input_file = InputFile::new(
                 &db,
                 Word::intern(&db, path.to_str().unwrap()),
@@ -220,14 +366,124 @@ fn main() {
                     lox_error_format::print_diagnostic(&db, diagnostic).unwrap();
                 }
             } else {
-                lox_execute::execute_file(
+                let limits = ExecutionLimits {
+                    max_instructions,
+                    max_call_depth: Some(max_call_depth),
+                    max_heap_objects,
+                    timeout: timeout_ms.map(Duration::from_millis),
+                };
+                let mut kernel = StdoutKernel::new();
+                if profile {
+                    let profile = profile::profile_file(&db, input_file, &mut kernel, true, limits);
+                    eprint!("{}", profile.report(&db));
+                    fs::write(&folded_stacks, profile.folded_stacks()).unwrap_or_else(|e| {
+                        panic!("failed to write {}: {}", folded_stacks.display(), e)
+                    });
+                } else if coverage {
+                    let mut coverage = Coverage::default();
+                    coverage.run_file(&db, input_file, &mut kernel, true, limits);
+                    eprint!("{}", coverage.summary());
+                    write_lcov(&coverage, &lcov_file);
+                } else {
+                    lox_execute::execute_file_with_limits(
+                        &db,
+                        input_file,
+                        &mut kernel,
+                        true,
+                        limits,
+                        &mut (),
+                    );
+                }
+                if let Some(code) = kernel.exit_code() {
+                    std::process::exit(code);
+                }
+            }
+        }
+        Commands::Check { paths, format } => {
+            let mut diagnostics = vec![];
+            for path in paths.iter().flat_map(|path| check::lox_files(path)) {
+                let input_file = InputFile::new(
                     &db,
-                    input_file,
-                    &mut StdoutKernel {},
-                    true,
-                    None::<fn(_, &lox_execute::VM)>,
+                    Word::intern(&db, path.to_str().unwrap()),
+                    fs::read_to_string(&path)
+                        .unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e)),
                 );
+                diagnostics.extend(check::check_file(&db, input_file));
+            }
+            print!("{}", check::format_diagnostics(&db, &diagnostics, format));
+            if diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.severity == Severity::Error)
+            {
+                std::process::exit(1);
             }
         }
+        Commands::Debug { path } => {
+            let input_file = InputFile::new(
+                &db,
+                Word::intern(&db, path.to_str().unwrap()),
+                fs::read_to_string(&path).unwrap(),
+            );
+            lox_compile::compile_file(&db, input_file);
+            let diagnostics =
+                lox_compile::compile_file::accumulated::<Diagnostics>(&db, input_file);
+            if !diagnostics.is_empty() {
+                for diagnostic in &diagnostics {
+                    lox_error_format::print_diagnostic(&db, diagnostic).unwrap();
+                }
+            } else {
+                let mut debugger = debug::Debugger::new(&db, input_file);
+                debugger
+                    .run(
+                        std::io::stdin().lock(),
+                        &mut std::io::stdout(),
+                        &mut StdoutKernel::new(),
+                    )
+                    .unwrap();
+            }
+        }
+        Commands::Dap => {
+            dap::DapServer::new(&db, std::io::stdout().lock())
+                .run(std::io::stdin().lock())
+                .unwrap();
+        }
+        Commands::Bench {
+            path,
+            iterations,
+            save_baseline,
+            baseline,
+            threshold,
+        } => {
+            let results =
+                bench::run(&db, &path, iterations).unwrap_or_else(|e| exit_with_error(&e));
+            if let Some(save_baseline) = save_baseline {
+                results
+                    .save(&save_baseline)
+                    .unwrap_or_else(|e| exit_with_error(&e));
+            }
+            if let Some(baseline) = baseline {
+                let baseline =
+                    bench::BenchResults::load(&baseline).unwrap_or_else(|e| exit_with_error(&e));
+                let regressions = results.compare(&baseline, threshold);
+                if regressions > 0 {
+                    println!("{} benchmark(s) regressed", regressions);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::has_directive;
+
+    #[test]
+    fn directives_match_whole_lines() {
+        assert!(has_directive("# trace\nprint 1;", "trace"));
+        assert!(has_directive("print 1;\n  #trace  ", "trace"));
+        assert!(!has_directive("# tracer\nprint 1;", "trace"));
+        assert!(!has_directive("# trace the loop\nprint 1;", "trace"));
+        assert!(!has_directive("print 1; # trace", "trace"));
     }
 }
@@ -0,0 +1,381 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use lox_db::Database;
+use lox_execute::{kernel::Kernel, ExecutionLimits, Observer, VM};
+use lox_ir::{
+    bytecode::Code,
+    input_file::InputFile,
+    line_index::{line_index, LineIndex},
+};
+
+// the report only lists this many of the hottest lines
+const REPORT_LINES: usize = 20;
+
+/// What a profiled program spent its instructions and time on.
+pub struct Profile {
+    input_file: InputFile,
+    instructions: u64,
+    elapsed: Duration,
+
+    // the number of executed instructions of each opcode
+    opcodes: HashMap<&'static str, u64>,
+
+    // the number of executed instructions compiled from each 1-based line
+    lines: BTreeMap<usize, u64>,
+
+    // the tree of the call stacks the program went through, the root is the main function
+    calls: Vec<CallNode>,
+}
+
+struct CallNode {
+    function: String,
+    parent: Option<usize>,
+    children: HashMap<String, usize>,
+
+    // how many times this call stack was entered
+    calls: u64,
+
+    // the instructions executed and the time spent while this call stack was the current one
+    instructions: u64,
+    self_time: Duration,
+
+    // the time spent from entering this call stack until returning from it
+    total_time: Duration,
+}
+
+impl CallNode {
+    fn new(function: String, parent: Option<usize>) -> Self {
+        Self {
+            function,
+            parent,
+            children: HashMap::new(),
+            calls: 0,
+            instructions: 0,
+            self_time: Duration::ZERO,
+            total_time: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Default)]
+struct FunctionStats {
+    calls: u64,
+    instructions: u64,
+    self_time: Duration,
+    total_time: Duration,
+}
+
+/// Runs `input_file` like [`lox_execute::execute_file_with_limits`], and records which opcodes,
+/// functions and lines the instructions executed by the program belong to, and the time spent
+/// in every call stack.
+pub fn profile_file(
+    db: &Database,
+    input_file: InputFile,
+    kernel: &mut impl Kernel,
+    diagnostic_with_color: bool,
+    limits: ExecutionLimits,
+) -> Profile {
+    let mut main_node = CallNode::new("main".to_string(), None);
+    main_node.calls = 1;
+    let start = Instant::now();
+    let mut profiler = Profiler {
+        profile: Profile {
+            input_file,
+            instructions: 0,
+            elapsed: Duration::ZERO,
+            opcodes: HashMap::new(),
+            lines: BTreeMap::new(),
+            calls: vec![main_node],
+        },
+        line_index: line_index(db, input_file),
+        now: start,
+        stack: vec![(0, start)],
+        depth: 0,
+        line: None,
+    };
+
+    lox_execute::execute_file_with_limits(
+        db,
+        input_file,
+        kernel,
+        diagnostic_with_color,
+        limits,
+        &mut profiler,
+    );
+
+    // the frames left on the stack return when the program ends
+    let Profiler {
+        mut profile,
+        now,
+        stack,
+        ..
+    } = profiler;
+    for (node, entered) in stack {
+        profile.calls[node].total_time += now - entered;
+    }
+    profile.elapsed = now - start;
+    profile
+}
+
+struct Profiler<'db> {
+    profile: Profile,
+    line_index: &'db LineIndex,
+
+    // when the last step ended
+    now: Instant,
+
+    // the call nodes of the frames on the call stack, with the time they were entered
+    stack: Vec<(usize, Instant)>,
+
+    // the call depth and the 1-based line of the instruction being executed
+    depth: usize,
+    line: Option<usize>,
+}
+
+impl<K> Observer<K> for Profiler<'_> {
+    fn before_step(&mut self, vm: &VM) {
+        self.depth = vm.call_depth();
+        self.line = vm
+            .current_span()
+            .map(|span| self.line_index.line(span.start) + 1);
+    }
+
+    fn after_step(&mut self, vm: &VM, _: &K, instruction: Option<&Code>) -> bool {
+        let profile = &mut self.profile;
+        let end = Instant::now();
+        let (current, _) = *self.stack.last().unwrap();
+        profile.calls[current].self_time += end - self.now;
+        self.now = end;
+
+        // a step without an instruction returns from a function that ran out of instructions
+        if let Some(instruction) = instruction {
+            profile.instructions += 1;
+            profile.calls[current].instructions += 1;
+            *profile.opcodes.entry(instruction.name()).or_default() += 1;
+            if let Some(line) = self.line {
+                *profile.lines.entry(line).or_default() += 1;
+            }
+        }
+
+        if vm.call_depth() > self.depth {
+            let function = vm.frames().next().unwrap().function_name();
+            let callee = profile.child(current, function);
+            profile.calls[callee].calls += 1;
+            self.stack.push((callee, end));
+        } else if vm.call_depth() < self.depth {
+            let (node, entered) = self.stack.pop().unwrap();
+            profile.calls[node].total_time += end - entered;
+        }
+        true
+    }
+}
+
+impl Profile {
+    fn child(&mut self, parent: usize, function: &str) -> usize {
+        if let Some(&child) = self.calls[parent].children.get(function) {
+            return child;
+        }
+        let child = self.calls.len();
+        self.calls
+            .push(CallNode::new(function.to_string(), Some(parent)));
+        self.calls[parent]
+            .children
+            .insert(function.to_string(), child);
+        child
+    }
+
+    // Returns whether the call stack of `node` calls its function again further up.
+    fn is_recursive(&self, node: usize) -> bool {
+        let function = &self.calls[node].function;
+        let mut parent = self.calls[node].parent;
+        while let Some(node) = parent {
+            if self.calls[node].function == *function {
+                return true;
+            }
+            parent = self.calls[node].parent;
+        }
+        false
+    }
+
+    fn functions(&self) -> Vec<(&str, FunctionStats)> {
+        let mut functions: HashMap<&str, FunctionStats> = HashMap::new();
+        for (index, node) in self.calls.iter().enumerate() {
+            let stats = functions.entry(&node.function).or_default();
+            stats.calls += node.calls;
+            stats.instructions += node.instructions;
+            stats.self_time += node.self_time;
+
+            // the time of a recursive call is already part of the total time of the outer call
+            if !self.is_recursive(index) {
+                stats.total_time += node.total_time;
+            }
+        }
+        let mut functions: Vec<_> = functions.into_iter().collect();
+        functions.sort_by(|(a_name, a), (b_name, b)| {
+            b.self_time.cmp(&a.self_time).then(a_name.cmp(b_name))
+        });
+        functions
+    }
+
+    /// Renders the counts and times as a human readable report.
+    pub fn report(&self, db: &Database) -> String {
+        let mut report = String::new();
+        let percent = |count: u64| count as f64 * 100.0 / self.instructions.max(1) as f64;
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        writeln!(
+            report,
+            "executed {} instructions in {:.3}ms",
+            self.instructions,
+            ms(self.elapsed)
+        )
+        .unwrap();
+
+        writeln!(report, "\nfunctions:").unwrap();
+        writeln!(
+            report,
+            "  {:<20} {:>8} {:>14} {:>8} {:>12} {:>12}",
+            "function", "calls", "instructions", "%", "self", "total"
+        )
+        .unwrap();
+        for (function, stats) in self.functions() {
+            writeln!(
+                report,
+                "  {:<20} {:>8} {:>14} {:>7.2}% {:>10.3}ms {:>10.3}ms",
+                function,
+                stats.calls,
+                stats.instructions,
+                percent(stats.instructions),
+                ms(stats.self_time),
+                ms(stats.total_time)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nopcodes:").unwrap();
+        writeln!(
+            report,
+            "  {:<24} {:>14} {:>8}",
+            "opcode", "instructions", "%"
+        )
+        .unwrap();
+        let mut opcodes: Vec<_> = self.opcodes.iter().collect();
+        opcodes.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+        for (opcode, count) in opcodes {
+            writeln!(
+                report,
+                "  {:<24} {:>14} {:>7.2}%",
+                opcode,
+                count,
+                percent(*count)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nhottest lines:").unwrap();
+        writeln!(report, "  {:>6} {:>14} {:>8}", "line", "instructions", "%").unwrap();
+        let source_lines: Vec<_> = self.input_file.source_text(db).lines().collect();
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        lines.sort_by(|(a_line, a), (b_line, b)| b.cmp(a).then(a_line.cmp(b_line)));
+        for (line, count) in lines.into_iter().take(REPORT_LINES) {
+            writeln!(
+                report,
+                "  {:>6} {:>14} {:>7.2}%  {}",
+                line,
+                count,
+                percent(*count),
+                source_lines.get(line - 1).map_or("", |text| text.trim())
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    /// Renders the call stacks in the folded format read by flame graph tools: one line per
+    /// call stack, with the functions from the outermost to the innermost separated by `;`,
+    /// followed by the self time of the call stack in microseconds.
+    pub fn folded_stacks(&self) -> String {
+        let mut lines = vec![];
+        for node in &self.calls {
+            let micros = node.self_time.as_micros();
+            if micros == 0 {
+                continue;
+            }
+            let mut functions = vec![node.function.as_str()];
+            let mut parent = node.parent;
+            while let Some(node) = parent {
+                functions.push(&self.calls[node].function);
+                parent = self.calls[node].parent;
+            }
+            functions.reverse();
+            lines.push(format!("{} {}", functions.join(";"), micros));
+        }
+        lines.sort();
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_db::Database;
+    use lox_execute::{kernel::BufferKernel, ExecutionLimits};
+
+    use super::profile_file;
+
+    #[test]
+    fn counts() {
+        let db = Database::default();
+        let input_file = db.new_input_file(
+            "test.lox",
+            "\
+fun fib(n) {
+    if (n <= 1) { return n; }
+    return fib(n - 1) + fib(n - 2);
+}
+print fib(5);
+"
+            .to_string(),
+        );
+        let mut kernel = BufferKernel::new();
+        let profile = profile_file(
+            &db,
+            input_file,
+            &mut kernel,
+            false,
+            ExecutionLimits::default(),
+        );
+        assert_eq!(kernel.take_buffer(), "5\n");
+
+        let functions = profile.functions();
+        let fib = &functions
+            .iter()
+            .find(|(function, _)| *function == "fib")
+            .unwrap()
+            .1;
+        assert_eq!(fib.calls, 15);
+        let main = &functions
+            .iter()
+            .find(|(function, _)| *function == "main")
+            .unwrap()
+            .1;
+        assert_eq!(main.calls, 1);
+        assert_eq!(fib.instructions + main.instructions, profile.instructions);
+
+        // one `call` instruction for `fib(5)` in main, and 14 for the recursive calls in `fib`
+        assert_eq!(profile.opcodes["call"], 15);
+        assert_eq!(profile.lines.values().sum::<u64>(), profile.instructions);
+        assert!(profile.lines[&2] > profile.lines[&5]);
+
+        // the call tree has a node for every depth of the recursion
+        let depths = profile
+            .calls
+            .iter()
+            .filter(|node| node.function == "fib")
+            .count();
+        assert_eq!(depths, 5);
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt::Write;
+
+use lox_db::Database;
+use lox_error_format::FormatOptions;
+use lox_execute::{kernel::BufferKernel, ControlFlow, Observer, VM};
+use lox_ir::{bytecode::Code, input_file::InputFile};
+use salsa::DebugWithDb;
+
+/// Runs `input_file` and records every instruction it executes, which is how VM changes
+/// are reviewed in the `execute` snapshots of the tests.
+///
+/// Each instruction is followed by the values on the stack, grouped by the call frame that
+/// introduced them. Calls, returns and printed lines are recorded where they happen.
+pub fn trace_file(db: &Database, input_file: InputFile) -> String {
+    let main = lox_execute::execute::main_function(db, input_file);
+    let mut vm = VM::new(db, main, false);
+    let mut kernel = BufferKernel::new();
+    let mut tracer = Tracer {
+        db,
+        trace: String::new(),
+        depth: 0,
+        function: String::new(),
+        printed: 0,
+    };
+
+    loop {
+        match vm.run_with(db, &mut kernel, &mut tracer) {
+            Ok(ControlFlow::Next) => {}
+            Ok(ControlFlow::Done) => {
+                writeln!(tracer.trace, "-- return from {}", tracer.function).unwrap();
+                return tracer.trace;
+            }
+            Err(diagnostic) => {
+                let output = lox_error_format::format_diagnostics_with_options(
+                    db,
+                    &[diagnostic],
+                    FormatOptions::no_color(),
+                )
+                .unwrap();
+                write!(tracer.trace, "-- error\n{}", output).unwrap();
+                return tracer.trace;
+            }
+        }
+    }
+}
+
+struct Tracer<'db> {
+    db: &'db Database,
+    trace: String,
+
+    // the call depth and the function of the instruction being executed
+    depth: usize,
+    function: String,
+
+    // the length of the output printed by the program so far
+    printed: usize,
+}
+
+impl Observer<BufferKernel> for Tracer<'_> {
+    fn before_step(&mut self, vm: &VM) {
+        self.depth = vm.call_depth();
+        self.function = vm.frames().next().unwrap().function_name().to_string();
+    }
+
+    fn after_step(&mut self, vm: &VM, kernel: &BufferKernel, instruction: Option<&Code>) -> bool {
+        let trace = &mut self.trace;
+        match instruction {
+            Some(instruction) => {
+                writeln!(trace, "{}: {:?}", self.function, instruction.debug(self.db))
+            }
+            None => writeln!(trace, "{}: end of function", self.function),
+        }
+        .unwrap();
+
+        for line in kernel.buffer()[self.printed..].lines() {
+            writeln!(trace, "  stdout: {:?}", line).unwrap();
+        }
+        self.printed = kernel.buffer().len();
+
+        // the program has finished, `trace_file` records the last return
+        if vm.call_depth() == 0 {
+            return true;
+        }
+        if vm.call_depth() > self.depth {
+            let callee = vm.frames().next().unwrap().function_name();
+            writeln!(trace, "-- call {}", callee).unwrap();
+        } else if vm.call_depth() < self.depth {
+            let caller = vm.frames().next().unwrap().function_name();
+            writeln!(trace, "-- return to {}", caller).unwrap();
+        }
+        writeln!(trace, "  stack: {}", format_stack(self.db, vm)).unwrap();
+        true
+    }
+}
+
+// Renders the values on the stack, with the values of each call frame in their own brackets.
+fn format_stack(db: &Database, vm: &VM) -> String {
+    let stack = vm.stack_values();
+    let mut starts: Vec<usize> = vm.frames().map(|frame| frame.stack_start()).collect();
+    starts.reverse();
+    starts.push(stack.len());
+
+    starts
+        .windows(2)
+        .map(|frame| {
+            let values: Vec<_> = stack[frame[0]..frame[1]]
+                .iter()
+                .map(|value| format!("{:?}", value.debug(db)))
+                .collect();
+            format!("[{}]", values.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
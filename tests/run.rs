@@ -0,0 +1,18 @@
+use std::process::Command;
+
+// `lox run` prints through `StdoutKernel`, and the test harness records the `output` snapshots
+// through `BufferKernel`, so both must end every printed value with a newline
+#[test]
+fn run_prints_like_the_test_harness() {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(["run", "lox_tests/var.lox"])
+        .current_dir(root)
+        .env_remove("RUST_LOG")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let expected = std::fs::read_to_string(format!("{}/lox_tests/var/output", root)).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}